@@ -1,4 +1,11 @@
-use crate::BuildOptions;
+use crate::{
+    coverage,
+    instrument::{InstrumentedHost, RandomInput, TestLimits},
+    json_schema,
+    profile::{self, ProbeTransport, Profile},
+    test_report::{HarnessOutput, TestCase, TestStatus},
+    BuildOptions, TestOptions,
+};
 use ansi_term::{Color, Style};
 use anyhow::Context;
 use base64::{engine::general_purpose, Engine as _};
@@ -12,12 +19,13 @@ use concordium_base::{
         },
         OwnedEntrypointName,
     },
+    hashes,
     smart_contracts::{ContractName, ReceiveName, WasmModule},
 };
 use concordium_smart_contract_engine::{
     utils::{self, NoDuplicateImport, ReportError, TestHost, WasmVersion, BUILD_INFO_SECTION_NAME},
     v0,
-    v1::{self, trie, InstanceState},
+    v1::{self, trie, EmittedDebugStatement, InstanceState},
     ExecResult,
 };
 use concordium_wasm::{
@@ -27,6 +35,7 @@ use concordium_wasm::{
     types::{CustomSection, ExportDescription, Module, Name},
     utils::strip,
     validate::{validate_module, ValidationConfig},
+    CostConfigurationV1,
 };
 use rand::{prelude::*, thread_rng, Rng};
 use rayon::prelude::*;
@@ -36,10 +45,12 @@ use std::{
     cmp::Ordering,
     collections::{BTreeMap, BTreeSet},
     env, fs,
-    io::Write,
+    io::{BufRead, BufReader, Write},
     path::{Path, PathBuf},
     process::{Command, Stdio},
     str,
+    sync::Mutex,
+    time::{Duration, Instant},
 };
 
 /// Encode all base64 strings using the standard alphabet and padding.
@@ -126,6 +137,11 @@ pub struct BuildInfo {
     pub stored_build_info: Option<(utils::VersionedBuildInfo, Vec<PathBuf>)>,
     /// The path to the file of the built module.
     pub out_filename: PathBuf,
+    /// The contracts of the module, with the names of their entrypoints.
+    pub contracts: BTreeMap<String, Vec<String>>,
+    /// The reference of the module, which identifies it on the chain. This is
+    /// the hash of the versioned module written to `out_filename`.
+    pub module_ref: hashes::ModuleReference,
 }
 
 /// Result of [`create_archive`]. It contains the actual archive with a
@@ -138,6 +154,84 @@ pub struct TarArchiveData {
     archived_files: Vec<PathBuf>,
 }
 
+/// The packages selected with `--workspace` or `--package`, in the order of
+/// the members of the workspace. With `--workspace` only the members that are
+/// smart contracts are selected. Returns `None` if no packages are selected, in
+/// which case the package of the manifest is built.
+pub(crate) fn selected_packages(options: &BuildOptions) -> anyhow::Result<Option<Vec<Package>>> {
+    if !options.selects_packages() {
+        return Ok(None);
+    }
+    let (metadata, _) = get_crate_metadata(&options.cargo_args)?;
+    let members = metadata.workspace_packages();
+    for name in options.packages.iter() {
+        anyhow::ensure!(
+            members.iter().any(|package| &package.name == name),
+            "Package '{}' is not a member of the workspace.",
+            name
+        );
+    }
+    let packages: Vec<Package> = members
+        .into_iter()
+        .filter(|package| {
+            if options.workspace {
+                is_contract_package(package)
+            } else {
+                options.packages.contains(&package.name)
+            }
+        })
+        .cloned()
+        .collect();
+    anyhow::ensure!(
+        !packages.is_empty(),
+        "The workspace has no smart contract packages."
+    );
+    Ok(Some(packages))
+}
+
+/// Whether the package is a smart contract, i.e., it depends on
+/// `concordium-std` and has a library built as a `cdylib`.
+fn is_contract_package(package: &Package) -> bool {
+    package
+        .dependencies
+        .iter()
+        .any(|dependency| dependency.name == "concordium-std")
+        && package
+            .targets
+            .iter()
+            .any(|target| target.crate_types.iter().any(|ty| ty == "cdylib"))
+}
+
+/// Replace the `--manifest-path` in the extra arguments to cargo with the
+/// manifest of the given package.
+pub(crate) fn with_manifest_path(cargo_args: &[String], package: &Package) -> Vec<String> {
+    let mut args = vec![
+        "--manifest-path".to_string(),
+        package.manifest_path.to_string(),
+    ];
+    let mut rest = cargo_args.iter();
+    while let Some(arg) = rest.next() {
+        if arg == "--manifest-path" {
+            rest.next();
+        } else if !arg.starts_with("--manifest-path=") {
+            args.push(arg.clone());
+        }
+    }
+    args
+}
+
+/// Walk the files of the package, in the order of their paths. Files that are
+/// ignored by Git are skipped, as are hidden files.
+pub(crate) fn package_files(package_root_path: &Path) -> ignore::Walk {
+    ignore::WalkBuilder::new(package_root_path)
+        .git_global(true)
+        .git_ignore(true)
+        .parents(true)
+        .hidden(true)
+        .sort_by_file_path(std::cmp::Ord::cmp)
+        .build()
+}
+
 /// Make a tarball of the package at the `package_root_path` location.
 /// This takes an additional `omit_files` list that is the list of files that
 /// will not be included in the archive.
@@ -151,14 +245,7 @@ fn create_archive(
     let in_package_root_dir = std::path::Path::new(package_version_string);
     let mut tar = tar::Builder::new(Vec::new());
     tar.mode(tar::HeaderMode::Deterministic);
-    // Ignore files that are ignored by Git.
-    let files = ignore::WalkBuilder::new(package_root_path)
-        .git_global(true)
-        .git_ignore(true)
-        .parents(true)
-        .hidden(true)
-        .sort_by_file_path(std::cmp::Ord::cmp)
-        .build();
+    let files = package_files(package_root_path);
     let mut lock_file_found = false;
     let mut archived_files = Vec::new();
     for file in files {
@@ -261,8 +348,9 @@ struct ContainerBuildOutput {
 /// tar archive. The arguments are
 ///
 /// - `image`, the docker image that will be used to build.
-/// - `package`, the package being built.
-/// - `package_root_path`, the root path of the package being built.
+/// - `package`, the package being built. The archive contains the whole
+///   workspace of the package, since the `Cargo.lock` file is at the root of
+///   the workspace, and the package is selected by the build command.
 /// - `cargo_extra_args`, the extra arguments to pass to the cargo build command.
 /// - `container_runtime`, the container runtime to use, e.g. `docker` or
 ///   `podman`
@@ -274,7 +362,6 @@ struct ContainerBuildOutput {
 fn build_in_container(
     image: String,
     package: &Package,
-    package_root_path: &Path,
     cargo_extra_args: &[String],
     container_runtime: &str,
     out_path: &Path,
@@ -286,7 +373,7 @@ fn build_in_container(
     let target_dir = metadata.target_directory.as_std_path();
     let package_version_string = format!("{}-{}", package.name, package.version);
     let tar_archive = create_archive(
-        package_root_path,
+        metadata.workspace_root.as_std_path(),
         &package_version_string,
         &[out_path, tar_path, target_dir],
     )?;
@@ -299,6 +386,8 @@ fn build_in_container(
         features: &[],
         package,
         cargo_extra_args: &extra_args_without_manifest,
+        keep_names: false,
+        debug_info: false,
     }
     .get_cargo_cmd_as_strings()?;
 
@@ -506,7 +595,6 @@ pub(crate) fn build_contract(
         } = build_in_container(
             image,
             package,
-            &package_root_path,
             cargo_extra_args,
             &container_runtime,
             &out_filename,
@@ -529,6 +617,8 @@ pub(crate) fn build_contract(
             features: &[],
             package,
             cargo_extra_args: &[],
+            keep_names: false,
+            debug_info: false,
         }
         .run_cargo_cmd(options.skip_wasm_opt)?;
 
@@ -540,7 +630,7 @@ pub(crate) fn build_contract(
 
     // Remove all custom sections to reduce the size of the module
     strip(&mut skeleton);
-    match options.version {
+    let contracts = match options.version {
         WasmVersion::V0 => {
             let module = validate_module(
                 ValidationConfig::V0,
@@ -549,8 +639,7 @@ pub(crate) fn build_contract(
             )
             .context("Could not validate resulting smart contract module as a V0 contract.")?;
             check_exports(&module, WasmVersion::V0)
-                .context("Contract and entrypoint validation failed for a V0 contract.")?;
-            module
+                .context("Contract and entrypoint validation failed for a V0 contract.")?
         }
         WasmVersion::V1 => {
             let module = validate_module(
@@ -563,8 +652,7 @@ pub(crate) fn build_contract(
             )
             .context("Could not validate resulting smart contract module as a V1 contract.")?;
             check_exports(&module, WasmVersion::V1)
-                .context("Contract and entrypoint validation failed for a V1 contract.")?;
-            module
+                .context("Contract and entrypoint validation failed for a V1 contract.")?
         }
     };
 
@@ -601,6 +689,12 @@ pub(crate) fn build_contract(
     (output_bytes[4..8]).copy_from_slice(&data_size.to_be_bytes());
 
     let total_module_len = output_bytes.len();
+    // The reference is computed the same way as for `cargo concordium module-ref`.
+    let module_ref = WasmModule {
+        version: options.version,
+        source: output_bytes[8..].to_vec().into(),
+    }
+    .get_module_ref();
     fs::write(&out_filename, output_bytes).context("Unable to write final module.")?;
 
     // File name cannot be canonicalized before the file exists, so we do it here.
@@ -611,12 +705,18 @@ pub(crate) fn build_contract(
         stored_build_info: stored_build_info.map(|(bi, a)| (bi, a.archived_files)),
         metadata: metadata.clone(),
         out_filename,
+        contracts,
+        module_ref,
     })
 }
 
 /// Check that exports of module conform to the specification so that they will
-/// be accepted by the chain.
-fn check_exports(module: &Module, version: WasmVersion) -> anyhow::Result<()> {
+/// be accepted by the chain. Returns the contracts of the module, with the
+/// names of their entrypoints.
+pub(crate) fn check_exports(
+    module: &Module,
+    version: WasmVersion,
+) -> anyhow::Result<BTreeMap<String, Vec<String>>> {
     // collect contracts in the module.
     let mut contracts = BTreeSet::new();
     let mut methods = BTreeMap::<_, BTreeSet<OwnedEntrypointName>>::new();
@@ -643,6 +743,16 @@ fn check_exports(module: &Module, version: WasmVersion) -> anyhow::Result<()> {
             }
         }
     }
+    let exported = contracts
+        .iter()
+        .map(|cn| {
+            let entrypoints = methods
+                .get(cn)
+                .map(|ens| ens.iter().map(|en| en.to_string()).collect())
+                .unwrap_or_default();
+            (cn.to_string(), entrypoints)
+        })
+        .collect();
     for (cn, _ens) in methods {
         if let Some(closest) = find_closest(contracts.iter().copied(), cn) {
             if closest.is_empty() {
@@ -673,7 +783,7 @@ fn check_exports(module: &Module, version: WasmVersion) -> anyhow::Result<()> {
             }
         }
     }
-    Ok(())
+    Ok(exported)
 }
 
 /// Find the string closest to the list of strings. If an exact match is found
@@ -730,6 +840,8 @@ pub fn build_contract_schema<A>(
         features: &["concordium-std/build-schema"],
         package,
         cargo_extra_args,
+        keep_names: false,
+        debug_info: false,
     }
     .run_cargo_cmd(skip_wasm_opt)?;
     let schema =
@@ -893,6 +1005,16 @@ pub fn write_schema_base64(
     Ok(())
 }
 
+/// How the types of a schema are represented in its JSON representation.
+#[derive(Debug, Clone, Copy)]
+pub enum SchemaTypeFormat {
+    /// The base64 encoding of the schema of the type.
+    Base64,
+    /// A JSON Schema (draft 2020-12) document describing the JSON
+    /// representation of values of the type.
+    JsonSchema,
+}
+
 /// Converts the ContractV0 schema of the given contract_name to JSON and writes
 /// it to a file named after the smart contract name at the specified location.
 pub fn write_json_schema_to_file_v0(
@@ -900,18 +1022,19 @@ pub fn write_json_schema_to_file_v0(
     contract_name: &str,
     contract_counter: usize,
     contract_schema: &ContractV0,
+    format: SchemaTypeFormat,
 ) -> anyhow::Result<()> {
     // create empty schema_json
     let mut schema_json: Value = Value::Object(serde_json::Map::new());
 
     // add init schema
     if let Some(init_schema) = &contract_schema.init {
-        schema_json["init"] = type_to_json(init_schema);
+        schema_json["init"] = type_to_json(init_schema, format);
     }
 
     // add state schema
     if let Some(state_schema) = &contract_schema.state {
-        schema_json["state"] = type_to_json(state_schema);
+        schema_json["state"] = type_to_json(state_schema, format);
     }
 
     // add receive entrypoints
@@ -922,7 +1045,7 @@ pub fn write_json_schema_to_file_v0(
         // iterate through the entrypoints and add their schemas
         for (method_name, receive_schema) in contract_schema.receive.iter() {
             // add `method_name` entrypoint
-            entrypoints[method_name] = type_to_json(receive_schema);
+            entrypoints[method_name] = type_to_json(receive_schema, format);
         }
 
         // add all receive entrypoints
@@ -932,18 +1055,18 @@ pub fn write_json_schema_to_file_v0(
     write_schema_json(path_of_out, contract_name, contract_counter, schema_json)
 }
 
-fn function_v1_schema(schema: &FunctionV1) -> Value {
+fn function_v1_schema(schema: &FunctionV1, format: SchemaTypeFormat) -> Value {
     // create empty function object
     let mut function_object: Value = Value::Object(serde_json::Map::new());
 
     // add parameter schema to function object
     if let Some(parameter_schema) = &schema.parameter() {
-        function_object["parameter"] = type_to_json(parameter_schema);
+        function_object["parameter"] = type_to_json(parameter_schema, format);
     }
 
     // add return_value schema to function object
     if let Some(return_value_schema) = &schema.return_value() {
-        function_object["returnValue"] = type_to_json(return_value_schema);
+        function_object["returnValue"] = type_to_json(return_value_schema, format);
     }
     function_object
 }
@@ -955,13 +1078,14 @@ pub fn write_json_schema_to_file_v1(
     contract_name: &str,
     contract_counter: usize,
     contract_schema: &ContractV1,
+    format: SchemaTypeFormat,
 ) -> anyhow::Result<()> {
     // create empty schema_json
     let mut schema_json: Value = Value::Object(serde_json::Map::new());
 
     // add init schema
     if let Some(init_schema) = &contract_schema.init {
-        schema_json["init"] = function_v1_schema(init_schema);
+        schema_json["init"] = function_v1_schema(init_schema, format);
     }
 
     // add receive entrypoints
@@ -972,7 +1096,7 @@ pub fn write_json_schema_to_file_v1(
         // iterate through the entrypoints and add their schemas
         for (method_name, receive_schema) in contract_schema.receive.iter() {
             // add `method_name` entrypoint
-            entrypoints[method_name] = function_v1_schema(receive_schema);
+            entrypoints[method_name] = function_v1_schema(receive_schema, format);
         }
 
         // add all receive entrypoints
@@ -982,29 +1106,33 @@ pub fn write_json_schema_to_file_v1(
     write_schema_json(path_of_out, contract_name, contract_counter, schema_json)
 }
 
-/// Convert a [schema type](schema::Type) to a base64 string.
-fn type_to_json(ty: &schema::Type) -> Value {
-    ENCODER.encode(contracts_common::to_bytes(ty)).into()
+/// Convert a [schema type](schema::Type) to a base64 string, or to a JSON
+/// Schema document.
+fn type_to_json(ty: &schema::Type, format: SchemaTypeFormat) -> Value {
+    match format {
+        SchemaTypeFormat::Base64 => ENCODER.encode(contracts_common::to_bytes(ty)).into(),
+        SchemaTypeFormat::JsonSchema => json_schema::json_schema_document(ty),
+    }
 }
 
 /// Convert a [`FunctionV2`] schema to a JSON representation.
-fn function_v2_schema(schema: &FunctionV2) -> Value {
+fn function_v2_schema(schema: &FunctionV2, format: SchemaTypeFormat) -> Value {
     // create empty object
     let mut function_object: Value = Value::Object(serde_json::Map::new());
 
     // add parameter schema
     if let Some(parameter_schema) = &schema.parameter {
-        function_object["parameter"] = type_to_json(parameter_schema);
+        function_object["parameter"] = type_to_json(parameter_schema, format);
     }
 
     // add return_value schema
     if let Some(return_value_schema) = &schema.return_value {
-        function_object["returnValue"] = type_to_json(return_value_schema);
+        function_object["returnValue"] = type_to_json(return_value_schema, format);
     }
 
     // add error schema
     if let Some(error_schema) = &schema.error {
-        function_object["error"] = type_to_json(error_schema);
+        function_object["error"] = type_to_json(error_schema, format);
     }
     function_object
 }
@@ -1016,13 +1144,14 @@ pub fn write_json_schema_to_file_v2(
     contract_name: &str,
     contract_counter: usize,
     contract_schema: &ContractV2,
+    format: SchemaTypeFormat,
 ) -> anyhow::Result<()> {
     // create empty schema_json
     let mut schema_json: Value = Value::Object(serde_json::Map::new());
 
     // add init schema
     if let Some(init_schema) = &contract_schema.init {
-        schema_json["init"] = function_v2_schema(init_schema);
+        schema_json["init"] = function_v2_schema(init_schema, format);
     }
 
     // add receive entrypoints
@@ -1033,7 +1162,7 @@ pub fn write_json_schema_to_file_v2(
         // iterate through the entrypoints and add their schemas
         for (method_name, receive_schema) in contract_schema.receive.iter() {
             // add `method_name` entrypoint
-            entrypoints[method_name] = function_v2_schema(receive_schema)
+            entrypoints[method_name] = function_v2_schema(receive_schema, format)
         }
 
         // add all receive entrypoints
@@ -1050,18 +1179,19 @@ pub fn write_json_schema_to_file_v3(
     contract_name: &str,
     contract_counter: usize,
     contract_schema: &ContractV3,
+    format: SchemaTypeFormat,
 ) -> anyhow::Result<()> {
     // create empty schema_json
     let mut schema_json: Value = Value::Object(serde_json::Map::new());
 
     // add init schema
     if let Some(init_schema) = &contract_schema.init {
-        schema_json["init"] = function_v2_schema(init_schema)
+        schema_json["init"] = function_v2_schema(init_schema, format)
     }
 
     // add event schema
     if let Some(event_schema) = &contract_schema.event {
-        schema_json["event"] = type_to_json(event_schema);
+        schema_json["event"] = type_to_json(event_schema, format);
     }
 
     // add receive entrypoints
@@ -1072,7 +1202,7 @@ pub fn write_json_schema_to_file_v3(
         // iterate through the entrypoints and add their schemas
         for (method_name, receive_schema) in contract_schema.receive.iter() {
             // add `method_name` entrypoint
-            entrypoints[method_name] = function_v2_schema(receive_schema)
+            entrypoints[method_name] = function_v2_schema(receive_schema, format)
         }
 
         // add all receive entrypoints
@@ -1085,11 +1215,17 @@ pub fn write_json_schema_to_file_v3(
 /// Build the smart contract module and run integration tests.
 ///
 /// All test targets are tested if `test_targets` is empty.
-/// Otherwise, it is only the listed targets that are tested.
+/// Otherwise, it is only the listed targets that are tested. The
+/// `harness_args` are passed to the test harness, for selecting the tests.
+///
+/// Returns whether the tests succeeded, and the results of the individual
+/// tests if `collect_results` is set.
 pub(crate) fn build_and_run_integration_tests(
     build_options: BuildOptions,
     test_targets: Vec<String>,
-) -> anyhow::Result<()> {
+    harness_args: &[String],
+    collect_results: bool,
+) -> anyhow::Result<(bool, Vec<TestCase>)> {
     let cargo_extra_args = build_options.cargo_args.clone();
     let allow_debug = build_options.allow_debug;
 
@@ -1138,34 +1274,37 @@ pub(crate) fn build_and_run_integration_tests(
         command.args(["--features", "concordium-std/debug"]);
     }
     command.args(&cargo_extra_args);
+    // check if the user has already supplied extra test flags
+    let (test_args, show_output) = cargo_extra_args
+        .iter()
+        .fold((false, false), |(ta, so), arg| {
+            if arg == "--" {
+                (true, so)
+            } else if arg == "--show-output" || arg == "--nocapture" {
+                (ta, true)
+            } else {
+                (ta, so)
+            }
+        });
+    let mut harness_args = harness_args.to_vec();
     // when allowing debug output, we make sure that test output is not captured.
     if allow_debug {
-        // check if the user has already supplied extra test flags
-        let (test_args, show_output) =
-            cargo_extra_args
-                .iter()
-                .fold((false, false), |(ta, so), arg| {
-                    if arg == "--" {
-                        (true, so)
-                    } else if arg == "--show-output" || arg == "--nocapture" {
-                        (ta, true)
-                    } else {
-                        (ta, so)
-                    }
-                });
-        // if the extra test args separator is added we should not add it again.
-        if !test_args {
-            command.arg("--");
-        }
         // if the user has already supplied either the --nocapture or --show-output
         // flags we do nothing, since output will be displayed. Otherwise we
         // tell the test harness to show output.
         if !show_output {
-            command.arg("--show-output");
+            harness_args.push("--show-output".to_string());
         }
 
         command.env("CARGO_CONCORDIUM_TEST_ALLOW_DEBUG", "1");
     }
+    if !harness_args.is_empty() {
+        // if the extra test args separator is added we should not add it again.
+        if !test_args {
+            command.arg("--");
+        }
+        command.args(harness_args);
+    }
     // This enviroment variable needs to match the
     // `CONTRACT_MODULE_OUTPUT_PATH_ENV_VAR` constant in the `contract-testing`
     // crate.
@@ -1181,50 +1320,218 @@ pub(crate) fn build_and_run_integration_tests(
     }
     eprintln!();
 
-    let result = command
-        .stdout(Stdio::inherit())
+    if !collect_results {
+        let result = command
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit())
+            .output()
+            .context("Failed running integration tests.")?;
+        return Ok((result.status.success(), Vec::new()));
+    }
+
+    // The results are read from the output of the test harness, which is passed
+    // on as it is read.
+    let mut child = command
+        .stdout(Stdio::piped())
         .stderr(Stdio::inherit())
-        .output()
+        .spawn()
         .context("Failed running integration tests.")?;
-
-    anyhow::ensure!(
-        result.status.success(),
-        Color::Red
-            .bold()
-            .paint("One or more integration tests failed.")
-    );
-    Ok(())
+    let stdout = child
+        .stdout
+        .take()
+        .context("Could not read the output of the integration tests.")?;
+    let mut harness_output = HarnessOutput::default();
+    for line in BufReader::new(stdout).lines() {
+        let line = line.context("Could not read the output of the integration tests.")?;
+        println!("{}", line);
+        harness_output.line(&line);
+    }
+    let status = child.wait().context("Failed running integration tests.")?;
+    Ok((status.success(), harness_output.finish()))
 }
 
-/// Runs a single unit test and prints out the result, returns an option so that
-/// succesful cases can be filtered
-fn get_test_result(
-    name: &Name,
+/// The number of runs of a failing test spent on shrinking its random input.
+const SHRINK_ATTEMPTS: usize = 1000;
+
+/// The settings shared by the runs of all the unit tests.
+struct UnitTestContext<'a> {
+    artifact: &'a Artifact<ArtifactNamedImport, CompiledFunction>,
+    /// The seed of the first run of each test.
     seed: u64,
-    artifact: &Artifact<ArtifactNamedImport, CompiledFunction>,
+    /// The number of seeds each test using randomness is run with.
+    runs: u32,
     enable_debug: bool,
-) -> Option<()> {
-    let test_name = name.as_ref().strip_prefix("concordium_test ")?;
+    energy_profile: Option<(&'a profile::Instrumented, &'a Path)>,
+    coverage: Option<&'a Mutex<Vec<u64>>>,
+    limits: TestLimits,
+}
 
-    // create a `TestHost` instance for each test with the usage flag set to `false`
-    let mut initial_state = trie::MutableState::initial_state();
-    let mut loader = trie::Loader::new(Vec::new());
-    let mut test_host = {
-        let inner = initial_state.get_inner(&mut loader);
-        let state = InstanceState::new(loader, inner);
-        TestHost::new(SmallRng::seed_from_u64(seed), state)
-    };
+/// The outcome of a single run of a unit test.
+struct UnitTestRun {
+    error: Option<ReportError>,
+    duration: Duration,
+    /// Whether the test used randomness.
+    rng_used: bool,
+    /// The random bytes given to the test, if they were recorded.
+    random_input: Option<Vec<u8>>,
+    debug_events: Vec<EmittedDebugStatement>,
+    energy_used: u64,
+    profile_probes: Vec<([u32; 2], u64)>,
+    block_hits: Vec<u64>,
+}
+
+impl UnitTestContext<'_> {
+    /// Run a unit test once with the given seed. If `random_input` is given,
+    /// the random bytes are taken from it instead of being drawn using the
+    /// seed.
+    fn run(&self, name: &Name, seed: u64, random_input: Option<RandomInput>) -> UnitTestRun {
+        // create a `TestHost` instance for each test with the usage flag set to `false`
+        let mut initial_state = trie::MutableState::initial_state();
+        let mut loader = trie::Loader::new(Vec::new());
+        let mut instrumented_host = {
+            let inner = initial_state.get_inner(&mut loader);
+            let state = InstanceState::new(loader, inner);
+            InstrumentedHost::new(
+                TestHost::new(SmallRng::seed_from_u64(seed), state),
+                self.limits,
+            )
+        };
+        instrumented_host.random_input = random_input;
+
+        let start = Instant::now();
+        let error = self
+            .artifact
+            .run(&mut instrumented_host, name, &[])
+            .err()
+            .map(|msg| {
+                msg.downcast_ref::<ReportError>()
+                    .cloned()
+                    .unwrap_or_else(|| ReportError::Other {
+                        msg: msg.to_string(),
+                    })
+            });
+        let duration = start.elapsed();
+
+        let random_input = instrumented_host.random_input.map(|input| input.used);
+        UnitTestRun {
+            error,
+            duration,
+            rng_used: instrumented_host.inner.rng_used
+                || random_input.as_ref().is_some_and(|input| !input.is_empty()),
+            random_input,
+            debug_events: std::mem::take(&mut instrumented_host.inner.debug_events),
+            energy_used: instrumented_host.energy_used,
+            profile_probes: instrumented_host.profile_probes,
+            block_hits: instrumented_host.block_hits,
+        }
+    }
+
+    /// Shrink the random input of a failing test, by running the test with
+    /// smaller inputs for as long as it keeps failing. Returns the smallest
+    /// failing input found, and the error of the test with that input.
+    fn shrink(
+        &self,
+        name: &Name,
+        seed: u64,
+        input: Vec<u8>,
+        error: ReportError,
+    ) -> (Vec<u8>, ReportError) {
+        let mut best = (input, error);
+        let mut attempts = 0;
+        'shrinking: loop {
+            let current = best.0.clone();
+            for candidate in shrink_candidates(&current) {
+                if attempts >= SHRINK_ATTEMPTS {
+                    break 'shrinking;
+                }
+                attempts += 1;
+                let run = self.run(name, seed, Some(RandomInput::fixed(candidate)));
+                let (Some(error), Some(used)) = (run.error, run.random_input) else {
+                    continue;
+                };
+                // The test may use more bytes than the candidate has, in which case the
+                // input is not necessarily smaller.
+                if (used.len(), &used) < (best.0.len(), &best.0) {
+                    best = (used, error);
+                    continue 'shrinking;
+                }
+            }
+            break;
+        }
+        best
+    }
+}
 
-    let test_result = artifact.run(&mut test_host, name, &[]).err().map(|msg| {
-        msg.downcast_ref::<ReportError>()
-            .cloned()
-            .unwrap_or_else(|| ReportError::Other {
-                msg: msg.to_string(),
+/// Smaller variants of a random input: shorter prefixes of it, the input with
+/// a range of bytes set to zero, and the input with a single byte decreased.
+fn shrink_candidates(input: &[u8]) -> impl Iterator<Item = Vec<u8>> + '_ {
+    let len = input.len();
+    let prefixes = [0, len / 2, len * 3 / 4, len.saturating_sub(1)]
+        .into_iter()
+        .filter(move |prefix_len| *prefix_len < len)
+        .map(move |prefix_len| input[..prefix_len].to_vec());
+    let zeroed = [8, 4, 2, 1]
+        .into_iter()
+        .flat_map(move |size| {
+            (0..len)
+                .step_by(size)
+                .map(move |start| start..len.min(start + size))
+        })
+        .filter(move |range| input[range.clone()].iter().any(|byte| *byte != 0))
+        .map(move |range| {
+            let mut candidate = input.to_vec();
+            candidate[range].fill(0);
+            candidate
+        });
+    let decreased = (0..len).flat_map(move |i| {
+        let byte = input[i];
+        let halved = byte / 2;
+        let decremented = byte.saturating_sub(1);
+        [Some(halved), (decremented != halved).then_some(decremented)]
+            .into_iter()
+            .flatten()
+            .filter(move |smaller| *smaller < byte)
+            .map(move |smaller| {
+                let mut candidate = input.to_vec();
+                candidate[i] = smaller;
+                candidate
             })
     });
+    prefixes.chain(zeroed).chain(decreased)
+}
+
+/// Runs a single unit test and prints out the result. Returns `None` if the
+/// export is not a unit test.
+///
+/// A test using randomness is run with a new seed each time, up to the number
+/// of runs, until it fails. The random input of the failure is then shrunk.
+fn get_test_result(name: &Name, context: &UnitTestContext) -> Option<TestCase> {
+    let test_name = name.as_ref().strip_prefix("concordium_test ")?;
+
+    let mut seeds = SmallRng::seed_from_u64(context.seed);
+    let mut seed = context.seed;
+    let mut num_runs = 0;
+    let run = loop {
+        let run = context.run(name, seed, None);
+        num_runs += 1;
+        if let Some(coverage) = context.coverage {
+            // A poisoned lock means that another test panicked while holding it.
+            let mut block_hits = coverage.lock().expect("Coverage lock poisoned.");
+            if block_hits.len() < run.block_hits.len() {
+                block_hits.resize(run.block_hits.len(), 0);
+            }
+            for (total, hits) in block_hits.iter_mut().zip(run.block_hits.iter()) {
+                *total += hits;
+            }
+        }
+        if run.error.is_some() || !run.rng_used || num_runs >= context.runs {
+            break run;
+        }
+        seed = seeds.gen();
+    };
 
     let mut print_vec = Vec::new();
-    match test_result {
+    match run.error {
         Some(ref err) => {
             print_vec.push(format!(
                 "  - {} ... {}",
@@ -1236,13 +1543,41 @@ fn get_test_result(
                 Color::Red.bold().paint("Error"),
                 Style::new().italic().paint(err.to_string())
             ));
-            if test_host.rng_used {
+            if run.rng_used {
                 print_vec.push(format!(
                     "    {}: {}",
                     Style::new().bold().paint("Seed"),
                     Style::new().bold().paint(seed.to_string())
                 ));
+                print_vec.push(format!(
+                    "    Replay the failure with `--replay '{}={}'`.",
+                    test_name, seed
+                ));
             };
+            if run.rng_used && context.runs > 1 {
+                // The random input is recorded by running the test again with the same seed.
+                let recorded = context.run(name, seed, Some(RandomInput::from_seed(seed)));
+                match (recorded.error, recorded.random_input) {
+                    (Some(error), Some(input)) => {
+                        let (input, error) = context.shrink(name, seed, input, error);
+                        print_vec.push(format!(
+                            "    {} ({} bytes): {}",
+                            Style::new().bold().paint("Shrunk random input"),
+                            input.len(),
+                            hex::encode(&input)
+                        ));
+                        print_vec.push(format!(
+                            "    {} ... {}",
+                            Color::Red.bold().paint("Error with shrunk input"),
+                            Style::new().italic().paint(error.to_string())
+                        ));
+                    }
+                    _ => print_vec.push(
+                        "    The failure could not be reproduced for shrinking the random input."
+                            .to_string(),
+                    ),
+                }
+            }
         }
         None => {
             print_vec.push(format!(
@@ -1250,35 +1585,71 @@ fn get_test_result(
                 test_name,
                 Color::Green.bold().paint("ok")
             ));
+            if num_runs > 1 {
+                print_vec.push(format!("    Passed with {} seeds.", num_runs));
+            }
+        }
+    }
+    if let Some((instrumented, out_dir)) = context.energy_profile {
+        let profile = Profile::from_test(instrumented, &run.profile_probes, run.energy_used);
+        let path = out_dir.join(format!("{}.folded", test_name));
+        if let Err(err) = profile.write_folded(&path) {
+            print_vec.push(format!(
+                "    {} ... {:#}",
+                Color::Red.bold().paint("Profiling failed"),
+                err
+            ));
         }
     }
-    if enable_debug && !test_host.debug_events.is_empty() {
+    if context.enable_debug && !run.debug_events.is_empty() {
         print_vec.push("    Emitted debug events.".to_string());
-        for event in test_host.debug_events {
+        for event in run.debug_events.iter() {
             print_vec.push(format!("    {event}"));
         }
     }
     eprintln!("{}", print_vec.join("\n"));
 
-    test_result.map(|_| ())
+    Some(TestCase {
+        name: test_name.to_string(),
+        status: if run.error.is_some() {
+            TestStatus::Failed
+        } else {
+            TestStatus::Passed
+        },
+        error: run.error.map(|err| err.to_string()),
+        seed: run.rng_used.then_some(seed),
+        duration: Some(run.duration),
+        debug_events: debug_events.iter().map(|event| event.to_string()).collect(),
+    })
 }
 
 /// Build tests and run them. If errors occur in building the tests, or there
 /// are runtime exceptions that are not expected then this function returns
 /// Err(...).
 ///
-/// Otherwise a boolean is returned, signifying whether the tests succeeded or
-/// failed.
+/// Otherwise the results of the tests are returned.
 ///
-/// The `seed` argument allows for providing the seed to instantiate a random
+/// The `seed` option allows for providing the seed to instantiate a random
 /// number generator. If `None` is given, a random seed will be sampled.
-pub fn build_and_run_wasm_test(
-    enable_debug: bool,
-    profile: &str,
-    cargo_extra_args: &[String],
-    seed: Option<u64>,
-    skip_wasm_opt: bool,
-) -> anyhow::Result<bool> {
+///
+/// Only the tests selected by the filters and skip patterns are run, and in
+/// list mode they are only printed.
+///
+/// Tests that exceed the energy or time limit fail.
+///
+/// If `energy_profile` is given, the energy spent by each test is profiled and
+/// written to a file named after the test in that directory. If `coverage` is
+/// given, the coverage of the package by all the tests is written to that file
+/// in the lcov format.
+pub(crate) fn build_and_run_wasm_test(
+    build_options: &BuildOptions,
+    options: &TestOptions,
+) -> anyhow::Result<Vec<TestCase>> {
+    let enable_debug = build_options.allow_debug;
+    let cargo_extra_args = &build_options.cargo_args;
+    let energy_profile = options.energy_profile.as_deref();
+    let coverage = options.coverage.as_deref();
+
     // Check that the wasm target is installed
     check_wasm_target()?;
 
@@ -1296,7 +1667,7 @@ pub fn build_and_run_wasm_test(
 
     let wasm = CargoBuildParameters {
         target_dir: &target_dir,
-        profile,
+        profile: &build_options.profile,
         locked: false,
         package,
         features: if enable_debug {
@@ -1305,41 +1676,147 @@ pub fn build_and_run_wasm_test(
             &["concordium-std/wasm-test"]
         },
         cargo_extra_args,
+        keep_names: energy_profile.is_some() || coverage.is_some(),
+        debug_info: coverage.is_some(),
+    }
+    .run_cargo_cmd(build_options.skip_wasm_opt)?;
+
+    if options.list {
+        let artifact = concordium_wasm::utils::instantiate::<ArtifactNamedImport, _>(
+            ValidationConfig::V1,
+            &NoDuplicateImport,
+            &wasm,
+        )?
+        .artifact;
+        let mut test_names: Vec<_> = artifact
+            .export
+            .keys()
+            .filter_map(|name| name.as_ref().strip_prefix("concordium_test "))
+            .filter(|test_name| options.selects(test_name))
+            .collect();
+        test_names.sort();
+        eprintln!("\n{}", Color::Green.bold().paint("Unit tests:"));
+        // The tests are listed on stdout in the same format as `cargo test -- --list`.
+        for test_name in test_names.iter() {
+            println!("{}: test", test_name);
+        }
+        eprintln!("{} unit tests", test_names.len());
+        return Ok(Vec::new());
     }
-    .run_cargo_cmd(skip_wasm_opt)?;
 
     eprintln!("\n{}", Color::Green.bold().paint("Running unit tests ..."));
 
-    let seed_u64 = match seed {
-        Some(s) => s,
-        None => {
+    let (seed_u64, runs) = match (&options.replay, options.seed) {
+        (Some(replay), _) => (replay.seed, 1),
+        (None, Some(s)) => (s, options.runs.unwrap_or(1)),
+        (None, None) => {
             // Since the seed was not provided, we use system randomness to sample a random
             // one and use is to seed a deterministic RNG. We store the seed so
             // we may report it to the user in case of test failure.
-            thread_rng().gen()
+            (thread_rng().gen(), options.runs.unwrap_or(1))
         }
     };
 
-    let artifact = concordium_wasm::utils::instantiate::<ArtifactNamedImport, _>(
-        ValidationConfig::V1,
-        &NoDuplicateImport,
-        &wasm,
-    )?
-    .artifact;
-    let artifact_keys: Vec<_> = artifact.export.keys().collect();
+    let limits = TestLimits {
+        energy: options.test_energy_limit,
+        time: options.test_timeout.map(Duration::from_secs),
+    };
+    // Profiling and the limits of the tests require the energy to be metered.
+    let metered = energy_profile.is_some() || limits.energy.is_some() || limits.time.is_some();
+    let instantiate = |module: &[u8]| -> anyhow::Result<_> {
+        let artifact = if metered {
+            concordium_wasm::utils::instantiate_with_metering::<ArtifactNamedImport, _>(
+                ValidationConfig::V1,
+                CostConfigurationV1,
+                &NoDuplicateImport,
+                module,
+            )?
+            .artifact
+        } else {
+            concordium_wasm::utils::instantiate::<ArtifactNamedImport, _>(
+                ValidationConfig::V1,
+                &NoDuplicateImport,
+                module,
+            )?
+            .artifact
+        };
+        Ok(artifact)
+    };
+    let mut covered = None;
+    let (artifact, profiling) = match energy_profile {
+        None if coverage.is_some() => {
+            let instrumented = coverage::instrument(&wasm)
+                .context("Could not instrument the unit tests for coverage.")?;
+            let artifact = instantiate(&instrumented.module)?;
+            covered = Some((instrumented, Mutex::new(Vec::new())));
+            (artifact, None)
+        }
+        Some(out_dir) => {
+            let instrumented = profile::instrument(&wasm, None, ProbeTransport::Host)
+                .context("Could not instrument the unit tests for profiling.")?;
+            let artifact = instantiate(&instrumented.module)?;
+            (artifact, Some((instrumented, out_dir)))
+        }
+        None => (instantiate(&wasm)?, None),
+    };
+    let mut num_filtered_out = 0;
+    let artifact_keys: Vec<_> = artifact
+        .export
+        .keys()
+        .filter(
+            |name| match name.as_ref().strip_prefix("concordium_test ") {
+                Some(test_name) if !options.selects(test_name) => {
+                    num_filtered_out += 1;
+                    false
+                }
+                _ => true,
+            },
+        )
+        .collect();
+    if num_filtered_out > 0 {
+        eprintln!("{} unit tests filtered out.", num_filtered_out);
+    }
 
-    let num_failed = artifact_keys
+    let context = UnitTestContext {
+        artifact: &artifact,
+        seed: seed_u64,
+        runs,
+        enable_debug,
+        energy_profile: profiling
+            .as_ref()
+            .map(|(instrumented, out_dir)| (instrumented, *out_dir)),
+        coverage: covered.as_ref().map(|(_, block_hits)| block_hits),
+        limits,
+    };
+    let results: Vec<_> = artifact_keys
         .into_par_iter()
-        .filter_map(|name| get_test_result(name, seed_u64, &artifact, enable_debug))
-        .count();
+        .filter_map(|name| get_test_result(name, &context))
+        .collect();
+    if let Some((_, out_dir)) = profiling {
+        eprintln!(
+            "Energy profiles of the unit tests written to {}.",
+            out_dir.display()
+        );
+    }
+    if let (Some((instrumented, block_hits)), Some(path)) = (covered, coverage) {
+        let block_hits = block_hits.into_inner().expect("Coverage lock poisoned.");
+        let root = package
+            .manifest_path
+            .parent()
+            .context("Unable to determine the package directory.")?;
+        let module_name = format!("{}.wasm", package.name.replace('-', "_"));
+        let report = instrumented.coverage(&block_hits, root.as_std_path(), &module_name);
+        report.write_lcov(path)?;
+        report.print_summary();
+        eprintln!("Coverage of the unit tests written to {}.", path.display());
+    }
 
-    if num_failed == 0 {
-        eprintln!("Unit test result: {}", Color::Green.bold().paint("ok"));
-        Ok(true)
-    } else {
+    if results.iter().any(TestCase::failed) {
         eprintln!("Unit test result: {}", Color::Red.bold().paint("FAILED"));
-        Ok(false)
+    } else {
+        eprintln!("Unit test result: {}", Color::Green.bold().paint("ok"));
     }
+    Ok(results)
 }
 
 /// Checks if the target is installed, and returns an error if not.
@@ -1381,6 +1858,11 @@ struct CargoBuildParameters<'a> {
     features: &'a [&'a str],
     package: &'a Package,
     cargo_extra_args: &'a [String],
+    /// Whether to keep the name section when optimizing the module.
+    keep_names: bool,
+    /// Whether to compile with debug information, which is also kept when
+    /// optimizing the module.
+    debug_info: bool,
 }
 
 impl CargoBuildParameters<'_> {
@@ -1422,6 +1904,12 @@ impl CargoBuildParameters<'_> {
         let executable = args.remove(0); // "cargo"
         let mut cmd = Command::new(&executable);
         cmd.args(&args);
+        if self.debug_info {
+            // Override the `debug` setting of the profile, see
+            // https://doc.rust-lang.org/cargo/reference/environment-variables.html
+            let profile = self.profile.to_uppercase().replace('-', "_");
+            cmd.env(format!("CARGO_PROFILE_{profile}_DEBUG"), "true");
+        }
 
         eprintln!(
             "     {} `{cargo_cmd_string}`",
@@ -1448,6 +1936,7 @@ impl CargoBuildParameters<'_> {
 
         if !skip_wasm_opt {
             wasm_opt::OptimizationOptions::new_opt_level_0()
+                .debug_info(self.keep_names || self.debug_info)
                 .run(&output_wasm_file, &output_wasm_file)
                 .context("Failed running wasm_opt")?;
         }
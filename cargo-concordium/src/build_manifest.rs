@@ -0,0 +1,150 @@
+//! The build manifest, a JSON file describing the artifacts produced by
+//! `cargo concordium build`. It is written with `--manifest-out`, for use by
+//! scripts deploying the module.
+use crate::build::BuildInfo;
+use anyhow::Context;
+use concordium_base::contracts_common::WasmVersion;
+use concordium_smart_contract_engine::utils::VersionedBuildInfo;
+use serde::Serialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The format of a schema file written by the build.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum SchemaFormat {
+    /// The binary schema, written with `--schema-out`.
+    Binary,
+    /// A directory with the schema in JSON, written with `--schema-json-out`.
+    Json,
+    /// The template of the schema, written with `--schema-template-out`.
+    Template,
+    /// The binary schema in base64, written with `--schema-base64-out`.
+    Base64,
+}
+
+/// A schema file written by the build.
+#[derive(Debug, Serialize)]
+pub(crate) struct SchemaFile {
+    pub format: SchemaFormat,
+    pub path: PathBuf,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest<'a> {
+    package: &'a str,
+    package_version: String,
+    module_path: &'a Path,
+    /// The module reference in hex.
+    module_reference: String,
+    /// The size of the module file in bytes.
+    module_size: usize,
+    wasm_version: &'static str,
+    contracts: Vec<Contract<'a>>,
+    schema: Schema<'a>,
+    /// The build information embedded in the module by a verifiable build.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    build_info: Option<EmbeddedBuildInfo<'a>>,
+}
+
+#[derive(Serialize)]
+struct Contract<'a> {
+    name: &'a str,
+    entrypoints: &'a [String],
+}
+
+#[derive(Serialize)]
+struct Schema<'a> {
+    /// Whether the schema is embedded in the module.
+    embedded: bool,
+    files: &'a [SchemaFile],
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct EmbeddedBuildInfo<'a> {
+    image: &'a str,
+    build_command: &'a [String],
+    /// The SHA256 hash of the source archive in hex.
+    archive_hash: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source_link: Option<&'a str>,
+    /// The source archive written next to the module.
+    source_archive: PathBuf,
+    /// The files in the source archive, relative to the package root.
+    archived_files: &'a [PathBuf],
+}
+
+/// Write the manifest of a build to the file at `path`. The paths of the
+/// schema files are made absolute, so that the manifest does not depend on
+/// the directory it is read from.
+pub(crate) fn write_manifest(
+    path: &Path,
+    build_info: &BuildInfo,
+    version: WasmVersion,
+    schema_embedded: bool,
+    schema_files: &[SchemaFile],
+) -> anyhow::Result<()> {
+    let package = build_info
+        .metadata
+        .root_package()
+        .context("Unable to determine package.")?;
+    let schema_files: Vec<_> = schema_files
+        .iter()
+        .map(|file| SchemaFile {
+            format: file.format,
+            path: file
+                .path
+                .canonicalize()
+                .unwrap_or_else(|_| file.path.clone()),
+        })
+        .collect();
+    let build_info_manifest = build_info.stored_build_info.as_ref().map(
+        |(VersionedBuildInfo::V0(bi), archived_files)| {
+            let mut source_archive = build_info.out_filename.clone().into_os_string();
+            source_archive.push(".tar");
+            EmbeddedBuildInfo {
+                image: &bi.image,
+                build_command: &bi.build_command,
+                archive_hash: bi.archive_hash.to_string(),
+                source_link: bi.source_link.as_deref(),
+                source_archive: source_archive.into(),
+                archived_files,
+            }
+        },
+    );
+    let manifest = Manifest {
+        package: &package.name,
+        package_version: package.version.to_string(),
+        module_path: &build_info.out_filename,
+        module_reference: build_info.module_ref.to_string(),
+        module_size: build_info.total_module_len,
+        wasm_version: match version {
+            WasmVersion::V0 => "V0",
+            WasmVersion::V1 => "V1",
+        },
+        contracts: build_info
+            .contracts
+            .iter()
+            .map(|(name, entrypoints)| Contract { name, entrypoints })
+            .collect(),
+        schema: Schema {
+            embedded: schema_embedded,
+            files: &schema_files,
+        },
+        build_info: build_info_manifest,
+    };
+    let json = serde_json::to_string_pretty(&manifest)
+        .context("Could not output the build manifest as JSON.")?;
+    if let Some(out_dir) = path.parent() {
+        fs::create_dir_all(out_dir)
+            .context("Unable to create directory for the build manifest.")?;
+    }
+    fs::write(path, json)
+        .with_context(|| format!("Could not write the build manifest to {}.", path.display()))?;
+    eprintln!("   Build manifest written to {}.", path.display());
+    Ok(())
+}
@@ -0,0 +1,446 @@
+//! A mock of the parts of the chain state that contracts can query, used for
+//! answering query interrupts when simulating receive calls.
+use anyhow::{ensure, Context};
+use concordium_base::contracts_common::{
+    AccountAddress, AccountSignatures, Amount, ContractAddress, Cursor, Deserial, Serial, Signature,
+};
+use concordium_smart_contract_engine::v1;
+use ed25519_dalek::Verifier;
+use serde::Deserialize;
+use std::{collections::BTreeMap, fs, path::Path, str::FromStr};
+
+/// The chain state file.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ChainStateSpec {
+    #[serde(default)]
+    accounts: Vec<AccountSpec>,
+    #[serde(default)]
+    contracts: Vec<ContractSpec>,
+    exchange_rates: Option<ExchangeRates>,
+}
+
+/// An account of the chain state file.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AccountSpec {
+    address: AccountAddress,
+    /// The total balance of the account.
+    balance: Amount,
+    /// The part of the balance that is staked.
+    #[serde(default)]
+    staked: Amount,
+    /// The part of the balance that is locked in releases.
+    #[serde(default)]
+    locked: Amount,
+    keys: Option<AccountKeys>,
+}
+
+/// A contract instance of the chain state file.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ContractSpec {
+    address: ContractAddress,
+    #[serde(default)]
+    balance: Amount,
+    /// The module reference in hex.
+    module_reference: Option<String>,
+    /// The name of the contract, e.g., `init_my_contract`.
+    name: Option<String>,
+}
+
+/// The public keys of an account, indexed by credential index.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AccountKeys {
+    threshold: u8,
+    credentials: BTreeMap<u8, CredentialKeys>,
+}
+
+/// The public keys of a credential, indexed by key index. The keys are
+/// Ed25519 keys given in hex.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CredentialKeys {
+    threshold: u8,
+    keys: BTreeMap<u8, String>,
+}
+
+/// An exchange rate as a fraction.
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ExchangeRate {
+    pub numerator: u64,
+    pub denominator: u64,
+}
+
+impl FromStr for ExchangeRate {
+    type Err = anyhow::Error;
+
+    /// Parse an exchange rate given as `numerator/denominator`, or as a whole
+    /// number.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (numerator, denominator) = s.split_once('/').unwrap_or((s, "1"));
+        let rate = Self {
+            numerator: numerator
+                .trim()
+                .parse()
+                .context("Invalid numerator of exchange rate.")?,
+            denominator: denominator
+                .trim()
+                .parse()
+                .context("Invalid denominator of exchange rate.")?,
+        };
+        ensure!(
+            rate.denominator != 0,
+            "The denominator of an exchange rate must not be zero."
+        );
+        Ok(rate)
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+struct ExchangeRates {
+    euro_per_energy: ExchangeRate,
+    micro_ccd_per_euro: ExchangeRate,
+}
+
+/// An account of the chain state.
+struct Account {
+    balance: Amount,
+    staked: Amount,
+    locked: Amount,
+    keys: Option<AccountPublicKeys>,
+}
+
+/// The public keys of an account.
+struct AccountPublicKeys {
+    threshold: u8,
+    credentials: BTreeMap<u8, (u8, BTreeMap<u8, ed25519_dalek::VerifyingKey>)>,
+}
+
+/// What is known about a contract instance when answering queries.
+pub(crate) struct ContractInfo {
+    pub balance: Amount,
+    pub module_reference: Option<[u8; 32]>,
+    pub name: Option<String>,
+}
+
+/// The chain state that queries are answered from.
+#[derive(Default)]
+pub(crate) struct ChainState {
+    accounts: BTreeMap<AccountAddress, Account>,
+    contracts: BTreeMap<ContractAddress, ContractSpec>,
+    exchange_rates: Option<ExchangeRates>,
+}
+
+impl ChainState {
+    /// Load the chain state from a JSON file.
+    pub(crate) fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let contents = fs::read(path)
+            .with_context(|| format!("Could not read chain state file {}.", path.display()))?;
+        let spec: ChainStateSpec =
+            serde_json::from_slice(&contents).context("Could not parse the chain state file.")?;
+        let mut accounts = BTreeMap::new();
+        for account in spec.accounts {
+            let keys = account
+                .keys
+                .map(AccountPublicKeys::try_from)
+                .transpose()
+                .with_context(|| format!("Invalid keys for account {}.", account.address))?;
+            let entry = Account {
+                balance: account.balance,
+                staked: account.staked,
+                locked: account.locked,
+                keys,
+            };
+            ensure!(
+                accounts.insert(account.address, entry).is_none(),
+                "Account {} is listed more than once in the chain state file.",
+                account.address
+            );
+        }
+        let mut contracts = BTreeMap::new();
+        for contract in spec.contracts {
+            let address = contract.address;
+            ensure!(
+                contracts.insert(address, contract).is_none(),
+                "Contract {} is listed more than once in the chain state file.",
+                address
+            );
+        }
+        Ok(Self {
+            accounts,
+            contracts,
+            exchange_rates: spec.exchange_rates,
+        })
+    }
+
+    /// Get what is known about a contract instance from the chain state.
+    pub(crate) fn contract_info(
+        &self,
+        address: &ContractAddress,
+    ) -> anyhow::Result<Option<ContractInfo>> {
+        let Some(contract) = self.contracts.get(address) else {
+            return Ok(None);
+        };
+        let module_reference = contract
+            .module_reference
+            .as_deref()
+            .map(parse_module_reference)
+            .transpose()
+            .with_context(|| format!("Invalid module reference for contract {}.", address))?;
+        Ok(Some(ContractInfo {
+            balance: contract.balance,
+            module_reference,
+            name: contract.name.clone(),
+        }))
+    }
+
+    /// Answer a query about an account or the exchange rates. Returns `None`
+    /// if the interrupt is not such a query. The `caller_balance` is the
+    /// balance of the contract making the query.
+    pub(crate) fn answer_query(
+        &self,
+        interrupt: &v1::Interrupt,
+        caller_balance: Amount,
+    ) -> anyhow::Result<Option<v1::InvokeResponse>> {
+        let response = match interrupt {
+            v1::Interrupt::QueryAccountBalance { address } => match self.accounts.get(address) {
+                Some(account) => {
+                    let mut data = Vec::new();
+                    account
+                        .balance
+                        .serial(&mut data)
+                        .expect("Cannot fail writing to vec.");
+                    account
+                        .staked
+                        .serial(&mut data)
+                        .expect("Cannot fail writing to vec.");
+                    account
+                        .locked
+                        .serial(&mut data)
+                        .expect("Cannot fail writing to vec.");
+                    success(caller_balance, data)
+                }
+                None => failure(v1::InvokeFailure::NonExistentAccount),
+            },
+            v1::Interrupt::QueryExchangeRates => {
+                let rates = self.exchange_rates.context(
+                    "The contract queried the exchange rates, but the chain state file does not \
+                     specify them.",
+                )?;
+                let mut data = Vec::new();
+                for rate in [rates.euro_per_energy, rates.micro_ccd_per_euro] {
+                    rate.numerator
+                        .serial(&mut data)
+                        .expect("Cannot fail writing to vec.");
+                    rate.denominator
+                        .serial(&mut data)
+                        .expect("Cannot fail writing to vec.");
+                }
+                success(caller_balance, data)
+            }
+            v1::Interrupt::QueryAccountKeys { address } => match self.accounts.get(address) {
+                Some(account) => {
+                    let keys = account.keys.as_ref().with_context(|| {
+                        format!(
+                            "The contract queried the keys of account {}, but the chain state \
+                             file does not specify them.",
+                            address
+                        )
+                    })?;
+                    success(caller_balance, keys.to_bytes())
+                }
+                None => failure(v1::InvokeFailure::NonExistentAccount),
+            },
+            v1::Interrupt::CheckAccountSignature { address, payload } => {
+                match self.accounts.get(address) {
+                    Some(account) => {
+                        let keys = account.keys.as_ref().with_context(|| {
+                            format!(
+                                "The contract checked a signature of account {}, but the chain \
+                                 state file does not specify its keys.",
+                                address
+                            )
+                        })?;
+                        match parse_signatures_and_data(payload) {
+                            Some((signatures, data)) if keys.verify(&signatures, &data) => {
+                                v1::InvokeResponse::Success {
+                                    new_balance: caller_balance,
+                                    data: None,
+                                }
+                            }
+                            Some(_) => failure(v1::InvokeFailure::SignatureCheckFailed),
+                            None => failure(v1::InvokeFailure::SignatureDataMalformed),
+                        }
+                    }
+                    None => failure(v1::InvokeFailure::NonExistentAccount),
+                }
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(response))
+    }
+}
+
+/// Answer a query about a contract instance. Returns `None` if the interrupt
+/// is not such a query.
+pub(crate) fn answer_contract_query(
+    interrupt: &v1::Interrupt,
+    contract: Option<ContractInfo>,
+    caller_balance: Amount,
+) -> anyhow::Result<Option<v1::InvokeResponse>> {
+    let response = match (interrupt, contract) {
+        (
+            v1::Interrupt::QueryContractBalance { .. }
+            | v1::Interrupt::QueryContractModuleReference { .. }
+            | v1::Interrupt::QueryContractName { .. },
+            None,
+        ) => failure(v1::InvokeFailure::NonExistentContract),
+        (v1::Interrupt::QueryContractBalance { .. }, Some(contract)) => success(
+            caller_balance,
+            concordium_base::contracts_common::to_bytes(&contract.balance),
+        ),
+        (v1::Interrupt::QueryContractModuleReference { address }, Some(contract)) => {
+            let module_reference = contract.module_reference.with_context(|| {
+                format!(
+                    "The contract queried the module reference of contract {}, but it is not \
+                     known.",
+                    address
+                )
+            })?;
+            success(caller_balance, module_reference.to_vec())
+        }
+        (v1::Interrupt::QueryContractName { address }, Some(contract)) => {
+            let name = contract.name.with_context(|| {
+                format!(
+                    "The contract queried the name of contract {}, but it is not known.",
+                    address
+                )
+            })?;
+            success(caller_balance, name.into_bytes())
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(response))
+}
+
+fn success(caller_balance: Amount, data: Vec<u8>) -> v1::InvokeResponse {
+    v1::InvokeResponse::Success {
+        new_balance: caller_balance,
+        data: Some(data),
+    }
+}
+
+fn failure(kind: v1::InvokeFailure) -> v1::InvokeResponse {
+    v1::InvokeResponse::Failure { kind }
+}
+
+fn parse_module_reference(hex_str: &str) -> anyhow::Result<[u8; 32]> {
+    let bytes = hex::decode(hex_str).context("The module reference is not valid hex.")?;
+    bytes
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("The module reference must be 32 bytes."))
+}
+
+/// Parse the payload of a signature check, which consists of the signatures
+/// followed by the length-prefixed data that was signed.
+fn parse_signatures_and_data(payload: &[u8]) -> Option<(AccountSignatures, Vec<u8>)> {
+    let mut cursor = Cursor::new(payload);
+    let signatures = AccountSignatures::deserial(&mut cursor).ok()?;
+    let data = Vec::<u8>::deserial(&mut cursor).ok()?;
+    if cursor.offset != payload.len() {
+        return None;
+    }
+    Some((signatures, data))
+}
+
+impl TryFrom<AccountKeys> for AccountPublicKeys {
+    type Error = anyhow::Error;
+
+    fn try_from(keys: AccountKeys) -> anyhow::Result<Self> {
+        let mut credentials = BTreeMap::new();
+        for (credential_index, credential) in keys.credentials {
+            let mut credential_keys = BTreeMap::new();
+            for (key_index, key) in credential.keys {
+                let bytes: [u8; 32] = hex::decode(&key)
+                    .ok()
+                    .and_then(|bytes| bytes.try_into().ok())
+                    .with_context(|| {
+                        format!("Key {key_index} of credential {credential_index} is not 32 bytes of hex.")
+                    })?;
+                let key = ed25519_dalek::VerifyingKey::from_bytes(&bytes).with_context(|| {
+                    format!("Key {key_index} of credential {credential_index} is not a valid Ed25519 key.")
+                })?;
+                credential_keys.insert(key_index, key);
+            }
+            ensure!(
+                credential.threshold > 0 && usize::from(credential.threshold) <= credential_keys.len(),
+                "The threshold of credential {credential_index} must be between 1 and the number of keys."
+            );
+            credentials.insert(credential_index, (credential.threshold, credential_keys));
+        }
+        ensure!(
+            keys.threshold > 0 && usize::from(keys.threshold) <= credentials.len(),
+            "The account threshold must be between 1 and the number of credentials."
+        );
+        Ok(Self {
+            threshold: keys.threshold,
+            credentials,
+        })
+    }
+}
+
+impl AccountPublicKeys {
+    /// Serialize the keys in the format contracts receive them in.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![self.credentials.len() as u8];
+        for (credential_index, (threshold, keys)) in &self.credentials {
+            out.push(*credential_index);
+            out.push(keys.len() as u8);
+            for (key_index, key) in keys {
+                out.push(*key_index);
+                // The tag for Ed25519 keys.
+                out.push(0);
+                out.extend_from_slice(key.as_bytes());
+            }
+            out.push(*threshold);
+        }
+        out.push(self.threshold);
+        out
+    }
+
+    /// Check the signatures on the data. This matches the check done by the
+    /// chain: enough credentials must sign to meet the account threshold,
+    /// each of them with enough keys to meet the credential threshold, and
+    /// all the signatures must be valid.
+    fn verify(&self, signatures: &AccountSignatures, data: &[u8]) -> bool {
+        if signatures.sigs.len() < usize::from(self.threshold) {
+            return false;
+        }
+        signatures
+            .sigs
+            .iter()
+            .all(|(credential_index, credential_signatures)| {
+                let Some((threshold, keys)) = self.credentials.get(credential_index) else {
+                    return false;
+                };
+                if credential_signatures.sigs.len() < usize::from(*threshold) {
+                    return false;
+                }
+                credential_signatures
+                    .sigs
+                    .iter()
+                    .all(|(key_index, signature)| {
+                        let Some(key) = keys.get(key_index) else {
+                            return false;
+                        };
+                        let Signature::Ed25519(signature) = signature;
+                        let signature = ed25519_dalek::Signature::from_bytes(&signature.0);
+                        key.verify(data, &signature).is_ok()
+                    })
+            })
+    }
+}
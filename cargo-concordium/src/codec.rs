@@ -0,0 +1,211 @@
+//! Converting values between their JSON representation and their binary
+//! serialization using a schema, for `cargo concordium encode` and
+//! `cargo concordium decode`.
+use crate::contract_schema::contract_schemas;
+use anyhow::Context;
+use base64::{engine::general_purpose, Engine as _};
+use concordium_base::contracts_common::schema::{Type, VersionedModuleSchema};
+use std::{
+    fs,
+    io::{Read, Write},
+    path::Path,
+    str::FromStr,
+};
+
+const ENCODER: base64::engine::GeneralPurpose = general_purpose::STANDARD;
+
+/// The kind of value to convert, determining the type in the schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ValueKind {
+    Parameter,
+    ReturnValue,
+    Error,
+    Event,
+    State,
+}
+
+impl FromStr for ValueKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "parameter" => Ok(Self::Parameter),
+            "return-value" => Ok(Self::ReturnValue),
+            "error" => Ok(Self::Error),
+            "event" => Ok(Self::Event),
+            "state" => Ok(Self::State),
+            _ => anyhow::bail!(
+                "Unsupported kind '{}'. Use 'parameter', 'return-value', 'error', 'event' or \
+                 'state'.",
+                s
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for ValueKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValueKind::Parameter => write!(f, "parameter"),
+            ValueKind::ReturnValue => write!(f, "return value"),
+            ValueKind::Error => write!(f, "error"),
+            ValueKind::Event => write!(f, "event"),
+            ValueKind::State => write!(f, "state"),
+        }
+    }
+}
+
+/// The encoding of the binary serialization of a value in a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Encoding {
+    Hex,
+    Base64,
+    /// The bytes themselves.
+    Binary,
+}
+
+impl FromStr for Encoding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hex" => Ok(Self::Hex),
+            "base64" => Ok(Self::Base64),
+            "binary" => Ok(Self::Binary),
+            _ => anyhow::bail!(
+                "Unsupported encoding '{}'. Use 'hex', 'base64' or 'binary'.",
+                s
+            ),
+        }
+    }
+}
+
+/// Get the type of the value from the schema. Parameters, return values and
+/// errors belong to the given entrypoint, or to the init function if
+/// `entrypoint` is `None`, while events and the state belong to the contract.
+pub(crate) fn value_type(
+    schema: &VersionedModuleSchema,
+    contract: &str,
+    entrypoint: Option<&str>,
+    kind: ValueKind,
+) -> anyhow::Result<Type> {
+    let mut contracts = contract_schemas(schema);
+    let contract_schema = contracts
+        .remove(contract)
+        .with_context(|| format!("The schema has no contract named '{}'.", contract))?;
+    let function = match entrypoint {
+        Some(entrypoint) => format!("entrypoint '{}'", entrypoint),
+        None => "init function".to_string(),
+    };
+    let ty = match kind {
+        ValueKind::Parameter | ValueKind::ReturnValue | ValueKind::Error => {
+            let function_schema = contract_schema.function(entrypoint).with_context(|| {
+                format!("The schema of contract '{}' has no {}.", contract, function)
+            })?;
+            match kind {
+                ValueKind::Parameter => function_schema.parameter.clone(),
+                ValueKind::ReturnValue => function_schema.return_value.clone(),
+                _ => function_schema.error.clone(),
+            }
+        }
+        ValueKind::Event | ValueKind::State => {
+            if entrypoint.is_some() {
+                anyhow::bail!(
+                    "The {} type belongs to the contract. Do not supply an entrypoint.",
+                    kind
+                );
+            }
+            if kind == ValueKind::Event {
+                contract_schema.event
+            } else {
+                contract_schema.state
+            }
+        }
+    };
+    ty.with_context(|| match kind {
+        ValueKind::Event | ValueKind::State => format!(
+            "The schema of contract '{}' has no {} type.",
+            contract, kind
+        ),
+        _ => format!(
+            "The schema of the {} of contract '{}' has no {} type.",
+            function, contract, kind
+        ),
+    })
+}
+
+/// Read the file at `path`, or stdin if `path` is `-`.
+fn read_input(path: &Path) -> anyhow::Result<Vec<u8>> {
+    if path == Path::new("-") {
+        let mut input = Vec::new();
+        std::io::stdin()
+            .read_to_end(&mut input)
+            .context("Could not read from stdin.")?;
+        Ok(input)
+    } else {
+        fs::read(path).with_context(|| format!("Could not read input file {}.", path.display()))
+    }
+}
+
+/// Write to the file at `path`, or to stdout if `path` is `-`.
+pub(crate) fn write_output(path: &Path, output: &[u8]) -> anyhow::Result<()> {
+    if path == Path::new("-") {
+        let mut stdout = std::io::stdout();
+        stdout
+            .write_all(output)
+            .and_then(|_| stdout.flush())
+            .context("Could not write to stdout.")
+    } else {
+        if let Some(out_dir) = path.parent() {
+            fs::create_dir_all(out_dir).context("Unable to create directory for the output.")?;
+        }
+        fs::write(path, output)
+            .with_context(|| format!("Could not write output file {}.", path.display()))
+    }
+}
+
+/// Serialize the JSON value in the file `input` using the type `ty`, and write
+/// the bytes to the file `out` in the given encoding. Hex and base64 are
+/// followed by a newline.
+pub(crate) fn encode_value(
+    ty: &Type,
+    input: &Path,
+    out: &Path,
+    encoding: Encoding,
+) -> anyhow::Result<()> {
+    let json: serde_json::Value = serde_json::from_slice(&read_input(input)?)
+        .context("Could not parse the input as JSON.")?;
+    let mut bytes = Vec::new();
+    ty.serial_value_into(&json, &mut bytes)
+        .context("Could not serialize the JSON using the schema.")?;
+    let output = match encoding {
+        Encoding::Hex => format!("{}\n", hex::encode(&bytes)).into_bytes(),
+        Encoding::Base64 => format!("{}\n", ENCODER.encode(&bytes)).into_bytes(),
+        Encoding::Binary => bytes,
+    };
+    write_output(out, &output)
+}
+
+/// Deserialize the bytes in the file `input`, in the given encoding, using the
+/// type `ty`, and write the JSON representation to the file `out`. Whitespace
+/// around hex and base64 is ignored.
+pub(crate) fn decode_value(
+    ty: &Type,
+    input: &Path,
+    out: &Path,
+    encoding: Encoding,
+) -> anyhow::Result<()> {
+    let input = read_input(input)?;
+    let bytes = match encoding {
+        Encoding::Hex => hex::decode(String::from_utf8_lossy(&input).trim())
+            .context("The input is not valid hex.")?,
+        Encoding::Base64 => ENCODER
+            .decode(String::from_utf8_lossy(&input).trim())
+            .context("The input is not valid base64.")?,
+        Encoding::Binary => input,
+    };
+    let json = ty
+        .to_json_string_pretty(&bytes)
+        .map_err(|_| anyhow::anyhow!("Could not deserialize the input using the schema."))?;
+    write_output(out, format!("{}\n", json).as_bytes())
+}
@@ -0,0 +1,1225 @@
+//! Generation of client code from the schema of a module, for
+//! `cargo concordium codegen`.
+//!
+//! The generated TypeScript describes the JSON representation of parameters,
+//! return values, errors and events defined by the schema, and serializes and
+//! deserializes them with the schema using `@concordium/web-sdk`. The generated
+//! Rust defines types for them, implementing `Serial` and `Deserial` such that
+//! they are serialized as described by the schema.
+use crate::contract_schema::{contract_schemas, enum_tag_size, ContractSchema};
+use anyhow::Context;
+use base64::{engine::general_purpose, Engine as _};
+use concordium_base::contracts_common::{
+    schema::{Fields, SizeLength, Type, VersionedModuleSchema},
+    to_bytes,
+};
+use std::{collections::BTreeSet, fmt::Write as _, fs, path::Path};
+
+const ENCODER: base64::engine::GeneralPurpose = general_purpose::STANDARD;
+
+/// Generate TypeScript bindings for each contract in the schema, and write
+/// them to files named after the contracts in the `out` directory.
+pub(crate) fn write_typescript(out: &Path, schema: &VersionedModuleSchema) -> anyhow::Result<()> {
+    if let VersionedModuleSchema::V0(_) = schema {
+        anyhow::bail!("Bindings can only be generated from schemas of V1 modules.");
+    }
+    for (counter, (contract_name, contract_schema)) in contract_schemas(schema).iter().enumerate() {
+        let code = typescript_contract(contract_name, contract_schema);
+        let file_stem = file_stem(contract_name, counter);
+        write_bindings(out, contract_name, &file_stem, "ts", "TypeScript", &code)?;
+    }
+    Ok(())
+}
+
+/// The name of the file with the bindings of a contract, without extension.
+/// Files are named after the contract, or after the counter if the contract
+/// name is not a valid file name on all platforms.
+fn file_stem(contract_name: &str, counter: usize) -> String {
+    if contract_name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "-_".contains(c))
+    {
+        contract_name.to_string()
+    } else {
+        format!("contract_{}", counter)
+    }
+}
+
+/// Write the bindings of a contract to the file `file_stem` with the given
+/// extension in the `out` directory.
+fn write_bindings(
+    out: &Path,
+    contract_name: &str,
+    file_stem: &str,
+    extension: &str,
+    language: &str,
+    code: &str,
+) -> anyhow::Result<()> {
+    let out_path = out.join(format!("{}.{}", file_stem, extension));
+    eprintln!(
+        "   Writing {} bindings for {} to {}.",
+        language,
+        contract_name,
+        out_path.display()
+    );
+    fs::create_dir_all(out).context("Unable to create directory for the resulting bindings.")?;
+    fs::write(&out_path, code)
+        .with_context(|| format!("Unable to write the bindings to {}.", out_path.display()))
+}
+
+/// Convert a name from the schema, such as the name of an entrypoint, to
+/// upper camel case for use in identifiers. Names that start with a digit are
+/// prefixed with an underscore.
+fn pascal_case(name: &str) -> String {
+    let mut out = String::new();
+    for word in name.split(|c: char| !c.is_ascii_alphanumeric()) {
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            out.push(first.to_ascii_uppercase());
+            out.extend(chars);
+        }
+    }
+    if out.is_empty() || out.starts_with(|c: char| c.is_ascii_digit()) {
+        out.insert(0, '_');
+    }
+    out
+}
+
+/// The names of the init function and the entrypoints of a contract in
+/// upper camel case. Names that would otherwise clash get a numeric suffix.
+fn function_identifiers(contract: &ContractSchema) -> Vec<(Option<&str>, String)> {
+    let mut used = BTreeSet::new();
+    let functions = std::iter::once(None).chain(contract.receive.keys().map(|n| Some(n.as_str())));
+    functions
+        .map(|function| {
+            let base = pascal_case(function.unwrap_or("init"));
+            let mut identifier = base.clone();
+            let mut suffix = 2;
+            while !used.insert(identifier.clone()) {
+                identifier = format!("{}{}", base, suffix);
+                suffix += 1;
+            }
+            (function, identifier)
+        })
+        .collect()
+}
+
+/// The base64 encoding of the schema of a type, as embedded in the generated
+/// code.
+fn type_schema_base64(ty: &Type) -> String {
+    ENCODER.encode(to_bytes(ty))
+}
+
+/// Generate the TypeScript bindings of a single contract.
+fn typescript_contract(contract_name: &str, contract: &ContractSchema) -> String {
+    let mut code = String::new();
+    let _ = writeln!(
+        code,
+        "// This file was generated by `cargo concordium codegen typescript` from the schema of \
+         the contract '{}'.\n// Do not edit it by hand.\n",
+        contract_name
+    );
+    code.push_str(
+        "import { deserializeTypeValue, serializeTypeValue, toBuffer } from \
+         '@concordium/web-sdk';\nimport { Buffer } from 'buffer/';\n",
+    );
+    let _ = write!(
+        code,
+        "\n/** The name of the contract. */\nexport const CONTRACT_NAME = {};\n",
+        ts_string(contract_name)
+    );
+    for (function, identifier) in function_identifiers(contract) {
+        let Some(schema) = contract.function(function) else {
+            continue;
+        };
+        let description = match function {
+            Some(entrypoint) => format!("the entrypoint {}", ts_string(entrypoint)),
+            None => "the init function".to_string(),
+        };
+        if let Some(parameter) = &schema.parameter {
+            let name = format!("{}Parameter", identifier);
+            ts_type_alias(
+                &mut code,
+                &name,
+                &format!("Parameter of {}.", description),
+                parameter,
+            );
+            ts_serializer(&mut code, &name, parameter);
+        }
+        if let Some(return_value) = &schema.return_value {
+            let name = format!("{}ReturnValue", identifier);
+            ts_type_alias(
+                &mut code,
+                &name,
+                &format!("Return value of {}.", description),
+                return_value,
+            );
+            ts_deserializer(&mut code, &name, return_value);
+        }
+        if let Some(error) = &schema.error {
+            let name = format!("{}Error", identifier);
+            ts_type_alias(
+                &mut code,
+                &name,
+                &format!("Error of {}.", description),
+                error,
+            );
+            ts_deserializer(&mut code, &name, error);
+        }
+    }
+    if let Some(event) = &contract.event {
+        ts_type_alias(&mut code, "Event", "Event logged by the contract.", event);
+        ts_deserializer(&mut code, "Event", event);
+    }
+    code
+}
+
+fn ts_type_alias(code: &mut String, name: &str, doc: &str, ty: &Type) {
+    let _ = write!(
+        code,
+        "\n/** {} */\nexport type {} = {};\n",
+        doc,
+        name,
+        ts_type(ty, 0)
+    );
+}
+
+fn ts_serializer(code: &mut String, name: &str, ty: &Type) {
+    let _ = write!(
+        code,
+        "\nconst {schema} = '{base64}';\n\n/** Serialize a value of type `{name}` using the \
+         schema. */\nexport function serialize{name}(value: {name}): Buffer {{\n  return \
+         serializeTypeValue(value, toBuffer({schema}, 'base64'));\n}}\n",
+        schema = schema_constant(name),
+        base64 = type_schema_base64(ty),
+        name = name,
+    );
+}
+
+fn ts_deserializer(code: &mut String, name: &str, ty: &Type) {
+    let _ = write!(
+        code,
+        "\nconst {schema} = '{base64}';\n\n/** Deserialize a value of type `{name}` using the \
+         schema. */\nexport function deserialize{name}(value: Buffer): {name} {{\n  return \
+         deserializeTypeValue(value, toBuffer({schema}, 'base64')) as {name};\n}}\n",
+        schema = schema_constant(name),
+        base64 = type_schema_base64(ty),
+        name = name,
+    );
+}
+
+/// The name of the constant holding the schema of the type `name`, e.g.,
+/// `TRANSFER_PARAMETER_SCHEMA` for `TransferParameter`.
+fn schema_constant(name: &str) -> String {
+    let mut constant = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() && i > 0 {
+            constant.push('_');
+        }
+        constant.push(c.to_ascii_uppercase());
+    }
+    constant.push_str("_SCHEMA");
+    constant
+}
+
+/// A string literal in TypeScript.
+fn ts_string(s: &str) -> String {
+    serde_json::to_string(s).expect("Serializing a string to JSON cannot fail.")
+}
+
+/// A property name in TypeScript, quoted if it is not a valid identifier.
+fn ts_key(name: &str) -> String {
+    let is_identifier = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+    if is_identifier {
+        name.to_string()
+    } else {
+        ts_string(name)
+    }
+}
+
+fn indentation(level: usize) -> String {
+    "  ".repeat(level)
+}
+
+/// The TypeScript type of the JSON representation of values of the schema
+/// type, as used by `serializeTypeValue` and `deserializeTypeValue`.
+fn ts_type(ty: &Type, level: usize) -> String {
+    match ty {
+        Type::Unit => "[]".to_string(),
+        Type::Bool => "boolean".to_string(),
+        Type::U8 | Type::U16 | Type::U32 | Type::I8 | Type::I16 | Type::I32 => "number".to_string(),
+        Type::U64 | Type::I64 => "number | bigint".to_string(),
+        // Amounts are in micro CCD, byte lists and arrays in hex.
+        Type::U128
+        | Type::I128
+        | Type::ULeb128(_)
+        | Type::ILeb128(_)
+        | Type::Amount
+        | Type::AccountAddress
+        | Type::Timestamp
+        | Type::Duration
+        | Type::String(_)
+        | Type::ByteList(_)
+        | Type::ByteArray(_) => "string".to_string(),
+        Type::ContractAddress => {
+            "{ index: number | bigint; subindex: number | bigint }".to_string()
+        }
+        Type::ContractName(_) => "{ contract: string }".to_string(),
+        Type::ReceiveName(_) => "{ contract: string; func: string }".to_string(),
+        Type::Pair(left, right) => {
+            format!("[{}, {}]", ts_type(left, level), ts_type(right, level))
+        }
+        Type::List(_, elem) | Type::Set(_, elem) | Type::Array(_, elem) => {
+            format!("Array<{}>", ts_type(elem, level))
+        }
+        Type::Map(_, key, value) => format!(
+            "Array<[{}, {}]>",
+            ts_type(key, level),
+            ts_type(value, level)
+        ),
+        Type::Struct(fields) => ts_fields(fields, level),
+        Type::Enum(variants) => {
+            ts_variants(variants.iter().map(|(name, fields)| (name, fields)), level)
+        }
+        Type::TaggedEnum(variants) => ts_variants(
+            variants.values().map(|(name, fields)| (name, fields)),
+            level,
+        ),
+    }
+}
+
+/// Named fields are represented by an object, and unnamed fields by an array.
+fn ts_fields(fields: &Fields, level: usize) -> String {
+    match fields {
+        Fields::Named(fields) if !fields.is_empty() => {
+            let mut out = "{\n".to_string();
+            for (name, ty) in fields {
+                let _ = writeln!(
+                    out,
+                    "{}{}: {};",
+                    indentation(level + 1),
+                    ts_key(name),
+                    ts_type(ty, level + 1)
+                );
+            }
+            out.push_str(&indentation(level));
+            out.push('}');
+            out
+        }
+        Fields::Named(_) => "{}".to_string(),
+        Fields::Unnamed(fields) => {
+            let fields: Vec<_> = fields.iter().map(|ty| ts_type(ty, level)).collect();
+            format!("[{}]", fields.join(", "))
+        }
+        Fields::None => "[]".to_string(),
+    }
+}
+
+/// An enum is represented by an object with the name of the variant as its
+/// only property.
+fn ts_variants<'a>(
+    variants: impl Iterator<Item = (&'a String, &'a Fields)>,
+    level: usize,
+) -> String {
+    let mut out = String::new();
+    for (name, fields) in variants {
+        let _ = write!(
+            out,
+            "\n{}| {{ {}: {} }}",
+            indentation(level + 1),
+            ts_key(name),
+            ts_fields(fields, level + 1)
+        );
+    }
+    if out.is_empty() {
+        "never".to_string()
+    } else {
+        out
+    }
+}
+
+/// Generate Rust bindings for each contract in the schema, and write them to
+/// files named after the contracts in the `out` directory, for use as modules
+/// of a crate.
+pub(crate) fn write_rust(out: &Path, schema: &VersionedModuleSchema) -> anyhow::Result<()> {
+    for (counter, (contract_name, contract_schema)) in contract_schemas(schema).iter().enumerate() {
+        let code = RustModule::generate(contract_name, contract_schema);
+        // Module names cannot contain dashes.
+        let file_stem = file_stem(contract_name, counter).replace('-', "_");
+        write_bindings(out, contract_name, &file_stem, "rs", "Rust", &code)?;
+    }
+    Ok(())
+}
+
+/// The representation of a schema type in the generated Rust code. Structs
+/// and enums, and types at the top level, are generated as named types with
+/// their own implementations of `Serial` and `Deserial`. All other types are
+/// serialized by code generated where they are used, since the standard
+/// implementations do not support the size lengths of the schema.
+enum RustType {
+    /// A type whose standard implementations of `Serial` and `Deserial` match
+    /// the schema.
+    Plain(&'static str),
+    /// A generated type.
+    Named(String),
+    Pair(Box<RustType>, Box<RustType>),
+    List(SizeLength, Box<RustType>),
+    Set(SizeLength, Box<RustType>),
+    Map(SizeLength, Box<RustType>, Box<RustType>),
+    Array(u32, Box<RustType>),
+    String(SizeLength),
+    ContractName(SizeLength),
+    ReceiveName(SizeLength),
+    ByteList(SizeLength),
+    ByteArray(u32),
+    ULeb128(u32),
+    ILeb128(u32),
+}
+
+/// The fields of a generated struct or enum variant, with the Rust
+/// identifiers of named fields.
+enum RustFields {
+    Named(Vec<(String, RustType)>),
+    Unnamed(Vec<RustType>),
+    None,
+}
+
+/// The Rust module generated for a contract.
+#[derive(Default)]
+struct RustModule {
+    /// The definitions of the generated types and their implementations.
+    items: Vec<String>,
+    /// The names of the generated types.
+    names: BTreeSet<String>,
+    /// The name of the top-level type currently being generated, referred to
+    /// by the documentation of the types it uses.
+    root: String,
+    /// The names imported from `concordium_base::contracts_common`, besides
+    /// those used by every module.
+    imports: BTreeSet<&'static str>,
+    /// The names imported from `std::collections`.
+    collections: BTreeSet<&'static str>,
+    uses_uleb128: bool,
+    uses_ileb128: bool,
+}
+
+impl RustModule {
+    /// Generate the Rust bindings of a single contract.
+    fn generate(contract_name: &str, contract: &ContractSchema) -> String {
+        let mut module = Self::default();
+        for (function, identifier) in function_identifiers(contract) {
+            let Some(schema) = contract.function(function) else {
+                continue;
+            };
+            let description = match function {
+                Some(entrypoint) => format!("the entrypoint {:?}", entrypoint),
+                None => "the init function".to_string(),
+            };
+            let parts = [
+                ("Parameter", "Parameter", &schema.parameter),
+                ("ReturnValue", "Return value", &schema.return_value),
+                ("Error", "Error", &schema.error),
+            ];
+            for (suffix, part, ty) in parts {
+                if let Some(ty) = ty {
+                    let doc = format!("{} of {}.", part, description);
+                    module.define(&format!("{}{}", identifier, suffix), Some(&doc), ty);
+                }
+            }
+        }
+        if let Some(event) = &contract.event {
+            module.define("Event", Some("Event logged by the contract."), event);
+        }
+        if let Some(state) = &contract.state {
+            module.define("State", Some("State of the contract."), state);
+        }
+        module.render(contract_name)
+    }
+
+    /// Assemble the module from the generated items.
+    fn render(mut self, contract_name: &str) -> String {
+        let mut code = format!(
+            "//! Types for the contract {:?}, generated by `cargo concordium codegen rust` from\n//! \
+             the schema of the contract. Do not edit this file by hand.\n",
+            contract_name
+        );
+        if self.uses_uleb128 || self.uses_ileb128 {
+            self.imports.insert("ParseError");
+        }
+        let mut imports: Vec<_> = ["Deserial", "ParseResult", "Read", "Serial", "Write"]
+            .into_iter()
+            .chain(self.imports.iter().copied())
+            .collect();
+        imports.sort_unstable();
+        let _ = writeln!(
+            code,
+            "use concordium_base::contracts_common::{{{}}};",
+            imports.join(", ")
+        );
+        if !self.collections.is_empty() {
+            let collections: Vec<_> = self.collections.iter().copied().collect();
+            let _ = writeln!(
+                code,
+                "use std::collections::{{{}}};",
+                collections.join(", ")
+            );
+        }
+        let _ = write!(
+            code,
+            "\n/// The name of the contract.\npub const CONTRACT_NAME: &str = {:?};\n",
+            contract_name
+        );
+        for item in self.items.iter() {
+            code.push('\n');
+            code.push_str(item);
+        }
+        if self.uses_uleb128 {
+            code.push_str(ULEB128_HELPERS);
+        }
+        if self.uses_ileb128 {
+            code.push_str(ILEB128_HELPERS);
+        }
+        code
+    }
+
+    /// Reserve a unique name for a generated type, based on `name`.
+    fn reserve_name(&mut self, name: &str) -> String {
+        let mut unique = name.to_string();
+        let mut suffix = 2;
+        while !self.names.insert(unique.clone()) {
+            unique = format!("{}{}", name, suffix);
+            suffix += 1;
+        }
+        unique
+    }
+
+    /// Generate a named type for the schema type, together with its
+    /// implementations of `Serial` and `Deserial`, and return its name.
+    /// Types other than structs and enums are wrapped in a tuple struct. Types
+    /// at the top level are documented by `doc`, and the types they use refer
+    /// to them.
+    fn define(&mut self, name: &str, doc: Option<&str>, ty: &Type) -> String {
+        let name = self.reserve_name(name);
+        let doc = match doc {
+            Some(doc) => {
+                self.root = name.clone();
+                doc.to_string()
+            }
+            None => format!("Part of [`{}`].", self.root),
+        };
+        let doc = doc.as_str();
+        let item = match ty {
+            Type::Struct(fields) => {
+                let fields = self.fields(&name, fields);
+                self.struct_item(&name, doc, &fields)
+            }
+            Type::Enum(variants) => {
+                self.imports.insert("ParseError");
+                let tag_type = length_type(&enum_tag_size(variants.len()));
+                let variants: Vec<_> = variants
+                    .iter()
+                    .enumerate()
+                    .map(|(tag, (variant, fields))| {
+                        let fields =
+                            self.fields(&format!("{}{}", name, pascal_case(variant)), fields);
+                        (tag.to_string(), variant.as_str(), fields)
+                    })
+                    .collect();
+                self.enum_item(&name, doc, tag_type, &variants)
+            }
+            Type::TaggedEnum(variants) => {
+                self.imports.insert("ParseError");
+                let variants: Vec<_> = variants
+                    .iter()
+                    .map(|(tag, (variant, fields))| {
+                        let fields =
+                            self.fields(&format!("{}{}", name, pascal_case(variant)), fields);
+                        (tag.to_string(), variant.as_str(), fields)
+                    })
+                    .collect();
+                self.enum_item(&name, doc, "u8", &variants)
+            }
+            ty => {
+                let ty = self.rust_type(&name, ty);
+                self.struct_item(&name, doc, &RustFields::Unnamed(vec![ty]))
+            }
+        };
+        self.items.push(item);
+        name
+    }
+
+    /// Convert the fields of a struct or enum variant. Types of fields that
+    /// need to be named are named after `parent` and the field.
+    fn fields(&mut self, parent: &str, fields: &Fields) -> RustFields {
+        match fields {
+            Fields::Named(fields) => {
+                let mut identifiers = BTreeSet::new();
+                let fields = fields
+                    .iter()
+                    .map(|(field, ty)| {
+                        let base = rust_identifier(field);
+                        let mut identifier = base.clone();
+                        let mut suffix = 2;
+                        while !identifiers.insert(identifier.clone()) {
+                            identifier = format!("{}_{}", base, suffix);
+                            suffix += 1;
+                        }
+                        let ty = self.rust_type(&format!("{}{}", parent, pascal_case(field)), ty);
+                        (identifier, ty)
+                    })
+                    .collect();
+                RustFields::Named(fields)
+            }
+            Fields::Unnamed(fields) => RustFields::Unnamed(
+                fields
+                    .iter()
+                    .enumerate()
+                    .map(|(i, ty)| self.rust_type(&format!("{}Field{}", parent, i), ty))
+                    .collect(),
+            ),
+            Fields::None => RustFields::None,
+        }
+    }
+
+    /// Convert a schema type. Structs and enums are generated as named types
+    /// called `name`.
+    fn rust_type(&mut self, name: &str, ty: &Type) -> RustType {
+        match ty {
+            Type::Unit => RustType::Plain("()"),
+            Type::Bool => RustType::Plain("bool"),
+            Type::U8 => RustType::Plain("u8"),
+            Type::U16 => RustType::Plain("u16"),
+            Type::U32 => RustType::Plain("u32"),
+            Type::U64 => RustType::Plain("u64"),
+            Type::U128 => RustType::Plain("u128"),
+            Type::I8 => RustType::Plain("i8"),
+            Type::I16 => RustType::Plain("i16"),
+            Type::I32 => RustType::Plain("i32"),
+            Type::I64 => RustType::Plain("i64"),
+            Type::I128 => RustType::Plain("i128"),
+            Type::Amount => self.imported("Amount"),
+            Type::AccountAddress => self.imported("AccountAddress"),
+            Type::ContractAddress => self.imported("ContractAddress"),
+            Type::Timestamp => self.imported("Timestamp"),
+            Type::Duration => self.imported("Duration"),
+            Type::Pair(left, right) => RustType::Pair(
+                Box::new(self.rust_type(&format!("{}First", name), left)),
+                Box::new(self.rust_type(&format!("{}Second", name), right)),
+            ),
+            Type::List(len, elem) => {
+                self.imports.insert("ParseError");
+                RustType::List(
+                    *len,
+                    Box::new(self.rust_type(&format!("{}Item", name), elem)),
+                )
+            }
+            Type::Set(len, elem) => {
+                self.imports.insert("ParseError");
+                self.collections.insert("BTreeSet");
+                RustType::Set(
+                    *len,
+                    Box::new(self.rust_type(&format!("{}Item", name), elem)),
+                )
+            }
+            Type::Map(len, key, value) => {
+                self.imports.insert("ParseError");
+                self.collections.insert("BTreeMap");
+                RustType::Map(
+                    *len,
+                    Box::new(self.rust_type(&format!("{}Key", name), key)),
+                    Box::new(self.rust_type(&format!("{}Value", name), value)),
+                )
+            }
+            Type::Array(len, elem) => {
+                self.imports.insert("ParseError");
+                RustType::Array(
+                    *len,
+                    Box::new(self.rust_type(&format!("{}Item", name), elem)),
+                )
+            }
+            Type::Struct(_) | Type::Enum(_) | Type::TaggedEnum(_) => {
+                RustType::Named(self.define(name, None, ty))
+            }
+            Type::String(len) => {
+                self.imports.insert("ParseError");
+                RustType::String(*len)
+            }
+            Type::ContractName(len) => {
+                self.imports.insert("ParseError");
+                self.imports.insert("OwnedContractName");
+                RustType::ContractName(*len)
+            }
+            Type::ReceiveName(len) => {
+                self.imports.insert("ParseError");
+                self.imports.insert("OwnedReceiveName");
+                RustType::ReceiveName(*len)
+            }
+            Type::ByteList(len) => {
+                self.imports.insert("ParseError");
+                RustType::ByteList(*len)
+            }
+            Type::ByteArray(len) => RustType::ByteArray(*len),
+            Type::ULeb128(max_bytes) => {
+                self.uses_uleb128 = true;
+                RustType::ULeb128(*max_bytes)
+            }
+            Type::ILeb128(max_bytes) => {
+                self.uses_ileb128 = true;
+                RustType::ILeb128(*max_bytes)
+            }
+        }
+    }
+
+    /// A type from `concordium_base::contracts_common`.
+    fn imported(&mut self, ty: &'static str) -> RustType {
+        self.imports.insert(ty);
+        RustType::Plain(ty)
+    }
+
+    fn struct_item(&self, name: &str, doc: &str, fields: &RustFields) -> String {
+        let mut item = format!("/// {}\n{}\npub struct {}", doc, RUST_DERIVES, name);
+        let pattern = match fields {
+            RustFields::Named(fields) => {
+                item.push_str(" {\n");
+                for (identifier, ty) in fields {
+                    let _ = writeln!(item, "    pub {}: {},", identifier, ty.rust_type());
+                }
+                item.push_str("}\n");
+                let bindings: Vec<_> = fields
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (identifier, _))| format!("{}: v{}", identifier, i))
+                    .collect();
+                format!("Self {{ {} }}", bindings.join(", "))
+            }
+            RustFields::Unnamed(fields) => {
+                let types: Vec<_> = fields
+                    .iter()
+                    .map(|ty| format!("pub {}", ty.rust_type()))
+                    .collect();
+                let _ = writeln!(item, "({});", types.join(", "));
+                let bindings: Vec<_> = (0..fields.len()).map(|i| format!("v{}", i)).collect();
+                format!("Self({})", bindings.join(", "))
+            }
+            RustFields::None => {
+                item.push_str(";\n");
+                String::new()
+            }
+        };
+
+        let mut serial = String::new();
+        if !pattern.is_empty() {
+            let _ = writeln!(serial, "        let {} = self;", pattern);
+            let mut counter = 0;
+            for (i, ty) in fields.types().enumerate() {
+                serial.push_str(&ty.serial(&format!("v{}", i), 2, &mut counter));
+            }
+        }
+        let (out, source) = match fields {
+            RustFields::None => ("_out", "_source"),
+            _ => ("out", "source"),
+        };
+        let _ = write!(
+            item,
+            "\nimpl Serial for {name} {{\n    fn serial<W: Write>(&self, {out}: &mut W) -> \
+             Result<(), W::Err> {{\n{serial}        Ok(())\n    }}\n}}\n",
+        );
+        let _ = write!(
+            item,
+            "\nimpl Deserial for {name} {{\n    fn deserial<R: Read>({source}: &mut R) -> \
+             ParseResult<Self> {{\n        Ok({constructor})\n    }}\n}}\n",
+            constructor = fields.constructor("Self", 2),
+        );
+        item
+    }
+
+    fn enum_item(
+        &self,
+        name: &str,
+        doc: &str,
+        tag_type: &str,
+        variants: &[(String, &str, RustFields)],
+    ) -> String {
+        let mut item = format!("/// {}\n{}\npub enum {} {{\n", doc, RUST_DERIVES, name);
+        let mut identifiers = BTreeSet::new();
+        let variants: Vec<_> = variants
+            .iter()
+            .map(|(tag, variant, fields)| {
+                let base = match pascal_case(variant).as_str() {
+                    "Self" => "Self_".to_string(),
+                    identifier => identifier.to_string(),
+                };
+                let mut identifier = base.clone();
+                let mut suffix = 2;
+                while !identifiers.insert(identifier.clone()) {
+                    identifier = format!("{}{}", base, suffix);
+                    suffix += 1;
+                }
+                (tag, identifier, fields)
+            })
+            .collect();
+        for (_, identifier, fields) in variants.iter() {
+            match fields {
+                RustFields::Named(fields) => {
+                    let _ = writeln!(item, "    {} {{", identifier);
+                    for (field, ty) in fields {
+                        let _ = writeln!(item, "        {}: {},", field, ty.rust_type());
+                    }
+                    item.push_str("    },\n");
+                }
+                RustFields::Unnamed(fields) => {
+                    let types: Vec<_> = fields.iter().map(RustType::rust_type).collect();
+                    let _ = writeln!(item, "    {}({}),", identifier, types.join(", "));
+                }
+                RustFields::None => {
+                    let _ = writeln!(item, "    {},", identifier);
+                }
+            }
+        }
+        item.push_str("}\n");
+
+        let mut serial = String::new();
+        let mut deserial = String::new();
+        if variants.is_empty() {
+            serial.push_str("        match *self {}\n");
+            deserial.push_str("        Err(ParseError::default())\n");
+        } else {
+            serial.push_str("        match self {\n");
+            let _ = writeln!(
+                deserial,
+                "        let tag = {}::deserial(source)?;",
+                tag_type
+            );
+            deserial.push_str("        match tag {\n");
+            for (tag, identifier, fields) in variants.iter() {
+                let pattern = match fields {
+                    RustFields::Named(fields) => {
+                        let bindings: Vec<_> = fields
+                            .iter()
+                            .enumerate()
+                            .map(|(i, (field, _))| format!("{}: v{}", field, i))
+                            .collect();
+                        format!("Self::{} {{ {} }}", identifier, bindings.join(", "))
+                    }
+                    RustFields::Unnamed(fields) => {
+                        let bindings: Vec<_> =
+                            (0..fields.len()).map(|i| format!("v{}", i)).collect();
+                        format!("Self::{}({})", identifier, bindings.join(", "))
+                    }
+                    RustFields::None => format!("Self::{}", identifier),
+                };
+                let _ = writeln!(serial, "            {} => {{", pattern);
+                let _ = writeln!(serial, "                {}{}.serial(out)?;", tag, tag_type);
+                let mut counter = 0;
+                for (i, ty) in fields.types().enumerate() {
+                    serial.push_str(&ty.serial(&format!("v{}", i), 4, &mut counter));
+                }
+                serial.push_str("            }\n");
+                let _ = writeln!(
+                    deserial,
+                    "            {} => Ok({}),",
+                    tag,
+                    fields.constructor(&format!("Self::{}", identifier), 3)
+                );
+            }
+            serial.push_str("        }\n        Ok(())\n");
+            deserial.push_str("            _ => Err(ParseError::default()),\n        }\n");
+        }
+        let _ = write!(
+            item,
+            "\nimpl Serial for {name} {{\n    fn serial<W: Write>(&self, out: &mut W) -> \
+             Result<(), W::Err> {{\n{serial}    }}\n}}\n",
+        );
+        let _ = write!(
+            item,
+            "\nimpl Deserial for {name} {{\n    fn deserial<R: Read>(source: &mut R) -> \
+             ParseResult<Self> {{\n{deserial}    }}\n}}\n",
+        );
+        item
+    }
+}
+
+impl RustFields {
+    fn types(&self) -> Box<dyn Iterator<Item = &RustType> + '_> {
+        match self {
+            RustFields::Named(fields) => Box::new(fields.iter().map(|(_, ty)| ty)),
+            RustFields::Unnamed(fields) => Box::new(fields.iter()),
+            RustFields::None => Box::new(std::iter::empty()),
+        }
+    }
+
+    /// An expression deserializing the fields in order and constructing
+    /// `path` from them.
+    fn constructor(&self, path: &str, level: usize) -> String {
+        match self {
+            RustFields::Named(fields) => {
+                let mut out = format!("{} {{\n", path);
+                for (identifier, ty) in fields {
+                    let _ = writeln!(
+                        out,
+                        "{}{}: {},",
+                        rust_indentation(level + 1),
+                        identifier,
+                        ty.deserial(level + 1)
+                    );
+                }
+                out.push_str(&rust_indentation(level));
+                out.push('}');
+                out
+            }
+            RustFields::Unnamed(fields) => {
+                let mut out = format!("{}(\n", path);
+                for ty in fields {
+                    let _ = writeln!(
+                        out,
+                        "{}{},",
+                        rust_indentation(level + 1),
+                        ty.deserial(level + 1)
+                    );
+                }
+                out.push_str(&rust_indentation(level));
+                out.push(')');
+                out
+            }
+            RustFields::None => path.to_string(),
+        }
+    }
+}
+
+impl RustType {
+    /// The Rust type.
+    fn rust_type(&self) -> String {
+        match self {
+            RustType::Plain(ty) => ty.to_string(),
+            RustType::Named(name) => name.clone(),
+            RustType::Pair(left, right) => {
+                format!("({}, {})", left.rust_type(), right.rust_type())
+            }
+            RustType::List(_, elem) => format!("Vec<{}>", elem.rust_type()),
+            RustType::Set(_, elem) => format!("BTreeSet<{}>", elem.rust_type()),
+            RustType::Map(_, key, value) => {
+                format!("BTreeMap<{}, {}>", key.rust_type(), value.rust_type())
+            }
+            RustType::Array(len, elem) => format!("[{}; {}]", elem.rust_type(), len),
+            RustType::String(_) => "String".to_string(),
+            RustType::ContractName(_) => "OwnedContractName".to_string(),
+            RustType::ReceiveName(_) => "OwnedReceiveName".to_string(),
+            RustType::ByteList(_) => "Vec<u8>".to_string(),
+            RustType::ByteArray(len) => format!("[u8; {}]", len),
+            RustType::ULeb128(_) => "u128".to_string(),
+            RustType::ILeb128(_) => "i128".to_string(),
+        }
+    }
+
+    /// Statements serializing the value referenced by the variable `value`
+    /// into `out`. Fresh variables are numbered using `counter`.
+    fn serial(&self, value: &str, level: usize, counter: &mut usize) -> String {
+        let indent = rust_indentation(level);
+        *counter += 1;
+        let n = *counter;
+        match self {
+            RustType::Plain(_) | RustType::Named(_) => {
+                format!("{}{}.serial(out)?;\n", indent, value)
+            }
+            RustType::Pair(left, right) => {
+                let mut out = format!("{}let (l{n}, r{n}) = {};\n", indent, value, n = n);
+                out.push_str(&left.serial(&format!("l{}", n), level, counter));
+                out.push_str(&right.serial(&format!("r{}", n), level, counter));
+                out
+            }
+            RustType::List(len, elem) | RustType::Set(len, elem) => {
+                let mut out = serial_length(len, &format!("{}.len()", value), level);
+                let _ = writeln!(out, "{}for x{} in {}.iter() {{", indent, n, value);
+                out.push_str(&elem.serial(&format!("x{}", n), level + 1, counter));
+                let _ = writeln!(out, "{}}}", indent);
+                out
+            }
+            RustType::Map(len, key, elem) => {
+                let mut out = serial_length(len, &format!("{}.len()", value), level);
+                let _ = writeln!(
+                    out,
+                    "{}for (k{n}, x{n}) in {}.iter() {{",
+                    indent,
+                    value,
+                    n = n
+                );
+                out.push_str(&key.serial(&format!("k{}", n), level + 1, counter));
+                out.push_str(&elem.serial(&format!("x{}", n), level + 1, counter));
+                let _ = writeln!(out, "{}}}", indent);
+                out
+            }
+            RustType::Array(_, elem) => {
+                let mut out = format!("{}for x{} in {}.iter() {{\n", indent, n, value);
+                out.push_str(&elem.serial(&format!("x{}", n), level + 1, counter));
+                let _ = writeln!(out, "{}}}", indent);
+                out
+            }
+            RustType::String(len) | RustType::ContractName(len) | RustType::ReceiveName(len) => {
+                let bytes = match self {
+                    RustType::ContractName(_) => {
+                        format!("{}.as_contract_name().get_chain_name().as_bytes()", value)
+                    }
+                    RustType::ReceiveName(_) => {
+                        format!("{}.as_receive_name().get_chain_name().as_bytes()", value)
+                    }
+                    _ => format!("{}.as_bytes()", value),
+                };
+                let mut out = format!("{}let s{} = {};\n", indent, n, bytes);
+                out.push_str(&serial_length(len, &format!("s{}.len()", n), level));
+                let _ = writeln!(out, "{}out.write_all(s{})?;", indent, n);
+                out
+            }
+            RustType::ByteList(len) => {
+                let mut out = serial_length(len, &format!("{}.len()", value), level);
+                let _ = writeln!(out, "{}out.write_all({})?;", indent, value);
+                out
+            }
+            RustType::ByteArray(_) => format!("{}out.write_all({})?;\n", indent, value),
+            RustType::ULeb128(_) => format!("{}serial_uleb128(*{}, out)?;\n", indent, value),
+            RustType::ILeb128(_) => format!("{}serial_ileb128(*{}, out)?;\n", indent, value),
+        }
+    }
+
+    /// An expression deserializing a value from `source`, formatted for use
+    /// at the given indentation level.
+    fn deserial(&self, level: usize) -> String {
+        match self {
+            RustType::Plain("()") => "<()>::deserial(source)?".to_string(),
+            RustType::Plain(ty) => format!("{}::deserial(source)?", ty),
+            RustType::Named(name) => format!("{}::deserial(source)?", name),
+            RustType::Pair(left, right) => {
+                format!("({}, {})", left.deserial(level), right.deserial(level))
+            }
+            RustType::List(len, elem) => rust_block(
+                &[
+                    format!("let len = {};", deserial_length(len)),
+                    "let mut items = Vec::new();".to_string(),
+                    "for _ in 0..len {".to_string(),
+                    format!("    items.push({});", elem.deserial(level + 2)),
+                    "}".to_string(),
+                    "items".to_string(),
+                ],
+                level,
+            ),
+            RustType::Set(len, elem) => rust_block(
+                &[
+                    format!("let len = {};", deserial_length(len)),
+                    "let mut items = BTreeSet::new();".to_string(),
+                    "for _ in 0..len {".to_string(),
+                    format!("    items.insert({});", elem.deserial(level + 2)),
+                    "}".to_string(),
+                    "items".to_string(),
+                ],
+                level,
+            ),
+            RustType::Map(len, key, value) => rust_block(
+                &[
+                    format!("let len = {};", deserial_length(len)),
+                    "let mut items = BTreeMap::new();".to_string(),
+                    "for _ in 0..len {".to_string(),
+                    format!("    let key = {};", key.deserial(level + 2)),
+                    format!("    let value = {};", value.deserial(level + 2)),
+                    "    items.insert(key, value);".to_string(),
+                    "}".to_string(),
+                    "items".to_string(),
+                ],
+                level,
+            ),
+            RustType::Array(len, elem) => rust_block(
+                &[
+                    "let mut items = Vec::new();".to_string(),
+                    format!("for _ in 0..{} {{", len),
+                    format!("    items.push({});", elem.deserial(level + 2)),
+                    "}".to_string(),
+                    format!(
+                        "<[{}; {}]>::try_from(items).map_err(|_| ParseError::default())?",
+                        elem.rust_type(),
+                        len
+                    ),
+                ],
+                level,
+            ),
+            RustType::String(len) => deserial_string(len, "", level),
+            RustType::ContractName(len) => deserial_string(len, "OwnedContractName::new", level),
+            RustType::ReceiveName(len) => deserial_string(len, "OwnedReceiveName::new", level),
+            RustType::ByteList(len) => rust_block(
+                &[
+                    format!("let len = {};", deserial_length(len)),
+                    "let mut bytes = vec![0u8; len];".to_string(),
+                    "source.read_exact(&mut bytes)?;".to_string(),
+                    "bytes".to_string(),
+                ],
+                level,
+            ),
+            RustType::ByteArray(len) => rust_block(
+                &[
+                    format!("let mut bytes = [0u8; {}];", len),
+                    "source.read_exact(&mut bytes)?;".to_string(),
+                    "bytes".to_string(),
+                ],
+                level,
+            ),
+            RustType::ULeb128(max_bytes) => format!("deserial_uleb128(source, {})?", max_bytes),
+            RustType::ILeb128(max_bytes) => format!("deserial_ileb128(source, {})?", max_bytes),
+        }
+    }
+}
+
+/// The derives of the generated types.
+const RUST_DERIVES: &str = "#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]";
+
+fn rust_indentation(level: usize) -> String {
+    "    ".repeat(level)
+}
+
+/// A block expression with the given lines, formatted for use at the given
+/// indentation level.
+fn rust_block(lines: &[String], level: usize) -> String {
+    let mut out = "{\n".to_string();
+    for line in lines {
+        let _ = writeln!(out, "{}{}", rust_indentation(level + 1), line);
+    }
+    out.push_str(&rust_indentation(level));
+    out.push('}');
+    out
+}
+
+/// The Rust integer type of a length prefix.
+fn length_type(len: &SizeLength) -> &'static str {
+    match len {
+        SizeLength::U8 => "u8",
+        SizeLength::U16 => "u16",
+        SizeLength::U32 => "u32",
+        SizeLength::U64 => "u64",
+    }
+}
+
+/// A statement serializing the length `len_expr` as a length prefix.
+fn serial_length(len: &SizeLength, len_expr: &str, level: usize) -> String {
+    format!(
+        "{}{}::try_from({}).map_err(|_| W::Err::default())?.serial(out)?;\n",
+        rust_indentation(level),
+        length_type(len),
+        len_expr
+    )
+}
+
+/// An expression deserializing a length prefix as a `usize`.
+fn deserial_length(len: &SizeLength) -> String {
+    format!(
+        "usize::try_from({}::deserial(source)?).map_err(|_| ParseError::default())?",
+        length_type(len)
+    )
+}
+
+/// An expression deserializing a string with a length prefix, and passing it
+/// to `constructor` if it is not empty.
+fn deserial_string(len: &SizeLength, constructor: &str, level: usize) -> String {
+    let mut lines = vec![
+        format!("let len = {};", deserial_length(len)),
+        "let mut bytes = vec![0u8; len];".to_string(),
+        "source.read_exact(&mut bytes)?;".to_string(),
+        "let string = String::from_utf8(bytes).map_err(|_| ParseError::default())?;".to_string(),
+    ];
+    if constructor.is_empty() {
+        lines.push("string".to_string());
+    } else {
+        lines.push(format!(
+            "{}(string).map_err(|_| ParseError::default())?",
+            constructor
+        ));
+    }
+    rust_block(&lines, level)
+}
+
+/// A Rust identifier for a field name from the schema. Characters that are
+/// not allowed are replaced by underscores, and keywords are escaped.
+fn rust_identifier(name: &str) -> String {
+    let mut identifier: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if identifier.is_empty() || identifier.starts_with(|c: char| c.is_ascii_digit()) {
+        identifier.insert(0, '_');
+    }
+    match identifier.as_str() {
+        "_" | "self" | "Self" | "super" | "crate" => identifier.push('_'),
+        "as" | "async" | "await" | "break" | "const" | "continue" | "dyn" | "else" | "enum"
+        | "extern" | "false" | "fn" | "for" | "if" | "impl" | "in" | "let" | "loop" | "match"
+        | "mod" | "move" | "mut" | "pub" | "ref" | "return" | "static" | "struct" | "trait"
+        | "true" | "try" | "type" | "unsafe" | "use" | "where" | "while" | "abstract"
+        | "become" | "box" | "do" | "final" | "macro" | "override" | "priv" | "typeof"
+        | "unsized" | "virtual" | "yield" => identifier.insert_str(0, "r#"),
+        _ => {}
+    }
+    identifier
+}
+
+/// The LEB128 encoding of unsigned integers, used by `ULeb128` types.
+const ULEB128_HELPERS: &str = r#"
+fn serial_uleb128<W: Write>(mut value: u128, out: &mut W) -> Result<(), W::Err> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            return byte.serial(out);
+        }
+        (byte | 0x80).serial(out)?;
+    }
+}
+
+fn deserial_uleb128<R: Read>(source: &mut R, max_bytes: u32) -> ParseResult<u128> {
+    let mut value = 0u128;
+    for i in 0..max_bytes.min(19) {
+        let byte = u8::deserial(source)?;
+        value |= u128::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(ParseError::default())
+}
+"#;
+
+/// The LEB128 encoding of signed integers, used by `ILeb128` types.
+const ILEB128_HELPERS: &str = r#"
+fn serial_ileb128<W: Write>(mut value: i128, out: &mut W) -> Result<(), W::Err> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+            return byte.serial(out);
+        }
+        (byte | 0x80).serial(out)?;
+    }
+}
+
+fn deserial_ileb128<R: Read>(source: &mut R, max_bytes: u32) -> ParseResult<i128> {
+    let mut value = 0i128;
+    let mut shift = 0;
+    for _ in 0..max_bytes.min(19) {
+        let byte = u8::deserial(source)?;
+        value |= i128::from(byte & 0x7f) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            if shift < 128 && byte & 0x40 != 0 {
+                value |= -1i128 << shift;
+            }
+            return Ok(value);
+        }
+    }
+    Err(ParseError::default())
+}
+"#;
@@ -1,7 +1,7 @@
 use anyhow::anyhow;
 use concordium_base::contracts_common::{
-    self, AccountAddress, Address, Amount, ContractAddress, EntrypointName, OwnedEntrypointName,
-    OwnedPolicy, Serial, SlotTime,
+    self, AccountAddress, Address, Amount, ChainMetadata, ContractAddress, EntrypointName,
+    OwnedEntrypointName, OwnedPolicy, Serial, SlotTime,
 };
 use concordium_smart_contract_engine::{v0, v1, ExecResult};
 use serde::Deserialize;
@@ -10,7 +10,7 @@ use serde::Deserialize;
 /// Used when simulating contracts to allow the user to only specify the
 /// necessary context fields.
 /// The default value is `None` for all `Option` fields.
-#[derive(serde::Deserialize, Default)]
+#[derive(serde::Deserialize, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct ChainMetadataOpt {
     slot_time: Option<SlotTime>,
@@ -27,7 +27,7 @@ impl v0::HasChainMetadata for ChainMetadataOpt {
 /// context fields used by the contract.
 /// The default value is `None` for all `Option` fields and the default of
 /// `ChainMetadataOpt` for `metadata`.
-#[derive(serde::Deserialize, Default)]
+#[derive(serde::Deserialize, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct InitContextOpt {
     #[serde(default)]
@@ -83,7 +83,7 @@ fn deserialize_optional_address<'de, D: serde::de::Deserializer<'de>>(
 /// context fields used by the contract.
 /// The default value is `None` for all `Option` fields and the default of
 /// `ChainMetadataOpt` for `metadata`.
-#[derive(serde::Deserialize, Default)]
+#[derive(serde::Deserialize, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct ReceiveContextOpt {
     #[serde(default)]
@@ -151,7 +151,7 @@ fn unwrap_ctx_field<A>(opt: Option<A>, name: &str) -> ExecResult<A> {
 /// context fields used by the contract.
 /// The default value is `None` for all `Option` fields and the default of
 /// `ChainMetadataOpt` for `metadata`.
-#[derive(serde::Deserialize, Default)]
+#[derive(serde::Deserialize, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct ReceiveContextV1Opt {
     #[serde(flatten)]
@@ -194,6 +194,38 @@ impl v0::HasReceiveContext for ReceiveContextV1Opt {
     }
 }
 
+impl ReceiveContextV1Opt {
+    /// Convert into a complete receive context, as needed when simulating
+    /// calls to other contracts.
+    /// The `sender` and `owner` default to the `invoker`, `senderPolicies`
+    /// defaults to no policies and `entrypoint` defaults to the provided
+    /// entrypoint. All other fields must be present.
+    pub(crate) fn into_complete(
+        self,
+        entrypoint: OwnedEntrypointName,
+    ) -> ExecResult<v1::ReceiveContext<v0::OwnedPolicyBytes>> {
+        let common = self.common;
+        let invoker = unwrap_ctx_field(common.invoker, "invoker")?;
+        let sender_policies = common
+            .sender_policies
+            .unwrap_or_else(|| contracts_common::to_bytes(&0u16));
+        Ok(v1::ReceiveContext {
+            common: v0::ReceiveContext {
+                metadata: ChainMetadata {
+                    slot_time: unwrap_ctx_field(common.metadata.slot_time, "metadata.slotTime")?,
+                },
+                invoker,
+                self_address: unwrap_ctx_field(common.self_address, "selfAddress")?,
+                self_balance: unwrap_ctx_field(common.self_balance, "selfBalance")?,
+                sender: common.sender.unwrap_or(Address::Account(invoker)),
+                owner: common.owner.unwrap_or(invoker),
+                sender_policies,
+            },
+            entrypoint: self.entrypoint.unwrap_or(entrypoint),
+        })
+    }
+}
+
 impl v1::HasReceiveContext for ReceiveContextV1Opt {
     fn entrypoint(&self) -> ExecResult<EntrypointName<'_>> {
         let ep = unwrap_ctx_field(self.entrypoint.as_ref(), "entrypoint")?;
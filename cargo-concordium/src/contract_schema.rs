@@ -0,0 +1,163 @@
+//! A view of a contract schema that does not depend on the version of the
+//! module schema it was extracted from.
+use concordium_base::contracts_common::{
+    schema::{FunctionV1, FunctionV2, SizeLength, Type, VersionedModuleSchema},
+    to_bytes,
+};
+use std::collections::BTreeMap;
+
+/// The schemas of a single function (init or receive) of a contract.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct FunctionSchema {
+    pub parameter: Option<Type>,
+    pub return_value: Option<Type>,
+    pub error: Option<Type>,
+    /// The size of the function schema as embedded in the module.
+    pub size: usize,
+}
+
+/// The schema of a single contract. Fields that are not supported by the
+/// version of the module schema are `None`, e.g., the event type is only
+/// present in V3 schemas, and the state type only in V0 schemas.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ContractSchema {
+    pub state: Option<Type>,
+    pub init: Option<FunctionSchema>,
+    pub receive: BTreeMap<String, FunctionSchema>,
+    pub event: Option<Type>,
+    /// The size of the contract schema as embedded in the module.
+    pub size: usize,
+}
+
+impl ContractSchema {
+    /// Get the schema of the init function if `entrypoint` is `None`, and the
+    /// schema of the given entrypoint otherwise.
+    pub fn function(&self, entrypoint: Option<&str>) -> Option<&FunctionSchema> {
+        match entrypoint {
+            Some(entrypoint) => self.receive.get(entrypoint),
+            None => self.init.as_ref(),
+        }
+    }
+}
+
+impl FunctionSchema {
+    /// A function schema with only a parameter type, as used by V0 schemas.
+    fn from_parameter(parameter: &Type) -> Self {
+        Self {
+            parameter: Some(parameter.clone()),
+            return_value: None,
+            error: None,
+            size: to_bytes(parameter).len(),
+        }
+    }
+
+    fn from_v1(schema: &FunctionV1) -> Self {
+        Self {
+            parameter: schema.parameter().cloned(),
+            return_value: schema.return_value().cloned(),
+            error: None,
+            size: to_bytes(schema).len(),
+        }
+    }
+
+    fn from_v2(schema: &FunctionV2) -> Self {
+        Self {
+            parameter: schema.parameter.clone(),
+            return_value: schema.return_value.clone(),
+            error: schema.error.clone(),
+            size: to_bytes(schema).len(),
+        }
+    }
+}
+
+/// Extract the schemas of all the contracts in the module schema, indexed by
+/// the contract name.
+pub(crate) fn contract_schemas(schema: &VersionedModuleSchema) -> BTreeMap<String, ContractSchema> {
+    match schema {
+        VersionedModuleSchema::V0(module_schema) => module_schema
+            .contracts
+            .iter()
+            .map(|(name, contract)| {
+                let schema = ContractSchema {
+                    state: contract.state.clone(),
+                    init: contract.init.as_ref().map(FunctionSchema::from_parameter),
+                    receive: contract
+                        .receive
+                        .iter()
+                        .map(|(n, ty)| (n.clone(), FunctionSchema::from_parameter(ty)))
+                        .collect(),
+                    event: None,
+                    size: to_bytes(contract).len(),
+                };
+                (name.clone(), schema)
+            })
+            .collect(),
+        VersionedModuleSchema::V1(module_schema) => module_schema
+            .contracts
+            .iter()
+            .map(|(name, contract)| {
+                let schema = ContractSchema {
+                    state: None,
+                    init: contract.init.as_ref().map(FunctionSchema::from_v1),
+                    receive: contract
+                        .receive
+                        .iter()
+                        .map(|(n, f)| (n.clone(), FunctionSchema::from_v1(f)))
+                        .collect(),
+                    event: None,
+                    size: to_bytes(contract).len(),
+                };
+                (name.clone(), schema)
+            })
+            .collect(),
+        VersionedModuleSchema::V2(module_schema) => module_schema
+            .contracts
+            .iter()
+            .map(|(name, contract)| {
+                let schema = ContractSchema {
+                    state: None,
+                    init: contract.init.as_ref().map(FunctionSchema::from_v2),
+                    receive: contract
+                        .receive
+                        .iter()
+                        .map(|(n, f)| (n.clone(), FunctionSchema::from_v2(f)))
+                        .collect(),
+                    event: None,
+                    size: to_bytes(contract).len(),
+                };
+                (name.clone(), schema)
+            })
+            .collect(),
+        VersionedModuleSchema::V3(module_schema) => module_schema
+            .contracts
+            .iter()
+            .map(|(name, contract)| {
+                let schema = ContractSchema {
+                    state: None,
+                    init: contract.init.as_ref().map(FunctionSchema::from_v2),
+                    receive: contract
+                        .receive
+                        .iter()
+                        .map(|(n, f)| (n.clone(), FunctionSchema::from_v2(f)))
+                        .collect(),
+                    event: contract.event.clone(),
+                    size: to_bytes(contract).len(),
+                };
+                (name.clone(), schema)
+            })
+            .collect(),
+    }
+}
+
+/// The size of the tag of an enum with the given number of variants. The tag
+/// is a `u8` for at most 256 variants, a `u16` for at most 65536 variants, and
+/// a `u32` otherwise.
+pub(crate) fn enum_tag_size(variants: usize) -> SizeLength {
+    if variants <= 256 {
+        SizeLength::U8
+    } else if variants <= 65536 {
+        SizeLength::U16
+    } else {
+        SizeLength::U32
+    }
+}
@@ -0,0 +1,196 @@
+//! Offline estimates of the NRG and CCD cost of deploying a module and of the
+//! transactions calling its contracts.
+//!
+//! The estimates follow the cost model of the chain: every transaction pays a
+//! base cost for its size and signatures, and on top of that the cost of the
+//! operation it performs. The cost of storing new contract state is not
+//! included.
+use crate::{chain_state::ExchangeRate, scenario, CostOptions, ScenarioOptions};
+use anyhow::{bail, Context};
+use concordium_base::{contracts_common::WasmVersion, smart_contracts::WasmModule};
+
+/// The NRG charged per signature on a transaction.
+const NRG_PER_SIGNATURE: u64 = 100;
+/// The NRG charged per byte of a transaction, including its header.
+const NRG_PER_TRANSACTION_BYTE: u64 = 1;
+/// The size of a transaction header in bytes.
+const TRANSACTION_HEADER_SIZE: u64 = 60;
+/// The bytes of module source charged one NRG when deploying the module.
+const DEPLOY_MODULE_BYTES_PER_NRG: u64 = 10;
+/// The bytes of module source charged one NRG when the module is looked up
+/// for calling a contract.
+const LOOKUP_MODULE_BYTES_PER_NRG: u64 = 50;
+/// The NRG charged for initializing a contract instance.
+const INIT_CONTRACT_BASE_COST: u64 = 300;
+/// The NRG charged for creating the new contract instance.
+const INIT_CONTRACT_CREATE_COST: u64 = 200;
+/// The NRG charged for updating a contract instance.
+const UPDATE_CONTRACT_BASE_COST: u64 = 300;
+/// The interpreter energy that corresponds to one NRG.
+const INTERPRETER_ENERGY_PER_NRG: u64 = 1000;
+
+/// The size of the payload tag, the amount, the module reference and the
+/// contract address in transaction payloads.
+const PAYLOAD_TAG_SIZE: u64 = 1;
+const AMOUNT_SIZE: u64 = 8;
+const MODULE_REFERENCE_SIZE: u64 = 32;
+const CONTRACT_ADDRESS_SIZE: u64 = 16;
+/// The size of the version and length prefixing the module source.
+pub(crate) const VERSIONED_MODULE_PREFIX_SIZE: u64 = 8;
+/// The size of the length prefixing a contract or receive name.
+const NAME_LENGTH_SIZE: u64 = 2;
+
+/// The cost of a single transaction in NRG.
+struct TransactionCost {
+    /// The cost for the size and signatures of the transaction.
+    base: u64,
+    /// The cost of the operation performed by the transaction.
+    operation: u64,
+}
+
+impl TransactionCost {
+    fn new(payload_size: u64, signatures: u32, operation: u64) -> Self {
+        let base = NRG_PER_TRANSACTION_BYTE * (TRANSACTION_HEADER_SIZE + payload_size)
+            + NRG_PER_SIGNATURE * u64::from(signatures);
+        Self { base, operation }
+    }
+
+    fn total(&self) -> u64 {
+        self.base + self.operation
+    }
+}
+
+/// The exchange rates used for converting NRG to CCD.
+struct Rates {
+    euro_per_energy: ExchangeRate,
+    micro_ccd_per_euro: ExchangeRate,
+}
+
+impl Rates {
+    /// Convert NRG to micro CCD, rounding up.
+    fn micro_ccd(&self, nrg: u64) -> u128 {
+        let numerator = u128::from(nrg)
+            * u128::from(self.euro_per_energy.numerator)
+            * u128::from(self.micro_ccd_per_euro.numerator);
+        let denominator = u128::from(self.euro_per_energy.denominator)
+            * u128::from(self.micro_ccd_per_euro.denominator);
+        numerator.div_ceil(denominator)
+    }
+}
+
+/// Print the estimated cost of deploying the module, and of the calls made by
+/// the scenario if one is given.
+pub(crate) fn print_costs(options: CostOptions) -> anyhow::Result<()> {
+    let versioned_module = WasmModule::from_file(&options.module)
+        .with_context(|| format!("Could not read module file {}", options.module.display()))?;
+    let module = versioned_module.source.as_ref();
+    let module_size = module.len() as u64;
+    let rates = match (options.euro_per_energy, options.micro_ccd_per_euro) {
+        (Some(euro_per_energy), Some(micro_ccd_per_euro)) => Some(Rates {
+            euro_per_energy,
+            micro_ccd_per_euro,
+        }),
+        (None, None) => None,
+        _ => bail!("Both --euro-per-energy and --micro-ccd-per-euro are needed to estimate CCD."),
+    };
+
+    let mut transactions = Vec::new();
+    let deploy = TransactionCost::new(
+        PAYLOAD_TAG_SIZE + VERSIONED_MODULE_PREFIX_SIZE + module_size,
+        options.signatures,
+        module_size / DEPLOY_MODULE_BYTES_PER_NRG,
+    );
+    transactions.push((format!("deploy module ({} bytes)", module_size), deploy));
+
+    if let Some(scenario) = options.scenario {
+        if !matches!(versioned_module.version, WasmVersion::V1) {
+            bail!("Scenarios are only supported for V1 contracts.")
+        }
+        let scenario_options = ScenarioOptions {
+            module: options.module.clone(),
+            scenario,
+            schema_path: options.schema_path,
+            state_bin_path: options.state_bin_path,
+            balance: options.balance,
+            out_bin: None,
+            should_display_state: false,
+            emit_debug: false,
+        };
+        let steps = scenario::run_scenario(scenario_options, module)?;
+        let lookup_module = module_size / LOOKUP_MODULE_BYTES_PER_NRG;
+        for step in steps {
+            // Interpreter energy is charged in whole NRG.
+            let execution = step.energy_used.div_ceil(INTERPRETER_ENERGY_PER_NRG);
+            let name_size = NAME_LENGTH_SIZE + step.contract_name.len() as u64;
+            let cost = match &step.entrypoint {
+                None => TransactionCost::new(
+                    PAYLOAD_TAG_SIZE
+                        + AMOUNT_SIZE
+                        + MODULE_REFERENCE_SIZE
+                        + name_size
+                        + "init_".len() as u64
+                        + step.parameter_size as u64,
+                    options.signatures,
+                    INIT_CONTRACT_BASE_COST + INIT_CONTRACT_CREATE_COST + lookup_module + execution,
+                ),
+                Some(entrypoint) => TransactionCost::new(
+                    PAYLOAD_TAG_SIZE
+                        + AMOUNT_SIZE
+                        + CONTRACT_ADDRESS_SIZE
+                        + name_size
+                        + ".".len() as u64
+                        + entrypoint.len() as u64
+                        + step.parameter_size as u64,
+                    options.signatures,
+                    UPDATE_CONTRACT_BASE_COST + lookup_module + execution,
+                ),
+            };
+            transactions.push((step.description, cost));
+        }
+    }
+
+    eprintln!("\nEstimated cost of the transactions:");
+    let mut total = 0;
+    for (description, cost) in transactions.iter() {
+        eprintln!(
+            "- {}: {} NRG ({} NRG for the size and signatures of the transaction, {} NRG for \
+             the operation){}",
+            description,
+            cost.total(),
+            cost.base,
+            cost.operation,
+            format_ccd(rates.as_ref(), cost.total())
+        );
+        total += cost.total();
+    }
+    if transactions.len() > 1 {
+        eprintln!("Total: {} NRG{}", total, format_ccd(rates.as_ref(), total));
+    }
+    eprintln!(
+        "The estimates assume {} signature(s) per transaction, and do not include the cost of \
+         storing new contract state.",
+        options.signatures
+    );
+    if rates.is_none() {
+        eprintln!(
+            "Use --euro-per-energy and --micro-ccd-per-euro with the current exchange rates of \
+             the chain to estimate the cost in CCD."
+        );
+    }
+    Ok(())
+}
+
+/// Format the cost in CCD if the exchange rates are known.
+fn format_ccd(rates: Option<&Rates>, nrg: u64) -> String {
+    match rates {
+        Some(rates) => {
+            let micro_ccd = rates.micro_ccd(nrg);
+            format!(
+                " = {}.{:06} CCD",
+                micro_ccd / 1_000_000,
+                micro_ccd % 1_000_000
+            )
+        }
+        None => String::new(),
+    }
+}
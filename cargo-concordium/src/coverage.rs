@@ -0,0 +1,343 @@
+//! Coverage of the code of a contract by its unit tests.
+//!
+//! The module is instrumented so that every block of straight-line code
+//! reports when it is executed, by calling a probe function with the index of
+//! the block. The blocks are mapped to lines of source code using the DWARF
+//! line table of the module, and the coverage is written in the lcov format.
+//! Without a line table only the coverage of functions is reported.
+use crate::instrument::{self, Instruction, ProbeImport, ProbeWriter, Probes};
+use anyhow::Context;
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// The host function used for emitting probes. It is not provided by the
+/// chain, but handled by the host running the unit tests.
+pub(crate) const PROBE: ProbeImport = ProbeImport {
+    module: "cargo_concordium",
+    name: "coverage",
+    num_params: 1,
+};
+
+/// A range of the code section of the original module.
+#[derive(Clone, Copy)]
+struct CodeRange {
+    start: usize,
+    end: usize,
+}
+
+/// A function defined in the original module.
+struct Function {
+    index: u32,
+    range: CodeRange,
+}
+
+/// A row of the DWARF line table, mapping an address in the code section to a
+/// line of source code.
+struct LineRow {
+    address: usize,
+    /// The index of the source file.
+    file: usize,
+    line: u64,
+}
+
+/// A module instrumented for coverage.
+pub(crate) struct Instrumented {
+    /// The instrumented module.
+    pub module: Vec<u8>,
+    /// The blocks of the original module, by the index reported by their
+    /// probe. The blocks are ordered by their position in the code section.
+    blocks: Vec<CodeRange>,
+    /// The functions defined in the original module, ordered by their
+    /// position in the code section.
+    functions: Vec<Function>,
+    /// The names of the functions of the original module, by index.
+    names: BTreeMap<u32, String>,
+    /// The source files referred to by the line table.
+    files: Vec<PathBuf>,
+    /// The line table of the original module, ordered by address.
+    lines: Vec<LineRow>,
+}
+
+/// Instrument a Wasm module for coverage.
+pub(crate) fn instrument(module: &[u8]) -> anyhow::Result<Instrumented> {
+    let names = instrument::function_names(module)?;
+    let (files, lines) =
+        line_table(module).context("Could not read the DWARF line table of the module.")?;
+    if lines.is_empty() {
+        eprintln!(
+            "The module has no debug information. Only the coverage of functions is reported."
+        );
+    }
+    let mut probes = CoverageProbes::default();
+    let module = instrument::instrument_module(module, &PROBE, &mut probes)?;
+    Ok(Instrumented {
+        module,
+        blocks: probes.blocks,
+        functions: probes.functions,
+        names,
+        files,
+        lines,
+    })
+}
+
+/// Emits a probe at the start of every block of straight-line code. A block
+/// ends at an instruction that may transfer control, including calls.
+#[derive(Default)]
+struct CoverageProbes {
+    blocks: Vec<CodeRange>,
+    functions: Vec<Function>,
+}
+
+impl CoverageProbes {
+    fn start_block(&mut self, probe: &mut ProbeWriter, offset: usize) {
+        probe.emit(&[self.blocks.len() as u32]);
+        self.blocks.push(CodeRange {
+            start: offset,
+            end: offset,
+        });
+    }
+}
+
+impl Probes for CoverageProbes {
+    fn function_start(&mut self, probe: &mut ProbeWriter, function: u32, offset: usize) {
+        self.functions.push(Function {
+            index: function,
+            range: CodeRange {
+                start: offset,
+                end: offset,
+            },
+        });
+        self.start_block(probe, offset);
+    }
+
+    fn after_instruction(
+        &mut self,
+        probe: &mut ProbeWriter,
+        _function: u32,
+        instruction: &Instruction,
+    ) {
+        if let Some(block) = self.blocks.last_mut() {
+            block.end = instruction.end;
+        }
+        if let Some(function) = self.functions.last_mut() {
+            function.range.end = instruction.end;
+        }
+        // `unreachable`, the control instructions and calls.
+        let ends_block = matches!(instruction.opcode, 0x00 | 0x02..=0x05 | 0x0B..=0x11);
+        if ends_block && !instruction.is_function_end {
+            self.start_block(probe, instruction.end);
+        }
+    }
+}
+
+/// Read the DWARF line table from the custom sections of the module. The
+/// addresses of the table are offsets in the code section.
+fn line_table(module: &[u8]) -> anyhow::Result<(Vec<PathBuf>, Vec<LineRow>)> {
+    let custom = instrument::custom_sections(module)?;
+    let dwarf = gimli::Dwarf::load(|id| -> gimli::Result<_> {
+        let data = custom.get(id.name()).copied().unwrap_or_default();
+        Ok(gimli::EndianSlice::new(data, gimli::LittleEndian))
+    })?;
+    let mut file_indices = BTreeMap::new();
+    let mut lines = Vec::new();
+    let mut units = dwarf.units();
+    while let Some(header) = units.next()? {
+        let unit = dwarf.unit(header)?;
+        let Some(program) = unit.line_program.clone() else {
+            continue;
+        };
+        let mut rows = program.rows();
+        while let Some((header, row)) = rows.next_row()? {
+            if row.end_sequence() {
+                continue;
+            }
+            let (Some(file), Some(line)) = (row.file(header), row.line()) else {
+                continue;
+            };
+            // Relative paths are relative to the directory of the file, which in
+            // turn may be relative to the compilation directory.
+            let mut path = PathBuf::new();
+            if let Some(comp_dir) = unit.comp_dir {
+                path.push(&*comp_dir.to_string_lossy());
+            }
+            if let Some(dir) = file.directory(header) {
+                path.push(&*dwarf.attr_string(&unit, dir)?.to_string_lossy());
+            }
+            path.push(
+                &*dwarf
+                    .attr_string(&unit, file.path_name())?
+                    .to_string_lossy(),
+            );
+            let next_index = file_indices.len();
+            let file = *file_indices.entry(path).or_insert(next_index);
+            lines.push(LineRow {
+                address: row.address() as usize,
+                file,
+                line: line.get(),
+            });
+        }
+    }
+    let mut files = vec![PathBuf::new(); file_indices.len()];
+    for (path, index) in file_indices {
+        files[index] = path;
+    }
+    lines.sort_by_key(|row| row.address);
+    Ok((files, lines))
+}
+
+/// The coverage of a single source file.
+#[derive(Default)]
+struct FileCoverage {
+    /// The line and the number of calls of each function, by name.
+    functions: BTreeMap<String, (u64, u64)>,
+    /// The number of times each line was executed.
+    lines: BTreeMap<u64, u64>,
+}
+
+/// The coverage of the source files of a package.
+pub(crate) struct Coverage {
+    files: BTreeMap<PathBuf, FileCoverage>,
+}
+
+impl Instrumented {
+    /// The number of times the block containing the address was executed,
+    /// or `None` if the address is not in a block.
+    fn hits_at(&self, block_hits: &[u64], address: usize) -> Option<u64> {
+        let idx = self
+            .blocks
+            .partition_point(|block| block.start <= address)
+            .checked_sub(1)?;
+        if address >= self.blocks[idx].end {
+            return None;
+        }
+        Some(block_hits.get(idx).copied().unwrap_or(0))
+    }
+
+    /// Compute the coverage of the source files in the `root` directory from
+    /// the number of times each block was executed. Without a line table, the
+    /// coverage of all functions is attributed to `module_name`.
+    pub(crate) fn coverage(&self, block_hits: &[u64], root: &Path, module_name: &str) -> Coverage {
+        let mut files: BTreeMap<PathBuf, FileCoverage> = BTreeMap::new();
+        for row in self.lines.iter() {
+            let path = &self.files[row.file];
+            if !path.starts_with(root) {
+                continue;
+            }
+            let Some(hits) = self.hits_at(block_hits, row.address) else {
+                continue;
+            };
+            let line = files
+                .entry(path.clone())
+                .or_default()
+                .lines
+                .entry(row.line)
+                .or_default();
+            *line = (*line).max(hits);
+        }
+        for function in self.functions.iter() {
+            let (path, line) = if self.lines.is_empty() {
+                (PathBuf::from(module_name), 0)
+            } else {
+                // The function is located at the first line of its code.
+                let first = self
+                    .lines
+                    .partition_point(|row| row.address < function.range.start);
+                match self.lines.get(first) {
+                    Some(row) if row.address < function.range.end => {
+                        (self.files[row.file].clone(), row.line)
+                    }
+                    _ => continue,
+                }
+            };
+            if !self.lines.is_empty() && !path.starts_with(root) {
+                continue;
+            }
+            let name = match self.names.get(&function.index) {
+                Some(name) => name.clone(),
+                None => format!("func[{}]", function.index),
+            };
+            let hits = self.hits_at(block_hits, function.range.start).unwrap_or(0);
+            // Distinct instances of a generic function may have the same name.
+            let entry = files
+                .entry(path)
+                .or_default()
+                .functions
+                .entry(name)
+                .or_insert((line, 0));
+            entry.0 = entry.0.min(line);
+            entry.1 += hits;
+        }
+        Coverage { files }
+    }
+}
+
+impl Coverage {
+    /// Write the coverage as a tracefile in the lcov format.
+    pub(crate) fn write_lcov(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(out_dir) = path.parent() {
+            fs::create_dir_all(out_dir)
+                .context("Unable to create directory for the coverage report.")?;
+        }
+        let mut out = String::new();
+        for (file, coverage) in self.files.iter() {
+            out.push_str("TN:\n");
+            out.push_str(&format!("SF:{}\n", file.display()));
+            for (name, (line, _)) in coverage.functions.iter() {
+                out.push_str(&format!("FN:{},{}\n", line, name));
+            }
+            for (name, (_, hits)) in coverage.functions.iter() {
+                out.push_str(&format!("FNDA:{},{}\n", hits, name));
+            }
+            out.push_str(&format!("FNF:{}\n", coverage.functions.len()));
+            out.push_str(&format!(
+                "FNH:{}\n",
+                coverage
+                    .functions
+                    .values()
+                    .filter(|(_, hits)| *hits > 0)
+                    .count()
+            ));
+            for (line, hits) in coverage.lines.iter() {
+                out.push_str(&format!("DA:{},{}\n", line, hits));
+            }
+            out.push_str(&format!("LF:{}\n", coverage.lines.len()));
+            out.push_str(&format!(
+                "LH:{}\n",
+                coverage.lines.values().filter(|hits| **hits > 0).count()
+            ));
+            out.push_str("end_of_record\n");
+        }
+        let mut file = fs::File::create(path).with_context(|| {
+            format!(
+                "Could not create the coverage report file {}.",
+                path.display()
+            )
+        })?;
+        file.write_all(out.as_bytes())
+            .context("Could not write the coverage report.")
+    }
+
+    /// Print the number of lines and functions covered.
+    pub(crate) fn print_summary(&self) {
+        let mut lines = (0, 0);
+        let mut functions = (0, 0);
+        for coverage in self.files.values() {
+            lines.0 += coverage.lines.values().filter(|hits| **hits > 0).count();
+            lines.1 += coverage.lines.len();
+            functions.0 += coverage
+                .functions
+                .values()
+                .filter(|(_, hits)| *hits > 0)
+                .count();
+            functions.1 += coverage.functions.len();
+        }
+        eprintln!(
+            "Unit test coverage: {} of {} lines and {} of {} functions executed.",
+            lines.0, lines.1, functions.0, functions.1
+        );
+    }
+}
@@ -0,0 +1,391 @@
+//! A comparison of two modules, for `cargo concordium diff`, e.g., for
+//! reviewing the changes of an upgrade.
+use crate::{
+    contract_schema::{contract_schemas, ContractSchema, FunctionSchema},
+    inspect::{read_module, Inspection},
+    report::OutputFormat,
+};
+use ansi_term::{Color, Style};
+use anyhow::Context;
+use concordium_base::{
+    contracts_common::{
+        schema::{Type, VersionedModuleSchema},
+        to_bytes, WasmVersion,
+    },
+    smart_contracts::WasmModule,
+};
+use concordium_smart_contract_engine::utils::{self, VersionedBuildInfo};
+use concordium_wasm::parse::parse_skeleton;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+
+/// A value that differs between the old and the new module.
+#[derive(Serialize)]
+struct Change<A> {
+    old: A,
+    new: A,
+}
+
+impl<A: PartialEq> Change<A> {
+    /// The change of the value, or `None` if it did not change.
+    fn of(old: A, new: A) -> Option<Self> {
+        (old != new).then_some(Self { old, new })
+    }
+}
+
+/// Added and removed elements of a set.
+#[derive(Serialize)]
+struct SetChange {
+    added: Vec<String>,
+    removed: Vec<String>,
+}
+
+impl SetChange {
+    fn of<'a>(
+        old: impl IntoIterator<Item = &'a String>,
+        new: impl IntoIterator<Item = &'a String>,
+    ) -> Self {
+        let old: BTreeSet<_> = old.into_iter().collect();
+        let new: BTreeSet<_> = new.into_iter().collect();
+        Self {
+            added: new.difference(&old).map(|s| s.to_string()).collect(),
+            removed: old.difference(&new).map(|s| s.to_string()).collect(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// A change of the schema of a type used by a contract.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SchemaChange {
+    /// The function the type belongs to, `init` or the name of an entrypoint,
+    /// or `None` for the event type of the contract.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    function: Option<String>,
+    /// The part of the function the type describes: `parameter`, `return
+    /// value`, `error` or `event`.
+    part: &'static str,
+    change: &'static str,
+}
+
+/// The changes of a contract present in both modules.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ContractDiff {
+    entrypoints: SetChange,
+    schema_changes: Vec<SchemaChange>,
+}
+
+/// A field of the embedded build information that differs.
+#[derive(Serialize)]
+struct BuildInfoChange {
+    field: &'static str,
+    old: Option<String>,
+    new: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ModuleDiff {
+    module_reference: Change<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    wasm_version: Option<Change<&'static str>>,
+    module_size: Change<usize>,
+    contracts: SetChange,
+    /// The changes of the contracts present in both modules, if any.
+    changed_contracts: BTreeMap<String, ContractDiff>,
+    /// Whether a schema is embedded in each module, if that changed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    schema_embedded: Option<Change<bool>>,
+    imports: SetChange,
+    build_info: Vec<BuildInfoChange>,
+}
+
+/// A module together with the parts of it that are compared.
+struct ModuleParts {
+    inspection: Inspection,
+    schema: Option<BTreeMap<String, ContractSchema>>,
+    build_info: Option<VersionedBuildInfo>,
+}
+
+impl ModuleParts {
+    fn read(path: &Path) -> anyhow::Result<Self> {
+        let module = read_module(path)?;
+        let inspection = Inspection::new(&module)
+            .with_context(|| format!("Could not inspect module {}.", path.display()))?;
+        Ok(Self {
+            inspection,
+            schema: embedded_schema(&module).map(|schema| contract_schemas(&schema)),
+            build_info: parse_skeleton(module.source.as_ref())
+                .ok()
+                .and_then(|skeleton| utils::get_build_info_from_skeleton(&skeleton).ok()),
+        })
+    }
+}
+
+/// The schema embedded in the module, if any.
+fn embedded_schema(module: &WasmModule) -> Option<VersionedModuleSchema> {
+    let source = module.source.as_ref();
+    match module.version {
+        WasmVersion::V0 => utils::get_embedded_schema_v0(source).ok(),
+        WasmVersion::V1 => utils::get_embedded_schema_v1(source).ok(),
+    }
+}
+
+/// Compare the modules in the files `old` and `new`, and print the
+/// differences.
+pub(crate) fn diff_modules(
+    old: &Path,
+    new: &Path,
+    output_format: OutputFormat,
+) -> anyhow::Result<()> {
+    let old = ModuleParts::read(old)?;
+    let new = ModuleParts::read(new)?;
+
+    let contracts = SetChange::of(
+        old.inspection.contracts.keys(),
+        new.inspection.contracts.keys(),
+    );
+    let mut changed_contracts = BTreeMap::new();
+    for (name, old_entrypoints) in old.inspection.contracts.iter() {
+        let Some(new_entrypoints) = new.inspection.contracts.get(name) else {
+            continue;
+        };
+        let entrypoints = SetChange::of(old_entrypoints, new_entrypoints);
+        let schemas = match (&old.schema, &new.schema) {
+            (Some(old_schema), Some(new_schema)) => {
+                let empty = ContractSchema::default();
+                schema_changes(
+                    old_schema.get(name).unwrap_or(&empty),
+                    new_schema.get(name).unwrap_or(&empty),
+                )
+            }
+            _ => Vec::new(),
+        };
+        if !entrypoints.is_empty() || !schemas.is_empty() {
+            changed_contracts.insert(
+                name.clone(),
+                ContractDiff {
+                    entrypoints,
+                    schema_changes: schemas,
+                },
+            );
+        }
+    }
+
+    let import_names = |inspection: &Inspection| -> Vec<String> {
+        inspection
+            .imports
+            .iter()
+            .map(|import| format!("{}.{}", import.module, import.name))
+            .collect()
+    };
+    let diff = ModuleDiff {
+        module_reference: Change {
+            old: old.inspection.module_reference.clone(),
+            new: new.inspection.module_reference.clone(),
+        },
+        wasm_version: Change::of(old.inspection.wasm_version, new.inspection.wasm_version),
+        module_size: Change {
+            old: old.inspection.module_size,
+            new: new.inspection.module_size,
+        },
+        contracts,
+        changed_contracts,
+        schema_embedded: Change::of(old.schema.is_some(), new.schema.is_some()),
+        imports: SetChange::of(
+            &import_names(&old.inspection),
+            &import_names(&new.inspection),
+        ),
+        build_info: build_info_changes(old.build_info.as_ref(), new.build_info.as_ref()),
+    };
+    match output_format {
+        OutputFormat::Json => {
+            let json = serde_json::to_string_pretty(&diff)
+                .context("Could not output the differences as JSON.")?;
+            println!("{}", json);
+        }
+        OutputFormat::Text => diff.print(),
+    }
+    Ok(())
+}
+
+/// The changes of the schemas of the functions and the event type of a
+/// contract.
+fn schema_changes(old: &ContractSchema, new: &ContractSchema) -> Vec<SchemaChange> {
+    let mut changes = Vec::new();
+    let mut functions: BTreeSet<Option<&str>> = BTreeSet::new();
+    functions.insert(None);
+    functions.extend(old.receive.keys().map(|name| Some(name.as_str())));
+    functions.extend(new.receive.keys().map(|name| Some(name.as_str())));
+    let empty = FunctionSchema::default();
+    for function in functions {
+        let old_function = old.function(function).unwrap_or(&empty);
+        let new_function = new.function(function).unwrap_or(&empty);
+        let parts = [
+            (
+                "parameter",
+                &old_function.parameter,
+                &new_function.parameter,
+            ),
+            (
+                "return value",
+                &old_function.return_value,
+                &new_function.return_value,
+            ),
+            ("error", &old_function.error, &new_function.error),
+        ];
+        for (part, old_type, new_type) in parts {
+            if let Some(change) = type_change(old_type.as_ref(), new_type.as_ref()) {
+                changes.push(SchemaChange {
+                    function: Some(function.unwrap_or("init").to_string()),
+                    part,
+                    change,
+                });
+            }
+        }
+    }
+    if let Some(change) = type_change(old.event.as_ref(), new.event.as_ref()) {
+        changes.push(SchemaChange {
+            function: None,
+            part: "event",
+            change,
+        });
+    }
+    changes
+}
+
+/// How the schema of a type changed, if it did. Types are compared by their
+/// serialization.
+fn type_change(old: Option<&Type>, new: Option<&Type>) -> Option<&'static str> {
+    match (old, new) {
+        (None, None) => None,
+        (None, Some(_)) => Some("added"),
+        (Some(_), None) => Some("removed"),
+        (Some(old), Some(new)) => (to_bytes(old) != to_bytes(new)).then_some("changed"),
+    }
+}
+
+/// The fields of the embedded build information that differ.
+fn build_info_changes(
+    old: Option<&VersionedBuildInfo>,
+    new: Option<&VersionedBuildInfo>,
+) -> Vec<BuildInfoChange> {
+    let fields = |build_info: Option<&VersionedBuildInfo>| -> [(&'static str, Option<String>); 4] {
+        match build_info {
+            Some(VersionedBuildInfo::V0(bi)) => [
+                ("image", Some(bi.image.clone())),
+                ("build command", Some(bi.build_command.join(" "))),
+                ("archive hash", Some(bi.archive_hash.to_string())),
+                ("source link", bi.source_link.clone()),
+            ],
+            None => [
+                ("image", None),
+                ("build command", None),
+                ("archive hash", None),
+                ("source link", None),
+            ],
+        }
+    };
+    fields(old)
+        .into_iter()
+        .zip(fields(new))
+        .filter(|((_, old), (_, new))| old != new)
+        .map(|((field, old), (_, new))| BuildInfoChange { field, old, new })
+        .collect()
+}
+
+impl ModuleDiff {
+    fn print(&self) {
+        let bold_style = Style::new().bold();
+        let added_style = Color::Green;
+        let removed_style = Color::Red;
+        if self.module_reference.old == self.module_reference.new {
+            eprintln!(
+                "The modules are identical, with module reference {}.",
+                bold_style.paint(&self.module_reference.old)
+            );
+            return;
+        }
+        eprintln!(
+            "Module reference: {} -> {}",
+            bold_style.paint(&self.module_reference.old),
+            bold_style.paint(&self.module_reference.new)
+        );
+        if let Some(version) = &self.wasm_version {
+            eprintln!("Module version: {} -> {}", version.old, version.new);
+        }
+        let delta = self.module_size.new as i64 - self.module_size.old as i64;
+        eprintln!(
+            "Module size: {} B -> {} B ({:+} B)",
+            self.module_size.old, self.module_size.new, delta
+        );
+
+        eprintln!("\nContracts:");
+        if self.contracts.is_empty() && self.changed_contracts.is_empty() {
+            eprintln!("  (no changes)");
+        }
+        for name in self.contracts.added.iter() {
+            eprintln!("  {}", added_style.paint(format!("+ {}", name)));
+        }
+        for name in self.contracts.removed.iter() {
+            eprintln!("  {}", removed_style.paint(format!("- {}", name)));
+        }
+        for (name, contract) in self.changed_contracts.iter() {
+            eprintln!("  ~ {}", bold_style.paint(name));
+            for entrypoint in contract.entrypoints.added.iter() {
+                eprintln!("    {}", added_style.paint(format!("+ {}", entrypoint)));
+            }
+            for entrypoint in contract.entrypoints.removed.iter() {
+                eprintln!("    {}", removed_style.paint(format!("- {}", entrypoint)));
+            }
+            for change in contract.schema_changes.iter() {
+                match &change.function {
+                    Some(function) => eprintln!(
+                        "    ~ schema of the {} of '{}' {}",
+                        change.part, function, change.change
+                    ),
+                    None => eprintln!("    ~ schema of the events {}", change.change),
+                }
+            }
+        }
+        if let Some(schema_embedded) = &self.schema_embedded {
+            if schema_embedded.new {
+                eprintln!("  Only the new module has an embedded schema.");
+            } else {
+                eprintln!("  Only the old module has an embedded schema.");
+            }
+        }
+
+        eprintln!("\nImported host functions:");
+        if self.imports.is_empty() {
+            eprintln!("  (no changes)");
+        }
+        for name in self.imports.added.iter() {
+            eprintln!("  {}", added_style.paint(format!("+ {}", name)));
+        }
+        for name in self.imports.removed.iter() {
+            eprintln!("  {}", removed_style.paint(format!("- {}", name)));
+        }
+
+        eprintln!("\nBuild information:");
+        if self.build_info.is_empty() {
+            eprintln!("  (no changes)");
+        }
+        for change in self.build_info.iter() {
+            eprintln!(
+                "  ~ {}: {} -> {}",
+                change.field,
+                change.old.as_deref().unwrap_or("(none)"),
+                change.new.as_deref().unwrap_or("(none)")
+            );
+        }
+    }
+}
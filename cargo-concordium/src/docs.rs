@@ -0,0 +1,401 @@
+//! Documentation of the contracts in a module schema, for
+//! `cargo concordium docs`, rendered as Markdown or as static HTML.
+//!
+//! The documentation is built as a list of blocks, which are then rendered in
+//! the chosen format.
+use crate::contract_schema::{contract_schemas, FunctionSchema};
+use concordium_base::contracts_common::{
+    schema::{Fields, Type, VersionedModuleSchema},
+    WasmVersion,
+};
+use concordium_smart_contract_engine::utils::{self, VersionedBuildInfo};
+use concordium_wasm::parse::parse_skeleton;
+use std::{fmt::Write as _, path::Path, str::FromStr};
+
+/// The format to render the documentation in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DocsFormat {
+    Markdown,
+    Html,
+}
+
+impl FromStr for DocsFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "markdown" => Ok(Self::Markdown),
+            "html" => Ok(Self::Html),
+            _ => anyhow::bail!("Unsupported format '{}'. Use 'markdown' or 'html'.", s),
+        }
+    }
+}
+
+enum Block {
+    /// A heading, optionally followed by a name in code.
+    Heading {
+        level: usize,
+        text: String,
+        code: Option<String>,
+    },
+    Paragraph(String),
+    /// A type in the schema, with a label such as `Parameter`.
+    Type {
+        label: &'static str,
+        signature: String,
+    },
+    /// A list of labelled values.
+    List(Vec<(&'static str, String)>),
+}
+
+/// The build info embedded in the module at `path`, if any. The module is
+/// unversioned if `wasm_version` is given, as for `get_schema`.
+pub(crate) fn module_build_info(
+    path: &Path,
+    wasm_version: Option<WasmVersion>,
+) -> Option<VersionedBuildInfo> {
+    let bytes = std::fs::read(path).ok()?;
+    let source = match wasm_version {
+        Some(_) => &bytes[..],
+        None => bytes.get(8..)?,
+    };
+    let skeleton = parse_skeleton(source).ok()?;
+    utils::get_build_info_from_skeleton(&skeleton).ok()
+}
+
+/// Render the documentation of every contract in the schema: its init function,
+/// its entrypoints with their parameter, return value and error types, and its
+/// event and state types, followed by the build info if present.
+pub(crate) fn render_docs(
+    schema: &VersionedModuleSchema,
+    build_info: Option<&VersionedBuildInfo>,
+    format: DocsFormat,
+) -> String {
+    let mut blocks = vec![Block::Heading {
+        level: 1,
+        text: "Contracts".into(),
+        code: None,
+    }];
+    for (contract_name, contract) in contract_schemas(schema).iter() {
+        blocks.push(Block::Heading {
+            level: 2,
+            text: "Contract ".into(),
+            code: Some(contract_name.clone()),
+        });
+        if let Some(state) = &contract.state {
+            blocks.push(Block::Type {
+                label: "State",
+                signature: signature(state),
+            });
+        }
+        if let Some(event) = &contract.event {
+            blocks.push(Block::Type {
+                label: "Event",
+                signature: signature(event),
+            });
+        }
+        if let Some(init) = &contract.init {
+            blocks.push(Block::Heading {
+                level: 3,
+                text: "Init function".into(),
+                code: None,
+            });
+            function_blocks(init, &mut blocks);
+        }
+        if !contract.receive.is_empty() {
+            blocks.push(Block::Heading {
+                level: 3,
+                text: "Entrypoints".into(),
+                code: None,
+            });
+        }
+        for (entrypoint, function) in contract.receive.iter() {
+            blocks.push(Block::Heading {
+                level: 4,
+                text: String::new(),
+                code: Some(entrypoint.clone()),
+            });
+            function_blocks(function, &mut blocks);
+        }
+    }
+    if let Some(VersionedBuildInfo::V0(build_info)) = build_info {
+        blocks.push(Block::Heading {
+            level: 2,
+            text: "Build information".into(),
+            code: None,
+        });
+        blocks.push(Block::List(vec![
+            ("Build image", build_info.image.clone()),
+            ("Build command", build_info.build_command.join(" ")),
+            ("Hash of the archive", build_info.archive_hash.to_string()),
+            (
+                "Link to source code",
+                build_info
+                    .source_link
+                    .clone()
+                    .unwrap_or_else(|| "none".into()),
+            ),
+        ]));
+    }
+    match format {
+        DocsFormat::Markdown => render_markdown(&blocks),
+        DocsFormat::Html => render_html(&blocks),
+    }
+}
+
+fn function_blocks(function: &FunctionSchema, blocks: &mut Vec<Block>) {
+    let types = [
+        ("Parameter", &function.parameter),
+        ("Return value", &function.return_value),
+        ("Error", &function.error),
+    ];
+    let mut documented = false;
+    for (label, ty) in types {
+        if let Some(ty) = ty {
+            blocks.push(Block::Type {
+                label,
+                signature: signature(ty),
+            });
+            documented = true;
+        }
+    }
+    if !documented {
+        blocks.push(Block::Paragraph(
+            "The schema has no types for this function.".into(),
+        ));
+    }
+}
+
+fn render_markdown(blocks: &[Block]) -> String {
+    let mut out = String::new();
+    for block in blocks {
+        match block {
+            Block::Heading { level, text, code } => {
+                let _ = write!(out, "{} {}", "#".repeat(*level), text);
+                if let Some(code) = code {
+                    let _ = write!(out, "`{}`", code);
+                }
+                out.push_str("\n\n");
+            }
+            Block::Paragraph(text) => {
+                let _ = write!(out, "{}\n\n", text);
+            }
+            Block::Type { label, signature } => {
+                let _ = write!(out, "**{}**\n\n```text\n{}\n```\n\n", label, signature);
+            }
+            Block::List(items) => {
+                for (label, value) in items {
+                    let _ = writeln!(out, "- **{}**: {}", label, value);
+                }
+                out.push('\n');
+            }
+        }
+    }
+    // A single newline at the end of the file.
+    let len = out.trim_end().len();
+    out.truncate(len);
+    out.push('\n');
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn render_html(blocks: &[Block]) -> String {
+    let mut out = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Contracts</title>\n\
+         <style>body { font-family: sans-serif; max-width: 60em; margin: auto; } pre { \
+         background: #f4f4f4; padding: 1em; }</style>\n</head>\n<body>\n",
+    );
+    for block in blocks {
+        match block {
+            Block::Heading { level, text, code } => {
+                let _ = write!(out, "<h{}>{}", level, escape_html(text));
+                if let Some(code) = code {
+                    let _ = write!(out, "<code>{}</code>", escape_html(code));
+                }
+                let _ = writeln!(out, "</h{}>", level);
+            }
+            Block::Paragraph(text) => {
+                let _ = writeln!(out, "<p>{}</p>", escape_html(text));
+            }
+            Block::Type { label, signature } => {
+                let _ = writeln!(
+                    out,
+                    "<p><strong>{}</strong></p>\n<pre><code>{}</code></pre>",
+                    escape_html(label),
+                    escape_html(signature)
+                );
+            }
+            Block::List(items) => {
+                out.push_str("<ul>\n");
+                for (label, value) in items {
+                    let _ = writeln!(
+                        out,
+                        "<li><strong>{}</strong>: {}</li>",
+                        escape_html(label),
+                        escape_html(value)
+                    );
+                }
+                out.push_str("</ul>\n");
+            }
+        }
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+/// A readable description of the type, similar to a Rust type, with named
+/// fields and enum variants on separate lines.
+fn signature(ty: &Type) -> String {
+    let mut out = String::new();
+    write_type(ty, 0, &mut out);
+    out
+}
+
+fn write_type(ty: &Type, indent: usize, out: &mut String) {
+    let name = match ty {
+        Type::Unit => "()",
+        Type::Bool => "bool",
+        Type::U8 => "u8",
+        Type::U16 => "u16",
+        Type::U32 => "u32",
+        Type::U64 => "u64",
+        Type::U128 => "u128",
+        Type::I8 => "i8",
+        Type::I16 => "i16",
+        Type::I32 => "i32",
+        Type::I64 => "i64",
+        Type::I128 => "i128",
+        Type::Amount => "Amount",
+        Type::AccountAddress => "AccountAddress",
+        Type::ContractAddress => "ContractAddress",
+        Type::Timestamp => "Timestamp",
+        Type::Duration => "Duration",
+        Type::String(_) => "String",
+        Type::ContractName(_) => "ContractName",
+        Type::ReceiveName(_) => "ReceiveName",
+        Type::ByteList(_) => "Bytes",
+        Type::ULeb128(max) => {
+            let _ = write!(out, "ULeb128<{}>", max);
+            return;
+        }
+        Type::ILeb128(max) => {
+            let _ = write!(out, "ILeb128<{}>", max);
+            return;
+        }
+        Type::ByteArray(len) => {
+            let _ = write!(out, "[u8; {}]", len);
+            return;
+        }
+        Type::Pair(left, right) => {
+            out.push('(');
+            write_type(left, indent, out);
+            out.push_str(", ");
+            write_type(right, indent, out);
+            out.push(')');
+            return;
+        }
+        Type::List(_, elem) | Type::Set(_, elem) => {
+            out.push_str(if matches!(ty, Type::List(..)) {
+                "List<"
+            } else {
+                "Set<"
+            });
+            write_type(elem, indent, out);
+            out.push('>');
+            return;
+        }
+        Type::Map(_, key, value) => {
+            out.push_str("Map<");
+            write_type(key, indent, out);
+            out.push_str(", ");
+            write_type(value, indent, out);
+            out.push('>');
+            return;
+        }
+        Type::Array(len, elem) => {
+            out.push('[');
+            write_type(elem, indent, out);
+            let _ = write!(out, "; {}]", len);
+            return;
+        }
+        Type::Struct(Fields::None) => "()",
+        Type::Struct(fields) => {
+            write_fields(fields, indent, out);
+            return;
+        }
+        Type::Enum(variants) => {
+            write_variants(
+                variants.iter().map(|(name, fields)| (name, fields, None)),
+                indent,
+                out,
+            );
+            return;
+        }
+        Type::TaggedEnum(variants) => {
+            write_variants(
+                variants
+                    .iter()
+                    .map(|(tag, (name, fields))| (name, fields, Some(*tag))),
+                indent,
+                out,
+            );
+            return;
+        }
+    };
+    out.push_str(name);
+}
+
+/// Named fields are written as a block with a field on each line, and unnamed
+/// fields as a tuple.
+fn write_fields(fields: &Fields, indent: usize, out: &mut String) {
+    match fields {
+        Fields::Named(fields) => {
+            out.push_str("{\n");
+            for (name, ty) in fields {
+                let _ = write!(out, "{}{}: ", "    ".repeat(indent + 1), name);
+                write_type(ty, indent + 1, out);
+                out.push_str(",\n");
+            }
+            let _ = write!(out, "{}}}", "    ".repeat(indent));
+        }
+        Fields::Unnamed(fields) => {
+            out.push('(');
+            for (i, ty) in fields.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_type(ty, indent, out);
+            }
+            out.push(')');
+        }
+        Fields::None => {}
+    }
+}
+
+/// Variants are written on separate lines, with their tag if it is given
+/// explicitly.
+fn write_variants<'a>(
+    variants: impl Iterator<Item = (&'a String, &'a Fields, Option<u8>)>,
+    indent: usize,
+    out: &mut String,
+) {
+    out.push_str("enum {\n");
+    for (name, fields, tag) in variants {
+        let _ = write!(out, "{}{}", "    ".repeat(indent + 1), name);
+        if let Fields::Named(_) = fields {
+            out.push(' ');
+        }
+        write_fields(fields, indent + 1, out);
+        if let Some(tag) = tag {
+            let _ = write!(out, " = {}", tag);
+        }
+        out.push_str(",\n");
+    }
+    let _ = write!(out, "{}}}", "    ".repeat(indent));
+}
@@ -0,0 +1,245 @@
+//! A summary of a built module, for `cargo concordium inspect`.
+use crate::{build::check_exports, cost::VERSIONED_MODULE_PREFIX_SIZE, report::OutputFormat};
+use ansi_term::{Color, Style};
+use anyhow::Context;
+use concordium_base::{
+    contracts_common::WasmVersion,
+    smart_contracts::{ContractName, ReceiveName, WasmModule},
+};
+use concordium_smart_contract_engine::{utils::NoDuplicateImport, v0, v1};
+use concordium_wasm::{
+    parse::{parse_custom, parse_skeleton, Skeleton},
+    types::ExportDescription,
+    validate::{validate_module, ValidationConfig},
+};
+use serde::Serialize;
+use std::{collections::BTreeMap, path::Path};
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Inspection {
+    pub wasm_version: &'static str,
+    /// The module reference in hex.
+    pub module_reference: String,
+    /// The size of the versioned module in bytes, as deployed to the chain.
+    pub module_size: usize,
+    /// The contracts of the module, with the names of their entrypoints.
+    pub contracts: BTreeMap<String, Vec<String>>,
+    /// The exported functions that are neither init nor receive functions of
+    /// a contract.
+    other_exports: Vec<String>,
+    /// The host functions imported by the module.
+    pub imports: Vec<Import>,
+    custom_sections: Vec<CustomSectionInfo>,
+    /// Whether the module is accepted by the chain as a V0 module.
+    valid_v0: Validity,
+    /// Whether the module is accepted by the chain as a V1 module.
+    valid_v1: Validity,
+}
+
+#[derive(Serialize)]
+pub(crate) struct Import {
+    pub module: String,
+    pub name: String,
+}
+
+#[derive(Serialize)]
+struct CustomSectionInfo {
+    name: String,
+    /// The size of the contents of the section in bytes.
+    size: usize,
+}
+
+#[derive(Serialize)]
+struct Validity {
+    valid: bool,
+    /// The reason the module is not valid.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl Validity {
+    fn new(result: anyhow::Result<()>) -> Self {
+        match result {
+            Ok(()) => Self {
+                valid: true,
+                error: None,
+            },
+            Err(err) => Self {
+                valid: false,
+                error: Some(format!("{:#}", err)),
+            },
+        }
+    }
+}
+
+/// Read the module in the file at `path`.
+pub(crate) fn read_module(path: &Path) -> anyhow::Result<WasmModule> {
+    WasmModule::from_file(path)
+        .with_context(|| format!("Could not read module file {}", path.display()))
+}
+
+/// Print a summary of the module in the file at `path`: its version,
+/// reference and size, the contracts and their entrypoints, the host functions
+/// it imports, its custom sections, and whether it is valid as a V0 and as a V1
+/// module.
+pub(crate) fn inspect_module(path: &Path, output_format: OutputFormat) -> anyhow::Result<()> {
+    let inspection = Inspection::new(&read_module(path)?)?;
+    match output_format {
+        OutputFormat::Json => {
+            let json = serde_json::to_string_pretty(&inspection)
+                .context("Could not output the inspection as JSON.")?;
+            println!("{}", json);
+        }
+        OutputFormat::Text => inspection.print(),
+    }
+    Ok(())
+}
+
+impl Inspection {
+    /// Summarize the module.
+    pub(crate) fn new(module: &WasmModule) -> anyhow::Result<Self> {
+        let mut skeleton = parse_skeleton(module.source.as_ref())
+            .context("The supplied module is not a valid Wasm module.")?;
+
+        let mut custom_sections = Vec::new();
+        for ucs in skeleton.custom.iter_mut() {
+            let cs = parse_custom(ucs).context("Could not parse a custom section.")?;
+            custom_sections.push(CustomSectionInfo {
+                name: cs.name.as_ref().to_string(),
+                size: cs.contents.len(),
+            });
+        }
+
+        // The exports and imports are read without the restrictions of the chain
+        // on imports, so that they are listed even for modules that are not valid.
+        // Modules can only import functions.
+        let parsed = validate_module(ValidationConfig::V1, &NoDuplicateImport, &skeleton)
+            .context("The module is not a valid Wasm module.")?;
+        let mut contracts = BTreeMap::<String, Vec<String>>::new();
+        let mut other_exports = Vec::new();
+        for export in parsed.export.exports.iter() {
+            if let ExportDescription::Func { .. } = export.description {
+                let name = export.name.as_ref();
+                if let Ok(cn) = ContractName::new(name) {
+                    contracts.entry(cn.contract_name().to_string()).or_default();
+                } else if let Ok(rn) = ReceiveName::new(name) {
+                    contracts
+                        .entry(rn.contract_name().to_string())
+                        .or_default()
+                        .push(rn.entrypoint_name().to_string());
+                } else {
+                    other_exports.push(name.to_string());
+                }
+            }
+        }
+        for entrypoints in contracts.values_mut() {
+            entrypoints.sort();
+        }
+        let imports = parsed
+            .import
+            .imports
+            .iter()
+            .map(|import| Import {
+                module: import.mod_name.as_ref().to_string(),
+                name: import.item_name.as_ref().to_string(),
+            })
+            .collect();
+
+        Ok(Self {
+            wasm_version: match module.version {
+                WasmVersion::V0 => "V0",
+                WasmVersion::V1 => "V1",
+            },
+            module_reference: module.get_module_ref().to_string(),
+            module_size: module.source.as_ref().len() + VERSIONED_MODULE_PREFIX_SIZE as usize,
+            contracts,
+            other_exports,
+            imports,
+            custom_sections,
+            valid_v0: Validity::new(validate_as(&skeleton, WasmVersion::V0)),
+            valid_v1: Validity::new(validate_as(&skeleton, WasmVersion::V1)),
+        })
+    }
+
+    fn print(&self) {
+        let bold_style = Style::new().bold();
+        eprintln!("Module version: {}", bold_style.paint(self.wasm_version));
+        eprintln!(
+            "Module reference: {}",
+            bold_style.paint(&self.module_reference)
+        );
+        eprintln!(
+            "Module size: {}",
+            bold_style.paint(format!("{} B", self.module_size))
+        );
+
+        eprintln!("\nContracts:");
+        if self.contracts.is_empty() {
+            eprintln!("  (none)");
+        }
+        for (contract, entrypoints) in self.contracts.iter() {
+            eprintln!("  - {}", bold_style.paint(contract));
+            for entrypoint in entrypoints {
+                eprintln!("    - {}", entrypoint);
+            }
+        }
+        if !self.other_exports.is_empty() {
+            eprintln!("\nOther exported functions:");
+            for name in self.other_exports.iter() {
+                eprintln!("  - {}", name);
+            }
+        }
+
+        eprintln!("\nImported host functions:");
+        if self.imports.is_empty() {
+            eprintln!("  (none)");
+        }
+        for import in self.imports.iter() {
+            eprintln!("  - {}.{}", import.module, import.name);
+        }
+
+        eprintln!("\nCustom sections:");
+        if self.custom_sections.is_empty() {
+            eprintln!("  (none)");
+        }
+        for section in self.custom_sections.iter() {
+            eprintln!("  - {} ({} B)", section.name, section.size);
+        }
+
+        eprintln!("\nValidation:");
+        for (version, validity) in [("V0", &self.valid_v0), ("V1", &self.valid_v1)] {
+            match &validity.error {
+                None => eprintln!("  - {}: {}", version, Color::Green.bold().paint("valid")),
+                Some(error) => eprintln!(
+                    "  - {}: {}, {}",
+                    version,
+                    Color::Red.bold().paint("not valid"),
+                    error
+                ),
+            }
+        }
+    }
+}
+
+/// Check that the module is accepted by the chain as a module of the given
+/// version, following the same rules as `cargo concordium build`.
+fn validate_as(skeleton: &Skeleton, version: WasmVersion) -> anyhow::Result<()> {
+    let module = match version {
+        WasmVersion::V0 => validate_module(
+            ValidationConfig::V0,
+            &v0::ConcordiumAllowedImports,
+            skeleton,
+        )?,
+        WasmVersion::V1 => validate_module(
+            ValidationConfig::V1,
+            &v1::ConcordiumAllowedImports {
+                support_upgrade: true,
+                enable_debug: false,
+            },
+            skeleton,
+        )?,
+    };
+    check_exports(&module, version)?;
+    Ok(())
+}
@@ -0,0 +1,1066 @@
+//! Instrumentation of Wasm modules with calls to a probe function.
+//!
+//! The module is rewritten on the level of the binary format. The probe
+//! function is imported by the module, and calls to it are inserted in the
+//! function bodies at the places chosen by an implementation of [`Probes`].
+//! Only the parts of the module referring to functions are otherwise changed,
+//! since importing a new function shifts the indices of the functions defined
+//! in the module.
+use crate::{coverage, profile};
+use anyhow::{bail, ensure, Context};
+use concordium_wasm::{
+    artifact::ArtifactNamedImport,
+    machine::{Host, RunResult, RuntimeStack},
+};
+use rand::{rngs::SmallRng, RngCore, SeedableRng};
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+/// The Wasm magic hash and version.
+const WASM_HEADER: [u8; 8] = [0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00];
+
+/// Section ids of the Wasm binary format.
+const CUSTOM_SECTION: u8 = 0;
+const TYPE_SECTION: u8 = 1;
+const IMPORT_SECTION: u8 = 2;
+const FUNCTION_SECTION: u8 = 3;
+const TABLE_SECTION: u8 = 4;
+const MEMORY_SECTION: u8 = 5;
+const EXPORT_SECTION: u8 = 7;
+const START_SECTION: u8 = 8;
+const ELEMENT_SECTION: u8 = 9;
+const CODE_SECTION: u8 = 10;
+
+/// A host function used for emitting probes. The function takes a number of
+/// `i32` arguments and returns nothing.
+pub(crate) struct ProbeImport {
+    pub module: &'static str,
+    pub name: &'static str,
+    pub num_params: usize,
+}
+
+/// An instruction of the original module.
+pub(crate) struct Instruction {
+    pub opcode: u8,
+    /// The offset of the instruction in the code section of the original
+    /// module.
+    pub offset: usize,
+    /// The offset of the next instruction in the code section of the original
+    /// module.
+    pub end: usize,
+    /// For calls, whether the called function may be defined in the module,
+    /// as opposed to being a host function.
+    pub calls_module_function: bool,
+    /// Whether the instruction is a call to the probe function made by the
+    /// original module, which is possible if the probe function is one of its
+    /// imports.
+    pub calls_probe: bool,
+    /// Whether the instruction is the `end` of the function body, after which
+    /// no probes can be inserted.
+    pub is_function_end: bool,
+}
+
+/// Decides where calls to the probe function are inserted.
+pub(crate) trait Probes {
+    /// Called at the start of the body of a function, where `offset` is the
+    /// offset of its first instruction in the code section of the original
+    /// module.
+    fn function_start(&mut self, probe: &mut ProbeWriter, function: u32, offset: usize);
+
+    /// Called before each instruction of the function is written.
+    fn before_instruction(
+        &mut self,
+        _probe: &mut ProbeWriter,
+        _function: u32,
+        _instruction: &Instruction,
+    ) {
+    }
+
+    /// Called after each instruction of the function has been written.
+    fn after_instruction(
+        &mut self,
+        probe: &mut ProbeWriter,
+        function: u32,
+        instruction: &Instruction,
+    );
+}
+
+/// Writes calls to the probe function into the body of a function.
+pub(crate) struct ProbeWriter<'a> {
+    out: &'a mut Vec<u8>,
+    probe: u32,
+}
+
+impl ProbeWriter<'_> {
+    /// Write a call to the probe function with the given arguments.
+    pub(crate) fn emit(&mut self, args: &[u32]) {
+        for arg in args {
+            self.out.push(0x41);
+            write_i64(self.out, i64::from(*arg as i32));
+        }
+        self.out.push(0x10);
+        write_u32(self.out, self.probe);
+    }
+}
+
+/// Instrument a Wasm module with calls to the probe function. The probe
+/// function is imported by the module unless it is already. Custom sections
+/// are dropped, since they are not needed for running the module and the
+/// function indices in the name section would be wrong.
+pub(crate) fn instrument_module(
+    module: &[u8],
+    import: &ProbeImport,
+    probes: &mut impl Probes,
+) -> anyhow::Result<Vec<u8>> {
+    let sections = parse_sections(module)?;
+
+    let mut num_types = 0;
+    let mut probe_type = None;
+    if let Some((_, bytes)) = sections.iter().find(|(id, _)| *id == TYPE_SECTION) {
+        let mut reader = Reader::new(bytes);
+        num_types = reader.u32()?;
+        for idx in 0..num_types {
+            ensure!(reader.u8()? == 0x60, "Malformed function type.");
+            let params = reader.vec_bytes()?;
+            let results = reader.vec_bytes()?;
+            if params.len() == import.num_params
+                && params.iter().all(|param| *param == 0x7F)
+                && results.is_empty()
+            {
+                probe_type = Some(idx);
+            }
+        }
+    }
+
+    let mut num_imports = 0;
+    let mut num_func_imports = 0;
+    let mut existing_probe = None;
+    if let Some((_, bytes)) = sections.iter().find(|(id, _)| *id == IMPORT_SECTION) {
+        let mut reader = Reader::new(bytes);
+        num_imports = reader.u32()?;
+        for _ in 0..num_imports {
+            let mod_name = reader.name()?;
+            let item_name = reader.name()?;
+            match reader.u8()? {
+                0x00 => {
+                    reader.u32()?;
+                    if mod_name == import.module && item_name == import.name {
+                        existing_probe = Some(num_func_imports);
+                    }
+                    num_func_imports += 1;
+                }
+                0x01 => {
+                    reader.u8()?;
+                    reader.limits()?;
+                }
+                0x02 => reader.limits()?,
+                0x03 => {
+                    reader.u8()?;
+                    reader.u8()?;
+                }
+                _ => bail!("Malformed import."),
+            }
+        }
+    }
+
+    // If the module does not already import the probe function it is added as
+    // the last imported function, which shifts the index of every function
+    // defined in the module by one.
+    let instrumentation = Instrumentation {
+        num_func_imports,
+        probe: existing_probe.unwrap_or(num_func_imports),
+        shift: existing_probe.is_none(),
+    };
+
+    let probe_type_idx = probe_type.unwrap_or(num_types);
+    let mut out = WASM_HEADER.to_vec();
+    let mut types_written = false;
+    let mut imports_written = false;
+    for &(id, bytes) in sections.iter() {
+        if id == CUSTOM_SECTION {
+            continue;
+        }
+        // Insert the type and import sections if the module does not have them.
+        if instrumentation.shift && !types_written && id > TYPE_SECTION {
+            write_section(&mut out, TYPE_SECTION, &probe_type_section(&[], 0, import));
+            types_written = true;
+        }
+        if instrumentation.shift && !imports_written && id > IMPORT_SECTION {
+            write_section(
+                &mut out,
+                IMPORT_SECTION,
+                &probe_import_section(&[], 0, import, probe_type_idx),
+            );
+            imports_written = true;
+        }
+        let new_bytes = match id {
+            TYPE_SECTION => {
+                types_written = true;
+                if instrumentation.shift && probe_type.is_none() {
+                    let mut reader = Reader::new(bytes);
+                    reader.u32()?;
+                    probe_type_section(reader.rest(), num_types, import)
+                } else {
+                    bytes.to_vec()
+                }
+            }
+            IMPORT_SECTION => {
+                imports_written = true;
+                if instrumentation.shift {
+                    let mut reader = Reader::new(bytes);
+                    reader.u32()?;
+                    probe_import_section(reader.rest(), num_imports, import, probe_type_idx)
+                } else {
+                    bytes.to_vec()
+                }
+            }
+            EXPORT_SECTION => instrumentation.exports(bytes)?,
+            START_SECTION => {
+                let mut new_bytes = Vec::new();
+                write_u32(
+                    &mut new_bytes,
+                    instrumentation.remap(Reader::new(bytes).u32()?),
+                );
+                new_bytes
+            }
+            ELEMENT_SECTION => instrumentation.elements(bytes)?,
+            CODE_SECTION => instrumentation.code(bytes, probes)?,
+            _ => bytes.to_vec(),
+        };
+        write_section(&mut out, id, &new_bytes);
+    }
+    Ok(out)
+}
+
+/// The init function of the module returned by [`probe_cost_module`] that
+/// does nothing.
+pub(crate) const PROBE_COST_PLAIN: &str = "init_plain";
+/// The init function of the module returned by [`probe_cost_module`] that only
+/// emits a probe.
+pub(crate) const PROBE_COST_PROBE: &str = "init_probe";
+
+/// A module with two init functions, [`PROBE_COST_PLAIN`] and
+/// [`PROBE_COST_PROBE`], which differ only in that the latter emits a probe
+/// with the given arguments. The energy spent by a probe is the difference
+/// between the energy spent by the two functions.
+pub(crate) fn probe_cost_module(import: &ProbeImport, args: &[u32]) -> anyhow::Result<Vec<u8>> {
+    let mut module = WASM_HEADER.to_vec();
+    // A single function type `(i64) -> i32`, as required of init functions.
+    write_section(
+        &mut module,
+        TYPE_SECTION,
+        &[0x01, 0x60, 0x01, 0x7E, 0x01, 0x7F],
+    );
+    write_section(&mut module, FUNCTION_SECTION, &[0x02, 0x00, 0x00]);
+    write_section(&mut module, MEMORY_SECTION, &[0x01, 0x00, 0x01]);
+    let mut exports = Vec::new();
+    write_u32(&mut exports, 2);
+    for (idx, name) in [PROBE_COST_PLAIN, PROBE_COST_PROBE].into_iter().enumerate() {
+        write_u32(&mut exports, name.len() as u32);
+        exports.extend_from_slice(name.as_bytes());
+        exports.push(0x00);
+        write_u32(&mut exports, idx as u32);
+    }
+    write_section(&mut module, EXPORT_SECTION, &exports);
+    // Both functions have no locals and return 0.
+    let body = [0x00, 0x41, 0x00, 0x0B];
+    let mut code = Vec::new();
+    write_u32(&mut code, 2);
+    for _ in 0..2 {
+        write_u32(&mut code, body.len() as u32);
+        code.extend_from_slice(&body);
+    }
+    write_section(&mut module, CODE_SECTION, &code);
+    instrument_module(&module, import, &mut SingleProbe { function: 1, args })
+}
+
+/// Emits a single probe at the start of a function.
+struct SingleProbe<'a> {
+    function: u32,
+    args: &'a [u32],
+}
+
+impl Probes for SingleProbe<'_> {
+    fn function_start(&mut self, probe: &mut ProbeWriter, function: u32, _offset: usize) {
+        if function == self.function {
+            probe.emit(self.args);
+        }
+    }
+
+    fn after_instruction(
+        &mut self,
+        _probe: &mut ProbeWriter,
+        _function: u32,
+        _instruction: &Instruction,
+    ) {
+    }
+}
+
+/// The parameters of the instrumentation of a module.
+struct Instrumentation {
+    /// The number of functions imported by the original module.
+    num_func_imports: u32,
+    /// The index of the function used for emitting probes.
+    probe: u32,
+    /// Whether the probe function is a new import, which shifts the indices of
+    /// the functions defined in the module.
+    shift: bool,
+}
+
+impl Instrumentation {
+    /// The index in the instrumented module of a function in the original
+    /// module.
+    fn remap(&self, idx: u32) -> u32 {
+        if self.shift && idx >= self.num_func_imports {
+            idx + 1
+        } else {
+            idx
+        }
+    }
+
+    fn exports(&self, bytes: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let mut reader = Reader::new(bytes);
+        let mut out = Vec::new();
+        let count = reader.u32()?;
+        write_u32(&mut out, count);
+        for _ in 0..count {
+            let start = reader.pos;
+            reader.name()?;
+            let kind = reader.u8()?;
+            out.extend_from_slice(&bytes[start..reader.pos]);
+            let idx = reader.u32()?;
+            write_u32(&mut out, if kind == 0x00 { self.remap(idx) } else { idx });
+        }
+        Ok(out)
+    }
+
+    fn elements(&self, bytes: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let mut reader = Reader::new(bytes);
+        let mut out = Vec::new();
+        let count = reader.u32()?;
+        write_u32(&mut out, count);
+        for _ in 0..count {
+            let start = reader.pos;
+            ensure!(
+                reader.u32()? == 0,
+                "Only active element segments are supported."
+            );
+            // The offset is a constant expression.
+            while reader.u8()? != 0x0B {
+                reader.leb()?;
+            }
+            out.extend_from_slice(&bytes[start..reader.pos]);
+            let num_funcs = reader.u32()?;
+            write_u32(&mut out, num_funcs);
+            for _ in 0..num_funcs {
+                let idx = reader.u32()?;
+                // Probes must be visible in the code, see `Instruction::calls_probe`.
+                ensure!(
+                    self.shift || idx != self.probe,
+                    "Modules calling the probe function indirectly are not supported."
+                );
+                write_u32(&mut out, self.remap(idx));
+            }
+        }
+        Ok(out)
+    }
+
+    fn code(&self, bytes: &[u8], probes: &mut impl Probes) -> anyhow::Result<Vec<u8>> {
+        let mut reader = Reader::new(bytes);
+        let mut out = Vec::new();
+        let count = reader.u32()?;
+        write_u32(&mut out, count);
+        for i in 0..count {
+            let size = reader.u32()? as usize;
+            let base = reader.pos;
+            let body = reader.bytes(size)?;
+            let new_body = self.function_body(probes, self.num_func_imports + i, body, base)?;
+            write_u32(&mut out, new_body.len() as u32);
+            out.extend_from_slice(&new_body);
+        }
+        Ok(out)
+    }
+
+    /// Instrument the body of the function with the given index in the
+    /// original module. The body starts at offset `base` in the code section.
+    fn function_body(
+        &self,
+        probes: &mut impl Probes,
+        function: u32,
+        body: &[u8],
+        base: usize,
+    ) -> anyhow::Result<Vec<u8>> {
+        let mut reader = Reader::new(body);
+        let num_locals = reader.u32()?;
+        for _ in 0..num_locals {
+            reader.u32()?;
+            reader.u8()?;
+        }
+        let mut out = body[..reader.pos].to_vec();
+        probes.function_start(
+            &mut ProbeWriter {
+                out: &mut out,
+                probe: self.probe,
+            },
+            function,
+            base + reader.pos,
+        );
+        while !reader.is_empty() {
+            let start = reader.pos;
+            let opcode = reader.u8()?;
+            let mut callee = None;
+            let mut calls_module_function = false;
+            match opcode {
+                0x10 => {
+                    let idx = reader.u32()?;
+                    callee = Some(idx);
+                    calls_module_function = idx >= self.num_func_imports;
+                }
+                0x11 => {
+                    reader.u32()?;
+                    reader.u8()?;
+                    calls_module_function = true;
+                }
+                0x02..=0x04 => {
+                    let block_type = reader.u8()?;
+                    if block_type & 0x80 != 0 {
+                        reader.leb()?;
+                    }
+                }
+                0x0C | 0x0D => reader.leb()?,
+                0x0E => {
+                    let num_labels = reader.u32()?;
+                    for _ in 0..=num_labels {
+                        reader.leb()?;
+                    }
+                }
+                0x20..=0x24 => reader.leb()?,
+                0x28..=0x3E => {
+                    reader.leb()?;
+                    reader.leb()?;
+                }
+                0x3F | 0x40 => {
+                    reader.u8()?;
+                }
+                0x41 | 0x42 => reader.leb()?,
+                0x43 => {
+                    reader.bytes(4)?;
+                }
+                0x44 => {
+                    reader.bytes(8)?;
+                }
+                0x00 | 0x01 | 0x05 | 0x0B | 0x0F | 0x1A | 0x1B | 0x45..=0xC4 => {}
+                opcode => bail!(
+                    "Unsupported instruction 0x{:02x} in function {}.",
+                    opcode,
+                    function
+                ),
+            }
+            let instruction = Instruction {
+                opcode,
+                offset: base + start,
+                end: base + reader.pos,
+                calls_module_function,
+                calls_probe: !self.shift && callee == Some(self.probe),
+                is_function_end: reader.is_empty(),
+            };
+            probes.before_instruction(
+                &mut ProbeWriter {
+                    out: &mut out,
+                    probe: self.probe,
+                },
+                function,
+                &instruction,
+            );
+            // The function index of calls is remapped.
+            match callee {
+                Some(idx) => {
+                    out.push(0x10);
+                    write_u32(&mut out, self.remap(idx));
+                }
+                None => out.extend_from_slice(&body[start..reader.pos]),
+            }
+            probes.after_instruction(
+                &mut ProbeWriter {
+                    out: &mut out,
+                    probe: self.probe,
+                },
+                function,
+                &instruction,
+            );
+        }
+        Ok(out)
+    }
+}
+
+/// The type section with the type of the probe function appended.
+fn probe_type_section(types: &[u8], num_types: u32, import: &ProbeImport) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_u32(&mut out, num_types + 1);
+    out.extend_from_slice(types);
+    out.push(0x60);
+    write_u32(&mut out, import.num_params as u32);
+    out.extend(std::iter::repeat_n(0x7F, import.num_params));
+    out.push(0x00);
+    out
+}
+
+/// The import section with the import of the probe function appended.
+fn probe_import_section(
+    imports: &[u8],
+    num_imports: u32,
+    import: &ProbeImport,
+    type_idx: u32,
+) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_u32(&mut out, num_imports + 1);
+    out.extend_from_slice(imports);
+    for name in [import.module, import.name] {
+        write_u32(&mut out, name.len() as u32);
+        out.extend_from_slice(name.as_bytes());
+    }
+    out.push(0x00);
+    write_u32(&mut out, type_idx);
+    out
+}
+
+/// Split a Wasm module into its sections.
+fn parse_sections(module: &[u8]) -> anyhow::Result<Vec<(u8, &[u8])>> {
+    ensure!(
+        module.starts_with(&WASM_HEADER),
+        "Expected a Wasm module in the binary format."
+    );
+    let mut reader = Reader::new(&module[WASM_HEADER.len()..]);
+    let mut sections = Vec::new();
+    while !reader.is_empty() {
+        let id = reader.u8()?;
+        let size = reader.u32()? as usize;
+        sections.push((id, reader.bytes(size)?));
+    }
+    Ok(sections)
+}
+
+/// The custom sections of a Wasm module by name, without the name.
+pub(crate) fn custom_sections(module: &[u8]) -> anyhow::Result<BTreeMap<&str, &[u8]>> {
+    let mut custom = BTreeMap::new();
+    for (id, bytes) in parse_sections(module)? {
+        if id == CUSTOM_SECTION {
+            let mut reader = Reader::new(bytes);
+            let name = reader.name()?;
+            custom.insert(name, reader.rest());
+        }
+    }
+    Ok(custom)
+}
+
+/// Read the names of the functions of a module from its name section. The
+/// names are demangled.
+pub(crate) fn function_names(module: &[u8]) -> anyhow::Result<BTreeMap<u32, String>> {
+    let mut names = BTreeMap::new();
+    let Some(bytes) = custom_sections(module)?.get("name").copied() else {
+        return Ok(names);
+    };
+    let mut reader = Reader::new(bytes);
+    while !reader.is_empty() {
+        let subsection = reader.u8()?;
+        let size = reader.u32()? as usize;
+        let mut sub_reader = Reader::new(reader.bytes(size)?);
+        // Subsection 1 contains the function names.
+        if subsection != 1 {
+            continue;
+        }
+        for _ in 0..sub_reader.u32()? {
+            let idx = sub_reader.u32()?;
+            let name = sub_reader.name()?;
+            names.insert(idx, format!("{:#}", rustc_demangle::demangle(name)));
+        }
+    }
+    Ok(names)
+}
+
+fn write_section(out: &mut Vec<u8>, id: u8, bytes: &[u8]) {
+    out.push(id);
+    write_u32(out, bytes.len() as u32);
+    out.extend_from_slice(bytes);
+}
+
+fn write_u32(out: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn write_i64(out: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// A reader of the parts of the Wasm binary format needed for instrumenting a
+/// module.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    fn rest(&self) -> &'a [u8] {
+        &self.bytes[self.pos..]
+    }
+
+    fn u8(&mut self) -> anyhow::Result<u8> {
+        let byte = *self
+            .bytes
+            .get(self.pos)
+            .context("Unexpected end of Wasm module.")?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn bytes(&mut self, len: usize) -> anyhow::Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len());
+        let end = end.context("Unexpected end of Wasm module.")?;
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    /// Skip an integer in the LEB128 encoding.
+    fn leb(&mut self) -> anyhow::Result<()> {
+        while self.u8()? & 0x80 != 0 {}
+        Ok(())
+    }
+
+    fn u32(&mut self) -> anyhow::Result<u32> {
+        let mut value = 0u32;
+        for shift in (0..35).step_by(7) {
+            let byte = self.u8()?;
+            value |= u32::from(byte & 0x7F) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        bail!("Malformed integer in Wasm module.")
+    }
+
+    fn name(&mut self) -> anyhow::Result<&'a str> {
+        let len = self.u32()? as usize;
+        std::str::from_utf8(self.bytes(len)?).context("Malformed name in Wasm module.")
+    }
+
+    /// Read a vector of bytes, such as the parameters of a function type.
+    fn vec_bytes(&mut self) -> anyhow::Result<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.bytes(len)
+    }
+
+    fn limits(&mut self) -> anyhow::Result<()> {
+        let has_max = self.u8()? == 0x01;
+        self.u32()?;
+        if has_max {
+            self.u32()?;
+        }
+        Ok(())
+    }
+}
+
+/// Limits on the resources a unit test may use.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct TestLimits {
+    /// The interpreter energy the test may use.
+    pub energy: Option<u64>,
+    /// The time the test may run for.
+    pub time: Option<Duration>,
+}
+
+/// The random bytes given to a test in place of those of the test host. The
+/// bytes of `prefix` are given first, and then bytes drawn from `rng`, or
+/// zeros if there is none. This allows recording the random input of a test,
+/// and running the test again with a modified input.
+pub(crate) struct RandomInput {
+    prefix: Vec<u8>,
+    rng: Option<SmallRng>,
+    /// The bytes given to the test so far.
+    pub used: Vec<u8>,
+}
+
+impl RandomInput {
+    /// Random bytes drawn from a random number generator with the given seed.
+    pub(crate) fn from_seed(seed: u64) -> Self {
+        Self {
+            prefix: Vec::new(),
+            rng: Some(SmallRng::seed_from_u64(seed)),
+            used: Vec::new(),
+        }
+    }
+
+    /// The given bytes followed by zeros.
+    pub(crate) fn fixed(prefix: Vec<u8>) -> Self {
+        Self {
+            prefix,
+            rng: None,
+            used: Vec::new(),
+        }
+    }
+
+    fn fill(&mut self, dest: &mut [u8]) {
+        let start = self.used.len();
+        for (i, byte) in dest.iter_mut().enumerate() {
+            *byte = self.prefix.get(start + i).copied().unwrap_or(0);
+        }
+        let from_prefix = self.prefix.len().saturating_sub(start).min(dest.len());
+        if let Some(rng) = self.rng.as_mut() {
+            rng.fill_bytes(&mut dest[from_prefix..]);
+        }
+        self.used.extend_from_slice(dest);
+    }
+}
+
+/// A host for running unit tests of an instrumented module. It records the
+/// probes emitted for profiling, together with the energy spent when they are
+/// emitted, and counts the blocks executed, for coverage. Neither kind of
+/// probe is charged any energy by the host. It also enforces the limits of the test,
+/// which are checked whenever energy is charged. Running the test must be
+/// metered for the energy to be recorded and the limits to be enforced.
+pub(crate) struct InstrumentedHost<H> {
+    pub inner: H,
+    limits: TestLimits,
+    /// The time at which the time limit is exceeded.
+    deadline: Option<Instant>,
+    /// The energy used so far.
+    pub energy_used: u64,
+    /// The arguments of each profiling probe, and the energy used when it was
+    /// emitted.
+    pub profile_probes: Vec<([u32; 2], u64)>,
+    /// The number of times each block was executed, by the index of the
+    /// block.
+    pub block_hits: Vec<u64>,
+    /// The random bytes given to the test, if they are not drawn by the inner
+    /// host.
+    pub random_input: Option<RandomInput>,
+}
+
+impl<H> InstrumentedHost<H> {
+    /// Construct a host for running a single test, which starts the time
+    /// limit of the test.
+    pub(crate) fn new(inner: H, limits: TestLimits) -> Self {
+        Self {
+            inner,
+            limits,
+            deadline: limits.time.map(|time| Instant::now() + time),
+            energy_used: 0,
+            profile_probes: Vec::new(),
+            block_hits: Vec::new(),
+            random_input: None,
+        }
+    }
+}
+
+impl<H: Host<ArtifactNamedImport>> Host<ArtifactNamedImport> for InstrumentedHost<H> {
+    type Interrupt = H::Interrupt;
+
+    fn tick_initial_memory(&mut self, num_pages: u32) -> RunResult<()> {
+        self.inner.tick_initial_memory(num_pages)
+    }
+
+    fn call(
+        &mut self,
+        f: &ArtifactNamedImport,
+        memory: &mut [u8],
+        stack: &mut RuntimeStack,
+    ) -> RunResult<Option<Self::Interrupt>> {
+        if f.matches(coverage::PROBE.module, coverage::PROBE.name) {
+            // The coverage probe is handled here, since the test host does not know it.
+            let block = unsafe { stack.pop_u32() } as usize;
+            if self.block_hits.len() <= block {
+                self.block_hits.resize(block + 1, 0);
+            }
+            self.block_hits[block] += 1;
+            return Ok(None);
+        }
+        if let Some(random_input) = self.random_input.as_mut() {
+            if f.matches("concordium", "get_random") {
+                let size = unsafe { stack.pop_u32() } as usize;
+                let dest = unsafe { stack.pop_u32() } as usize;
+                let bytes = dest
+                    .checked_add(size)
+                    .and_then(|end| memory.get_mut(dest..end))
+                    .context("Illegal memory access in get_random.")?;
+                random_input.fill(bytes);
+                return Ok(None);
+            }
+        }
+        if f.matches(profile::PROBE.module, profile::PROBE.name) {
+            // Arguments are popped in reverse order.
+            let kind = unsafe { stack.pop_u32() };
+            let function = unsafe { stack.pop_u32() };
+            self.profile_probes
+                .push(([function, kind], self.energy_used));
+            return Ok(None);
+        }
+        self.inner.call(f, memory, stack)
+    }
+
+    fn tick_energy(&mut self, energy: u64) -> RunResult<()> {
+        self.energy_used += energy;
+        if let Some(limit) = self.limits.energy {
+            ensure!(
+                self.energy_used <= limit,
+                "Test exceeded the limit of {} interpreter energy.",
+                limit
+            );
+        }
+        if let (Some(deadline), Some(time)) = (self.deadline, self.limits.time) {
+            ensure!(
+                Instant::now() <= deadline,
+                "Test exceeded the time limit of {} seconds.",
+                time.as_secs_f64()
+            );
+        }
+        self.inner.tick_energy(energy)
+    }
+
+    fn track_call(&mut self) -> RunResult<()> {
+        self.inner.track_call()
+    }
+
+    fn track_return(&mut self) {
+        self.inner.track_return()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{coverage, profile};
+    use concordium_smart_contract_engine::{
+        utils::{NoDuplicateImport, TestHost},
+        v1::{self, trie, InstanceState},
+    };
+    use concordium_wasm::{
+        machine::Value,
+        parse::parse_skeleton,
+        utils::instantiate_with_metering,
+        validate::{validate_module, ValidationConfig},
+        CostConfigurationV1,
+    };
+
+    /// The import of `debug_print`, which the test module imports itself.
+    const DEBUG_PRINT: ProbeImport = ProbeImport {
+        module: "concordium",
+        name: "debug_print",
+        num_params: 6,
+    };
+
+    /// A module with the init function `init_test`, which emits a debug event
+    /// and calls the function `add_one` both directly and through a table.
+    fn test_module() -> Vec<u8> {
+        let mut module = WASM_HEADER.to_vec();
+        // The types `(i64) -> i32`, `(i32) -> i32` and the type of `debug_print`.
+        write_section(
+            &mut module,
+            TYPE_SECTION,
+            &[
+                0x03, 0x60, 0x01, 0x7E, 0x01, 0x7F, 0x60, 0x01, 0x7F, 0x01, 0x7F, 0x60, 0x06, 0x7F,
+                0x7F, 0x7F, 0x7F, 0x7F, 0x7F, 0x00,
+            ],
+        );
+        let mut imports = Vec::new();
+        write_u32(&mut imports, 1);
+        for name in [DEBUG_PRINT.module, DEBUG_PRINT.name] {
+            write_u32(&mut imports, name.len() as u32);
+            imports.extend_from_slice(name.as_bytes());
+        }
+        imports.extend_from_slice(&[0x00, 0x02]);
+        write_section(&mut module, IMPORT_SECTION, &imports);
+        write_section(&mut module, FUNCTION_SECTION, &[0x02, 0x00, 0x01]);
+        write_section(&mut module, TABLE_SECTION, &[0x01, 0x70, 0x00, 0x01]);
+        write_section(&mut module, MEMORY_SECTION, &[0x01, 0x00, 0x01]);
+        let mut exports = Vec::new();
+        write_u32(&mut exports, 1);
+        write_u32(&mut exports, 9);
+        exports.extend_from_slice(b"init_test");
+        exports.extend_from_slice(&[0x00, 0x01]);
+        write_section(&mut module, EXPORT_SECTION, &exports);
+        // The table contains `add_one` at index 0.
+        write_section(
+            &mut module,
+            ELEMENT_SECTION,
+            &[0x01, 0x00, 0x41, 0x00, 0x0B, 0x01, 0x02],
+        );
+        let init_test: &[u8] = &[
+            0x00, // No locals.
+            0x41, 0x00, 0x41, 0x00, 0x41, 0x00, 0x41, 0x00, 0x41, 0x00, 0x41, 0x00, 0x10,
+            0x00, // debug_print(0, 0, 0, 0, 0, 0)
+            0x41, 0x01, 0x10, 0x02, // add_one(1)
+            0x41, 0x00, 0x11, 0x01, 0x00, // add_one through the table
+            0x41, 0x03, 0x6B, // Return 3 - 3.
+            0x0B,
+        ];
+        let add_one: &[u8] = &[0x00, 0x20, 0x00, 0x41, 0x01, 0x6A, 0x0B];
+        let mut code = Vec::new();
+        write_u32(&mut code, 2);
+        for body in [init_test, add_one] {
+            write_u32(&mut code, body.len() as u32);
+            code.extend_from_slice(body);
+        }
+        write_section(&mut module, CODE_SECTION, &code);
+        module
+    }
+
+    /// Emits a probe with zeros as arguments at every place possible.
+    struct EveryInstruction {
+        num_params: usize,
+    }
+
+    impl Probes for EveryInstruction {
+        fn function_start(&mut self, probe: &mut ProbeWriter, _function: u32, _offset: usize) {
+            probe.emit(&vec![0; self.num_params]);
+        }
+
+        fn before_instruction(
+            &mut self,
+            probe: &mut ProbeWriter,
+            _function: u32,
+            _instruction: &Instruction,
+        ) {
+            probe.emit(&vec![0; self.num_params]);
+        }
+
+        fn after_instruction(
+            &mut self,
+            probe: &mut ProbeWriter,
+            _function: u32,
+            instruction: &Instruction,
+        ) {
+            if !instruction.is_function_end {
+                probe.emit(&vec![0; self.num_params]);
+            }
+        }
+    }
+
+    fn instrument(import: &ProbeImport) -> Vec<u8> {
+        instrument_module(
+            &test_module(),
+            import,
+            &mut EveryInstruction {
+                num_params: import.num_params,
+            },
+        )
+        .expect("Instrumentation should succeed.")
+    }
+
+    /// Run the init function with metering, returning the energy used and the
+    /// number of profiling probes emitted.
+    fn run(module: &[u8], name: &str) -> (u64, usize) {
+        let artifact = instantiate_with_metering::<ArtifactNamedImport, _>(
+            ValidationConfig::V1,
+            CostConfigurationV1,
+            &NoDuplicateImport,
+            module,
+        )
+        .expect("Instantiation should succeed.")
+        .artifact;
+        let mut initial_state = trie::MutableState::initial_state();
+        let mut loader = trie::Loader::new(Vec::new());
+        let mut host = {
+            let inner = initial_state.get_inner(&mut loader);
+            let state = InstanceState::new(loader, inner);
+            InstrumentedHost::new(
+                TestHost::new(SmallRng::seed_from_u64(0), state),
+                TestLimits::default(),
+            )
+        };
+        artifact
+            .run(&mut host, name, &[Value::I64(0)])
+            .expect("Running the function should succeed.");
+        (host.energy_used, host.profile_probes.len())
+    }
+
+    #[test]
+    fn instrumented_modules_are_valid() {
+        for import in [&coverage::PROBE, &profile::PROBE] {
+            let module = instrument(import);
+            let skeleton = parse_skeleton(&module).expect("Parsing should succeed.");
+            validate_module(ValidationConfig::V1, &NoDuplicateImport, &skeleton)
+                .expect("The instrumented module should be valid.");
+        }
+        // Probes using an import of the module itself only use the host functions
+        // of the chain.
+        let module = instrument(&DEBUG_PRINT);
+        let skeleton = parse_skeleton(&module).expect("Parsing should succeed.");
+        validate_module(
+            ValidationConfig::V1,
+            &v1::ConcordiumAllowedImports {
+                support_upgrade: true,
+                enable_debug: true,
+            },
+            &skeleton,
+        )
+        .expect("The instrumented module should be valid on the chain.");
+    }
+
+    #[test]
+    fn function_indices_are_remapped() {
+        let module = instrument(&profile::PROBE);
+        let sections = parse_sections(&module).expect("Parsing should succeed.");
+        let section = |id| {
+            sections
+                .iter()
+                .find(|(section_id, _)| *section_id == id)
+                .map(|(_, bytes)| *bytes)
+                .expect("The section should be present.")
+        };
+        // The probe is imported after `debug_print`, shifting `init_test` to 2.
+        let mut exports = Reader::new(section(EXPORT_SECTION));
+        assert_eq!(exports.u32().unwrap(), 1);
+        assert_eq!(exports.name().unwrap(), "init_test");
+        assert_eq!(exports.u8().unwrap(), 0x00);
+        assert_eq!(exports.u32().unwrap(), 2);
+        // The table contains `add_one`, which is shifted to 3.
+        assert_eq!(
+            section(ELEMENT_SECTION),
+            &[0x01, 0x00, 0x41, 0x00, 0x0B, 0x01, 0x03]
+        );
+    }
+
+    #[test]
+    fn instrumentation_only_adds_the_energy_of_probes() {
+        let probe_cost_module = probe_cost_module(&profile::PROBE, &[0, 0]).unwrap();
+        let (with_probe, _) = run(&probe_cost_module, PROBE_COST_PROBE);
+        let (without_probe, _) = run(&probe_cost_module, PROBE_COST_PLAIN);
+        let probe_cost = with_probe - without_probe;
+        assert!(probe_cost > 0);
+
+        let (original, num_probes) = run(&test_module(), "init_test");
+        assert_eq!(num_probes, 0);
+        let (instrumented, num_probes) = run(&instrument(&profile::PROBE), "init_test");
+        assert!(num_probes > 0);
+        assert_eq!(instrumented - probe_cost * num_probes as u64, original);
+    }
+}
@@ -0,0 +1,177 @@
+//! Conversion of schema types to JSON Schema (draft 2020-12) documents
+//! describing the JSON representation of their values, as accepted by
+//! `--parameter-json` and produced when values are printed as JSON. The
+//! documents can be used by frontends for validating input and generating
+//! forms.
+use concordium_base::contracts_common::schema::{Fields, SizeLength, Type};
+use serde_json::{json, Value};
+
+/// The dialect of the generated documents.
+const JSON_SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// A JSON Schema document for the JSON representation of values of the type.
+pub(crate) fn json_schema_document(ty: &Type) -> Value {
+    let mut document = json!({ "$schema": JSON_SCHEMA_DIALECT });
+    if let (Value::Object(document), Value::Object(schema)) = (&mut document, json_schema(ty)) {
+        document.extend(schema);
+    }
+    document
+}
+
+/// The largest length allowed by a length prefix, if it restricts lengths.
+fn max_length(len: &SizeLength) -> Option<u64> {
+    match len {
+        SizeLength::U8 => Some(u8::MAX.into()),
+        SizeLength::U16 => Some(u16::MAX.into()),
+        SizeLength::U32 => Some(u32::MAX.into()),
+        SizeLength::U64 => None,
+    }
+}
+
+/// A JSON array of values of the type `items`, with at most as many elements as
+/// allowed by the length prefix.
+fn array_schema(len: &SizeLength, items: Value) -> Value {
+    let mut schema = json!({ "type": "array", "items": items });
+    if let Some(max) = max_length(len) {
+        schema["maxItems"] = max.into();
+    }
+    schema
+}
+
+/// A JSON array with exactly one element of each of the given types.
+fn tuple_schema(items: Vec<Value>) -> Value {
+    let len = items.len();
+    json!({
+        "type": "array",
+        "prefixItems": items,
+        "items": false,
+        "minItems": len,
+        "maxItems": len,
+    })
+}
+
+fn integer_schema(minimum: impl Into<Value>, maximum: impl Into<Value>) -> Value {
+    json!({ "type": "integer", "minimum": minimum.into(), "maximum": maximum.into() })
+}
+
+/// Integers that do not fit in a JSON number are represented by strings.
+fn integer_string_schema(signed: bool, description: &str) -> Value {
+    let pattern = if signed { "^-?[0-9]+$" } else { "^[0-9]+$" };
+    json!({ "type": "string", "pattern": pattern, "description": description })
+}
+
+/// An object with exactly the given properties.
+fn object_schema(properties: serde_json::Map<String, Value>) -> Value {
+    let required: Vec<_> = properties.keys().cloned().collect();
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false,
+    })
+}
+
+/// Named fields are represented by an object, unnamed fields by an array, and
+/// no fields by an empty array.
+fn fields_schema(fields: &Fields) -> Value {
+    match fields {
+        Fields::Named(fields) => object_schema(
+            fields
+                .iter()
+                .map(|(name, ty)| (name.clone(), json_schema(ty)))
+                .collect(),
+        ),
+        Fields::Unnamed(fields) => tuple_schema(fields.iter().map(json_schema).collect()),
+        Fields::None => tuple_schema(Vec::new()),
+    }
+}
+
+/// An enum is represented by an object with the name of the variant as its
+/// only property.
+fn variants_schema<'a>(variants: impl Iterator<Item = &'a (String, Fields)>) -> Value {
+    let variants: Vec<_> = variants
+        .map(|(name, fields)| {
+            let mut properties = serde_json::Map::new();
+            properties.insert(name.clone(), fields_schema(fields));
+            object_schema(properties)
+        })
+        .collect();
+    json!({ "oneOf": variants })
+}
+
+/// The JSON Schema of the JSON representation of values of the type.
+fn json_schema(ty: &Type) -> Value {
+    match ty {
+        // The value is ignored when serializing.
+        Type::Unit => json!({}),
+        Type::Bool => json!({ "type": "boolean" }),
+        Type::U8 => integer_schema(u8::MIN, u8::MAX),
+        Type::U16 => integer_schema(u16::MIN, u16::MAX),
+        Type::U32 => integer_schema(u32::MIN, u32::MAX),
+        Type::U64 => integer_schema(u64::MIN, u64::MAX),
+        Type::I8 => integer_schema(i8::MIN, i8::MAX),
+        Type::I16 => integer_schema(i16::MIN, i16::MAX),
+        Type::I32 => integer_schema(i32::MIN, i32::MAX),
+        Type::I64 => integer_schema(i64::MIN, i64::MAX),
+        Type::U128 => integer_string_schema(false, "An unsigned 128-bit integer."),
+        Type::I128 => integer_string_schema(true, "A signed 128-bit integer."),
+        Type::ULeb128(_) => integer_string_schema(false, "An unsigned integer."),
+        Type::ILeb128(_) => integer_string_schema(true, "A signed integer."),
+        Type::Amount => integer_string_schema(false, "An amount of micro CCD."),
+        Type::AccountAddress => json!({
+            "type": "string",
+            "pattern": "^[1-9A-HJ-NP-Za-km-z]{50}$",
+            "description": "An account address in base58check.",
+        }),
+        Type::ContractAddress => {
+            let mut properties = serde_json::Map::new();
+            properties.insert("index".into(), integer_schema(u64::MIN, u64::MAX));
+            properties.insert("subindex".into(), integer_schema(u64::MIN, u64::MAX));
+            object_schema(properties)
+        }
+        Type::Timestamp => json!({ "type": "string", "format": "date-time" }),
+        Type::Duration => json!({
+            "type": "string",
+            "description": "A duration, e.g., '10d 1h 42m 7s 120ms'.",
+        }),
+        Type::Pair(left, right) => tuple_schema(vec![json_schema(left), json_schema(right)]),
+        Type::List(len, elem) => array_schema(len, json_schema(elem)),
+        Type::Set(len, elem) => {
+            let mut schema = array_schema(len, json_schema(elem));
+            schema["uniqueItems"] = true.into();
+            schema
+        }
+        // Maps are represented by an array of key-value pairs.
+        Type::Map(len, key, value) => array_schema(
+            len,
+            tuple_schema(vec![json_schema(key), json_schema(value)]),
+        ),
+        Type::Array(len, elem) => json!({
+            "type": "array",
+            "items": json_schema(elem),
+            "minItems": len,
+            "maxItems": len,
+        }),
+        Type::Struct(fields) => fields_schema(fields),
+        Type::Enum(variants) => variants_schema(variants.iter()),
+        Type::TaggedEnum(variants) => variants_schema(variants.values()),
+        Type::String(_) => json!({ "type": "string" }),
+        Type::ContractName(_) => {
+            let mut properties = serde_json::Map::new();
+            properties.insert("contract".into(), json!({ "type": "string" }));
+            object_schema(properties)
+        }
+        Type::ReceiveName(_) => {
+            let mut properties = serde_json::Map::new();
+            properties.insert("contract".into(), json!({ "type": "string" }));
+            properties.insert("func".into(), json!({ "type": "string" }));
+            object_schema(properties)
+        }
+        // Bytes are represented in hex.
+        Type::ByteList(_) => json!({ "type": "string", "pattern": "^([0-9a-fA-F]{2})*$" }),
+        Type::ByteArray(len) => json!({
+            "type": "string",
+            "pattern": format!("^[0-9a-fA-F]{{{}}}$", 2 * u64::from(*len)),
+        }),
+    }
+}
@@ -0,0 +1,744 @@
+//! Simulating the interactions of a contract with the chain, i.e., calls to
+//! other contract instances that are deployed locally, given by a module and a
+//! state file, and queries answered from a mock chain state.
+use crate::{
+    chain_state::{answer_contract_query, ChainState, ContractInfo},
+    contract_schema::{contract_schemas, ContractSchema},
+    find_receive_name, print_debug, print_interrupt, print_logs_v1, read_state_from_file,
+    report::{ChainTraceEntry, OutputFormat, ValueSchemas},
+};
+use anyhow::{bail, ensure, Context};
+use concordium_base::{
+    contracts_common::{
+        schema::Type, AccountAddress, Address, Amount, ChainMetadata, ContractAddress,
+        OwnedEntrypointName, OwnedParameter, SlotTime, WasmVersion,
+    },
+    smart_contracts::WasmModule,
+};
+use concordium_smart_contract_engine::{
+    utils, v0,
+    v1::{self, DebugTracker},
+    InterpreterEnergy,
+};
+use concordium_wasm::{
+    artifact::{Artifact, CompiledFunction},
+    validate::ValidationConfig,
+    CostConfigurationV1,
+};
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+type ArtifactV1 = Artifact<v1::ProcessedImports, CompiledFunction>;
+
+/// A complete receive context, as required for resuming an interrupted
+/// receive call.
+pub(crate) type CompleteReceiveContext = v1::ReceiveContext<v0::OwnedPolicyBytes>;
+
+/// The result of a receive call whose calls to local contracts have been
+/// resolved.
+pub(crate) type ResolvedReceiveResult = v1::ReceiveResult<CompiledFunction, DebugTracker>;
+
+/// An entry of the contracts file.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InstanceSpec {
+    /// The address of the instance.
+    address: ContractAddress,
+    /// Path to the module of the instance, relative to the contracts file.
+    module: PathBuf,
+    /// The name of the contract, without the `init_` prefix.
+    contract: String,
+    /// Path to the state of the instance in binary, relative to the contracts
+    /// file.
+    state_bin: PathBuf,
+    /// The balance of the instance. Defaults to zero.
+    #[serde(default)]
+    balance: Amount,
+    /// The owner of the instance. Defaults to the invoker of the transaction.
+    owner: Option<AccountAddress>,
+    /// Where to write the new state of the instance in binary, relative to
+    /// the contracts file.
+    out_bin: Option<PathBuf>,
+}
+
+/// A contract instance that is deployed locally.
+struct LocalInstance {
+    artifact: Arc<ArtifactV1>,
+    module_reference: [u8; 32],
+    contract_name: String,
+    state: v1::trie::PersistentState,
+    balance: Amount,
+    owner: Option<AccountAddress>,
+    /// The schema of the contract, if the module has an embedded schema.
+    schema: Option<ContractSchema>,
+    out_bin: Option<PathBuf>,
+}
+
+/// The parts of the context of an executing receive call that are needed for
+/// constructing the context of the contracts it calls.
+struct Frame {
+    address: ContractAddress,
+    invoker: AccountAddress,
+    slot_time: SlotTime,
+    sender_policies: v0::OwnedPolicyBytes,
+    balance: Amount,
+}
+
+/// The result of calling a local contract.
+enum CallResult {
+    /// The call finished, and the caller should be resumed with the response.
+    Response {
+        response: v1::InvokeResponse,
+        remaining_energy: InterpreterEnergy,
+    },
+    /// The call ran out of energy, which terminates the entire transaction.
+    OutOfEnergy(DebugTracker),
+}
+
+/// The contract instances that calls can be made to, and the chain state
+/// that queries are answered from.
+pub(crate) struct LocalChain {
+    instances: BTreeMap<ContractAddress, LocalInstance>,
+    chain_state: ChainState,
+    /// The addresses of the instances that are currently executing. This is
+    /// used to detect reentrant calls, which are not supported.
+    call_stack: Vec<ContractAddress>,
+    /// The calls and queries are printed as they happen when the output is
+    /// text, and recorded in `chain_trace` for the report otherwise.
+    output_format: OutputFormat,
+    chain_trace: Vec<ChainTraceEntry>,
+}
+
+impl LocalChain {
+    /// Construct the local chain from the instances listed in a contracts
+    /// file, if any, and the chain state.
+    pub(crate) fn new(
+        contracts_file: Option<&Path>,
+        chain_state: ChainState,
+        output_format: OutputFormat,
+    ) -> anyhow::Result<Self> {
+        let instances = match contracts_file {
+            Some(path) => load_instances(path)?,
+            None => BTreeMap::new(),
+        };
+        Ok(Self {
+            instances,
+            chain_state,
+            call_stack: Vec::new(),
+            output_format,
+            chain_trace: Vec::new(),
+        })
+    }
+
+    /// Take the calls and queries recorded for the report.
+    pub(crate) fn take_chain_trace(&mut self) -> Vec<ChainTraceEntry> {
+        std::mem::take(&mut self.chain_trace)
+    }
+
+    /// Report a call that failed before the callee was executed.
+    fn call_failed(&mut self, address: ContractAddress, reason: String) {
+        match self.output_format {
+            OutputFormat::Text => eprintln!("The call failed since {}.", reason),
+            OutputFormat::Json => self
+                .chain_trace
+                .push(ChainTraceEntry::CallFailed { address, reason }),
+        }
+    }
+
+    /// Write the states of the instances that specify where to write them.
+    pub(crate) fn write_states(&self) -> anyhow::Result<()> {
+        let mut loader = v1::trie::Loader::new(&[][..]);
+        for (address, instance) in &self.instances {
+            if let Some(file_path) = &instance.out_bin {
+                let mut out_file = std::fs::File::create(file_path)
+                    .context("Could not create file to write state into.")?;
+                instance
+                    .state
+                    .serialize(&mut loader, &mut out_file)
+                    .context("Could not write the state.")?;
+                eprintln!(
+                    "Resulting state of contract {} written to {}.",
+                    address,
+                    file_path.display()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Invoke a receive function, and resolve the calls it makes to the local
+    /// contracts by executing them and resuming the invocation with their
+    /// result. The `state` is updated in place. The resulting `state_changed`
+    /// flag accounts for changes made before any of the calls.
+    ///
+    /// Queries are answered from the local instances and the chain state.
+    /// Other interrupts, i.e., transfers and upgrades, are returned as the
+    /// result.
+    pub(crate) fn invoke(
+        &mut self,
+        artifact: Arc<ArtifactV1>,
+        ctx: CompleteReceiveContext,
+        invocation: v1::ReceiveInvocation,
+        state: &mut v1::trie::MutableState,
+        schema_event: Option<&Type>,
+        emit_debug: bool,
+    ) -> anyhow::Result<ResolvedReceiveResult> {
+        let mut frame = Frame {
+            address: ctx.common.self_address,
+            invoker: ctx.common.invoker,
+            slot_time: ctx.common.metadata.slot_time,
+            sender_policies: ctx.common.sender_policies.clone(),
+            balance: ctx.common.self_balance,
+        };
+        ensure!(
+            !self.call_stack.contains(&frame.address),
+            "Contract {} is called while it is already executing. Reentrant calls cannot be \
+             simulated.",
+            frame.address
+        );
+        self.call_stack.push(frame.address);
+
+        let mut loader = v1::trie::Loader::new(&[][..]);
+        let inner = state.get_inner(&mut loader);
+        let instance_state = v1::InstanceState::new(loader, inner);
+        let mut result = v1::invoke_receive::<
+            _,
+            _,
+            _,
+            _,
+            CompleteReceiveContext,
+            CompleteReceiveContext,
+            DebugTracker,
+        >(
+            artifact,
+            ctx,
+            invocation,
+            instance_state,
+            v1::ReceiveParams::new_p6(),
+        )
+        .context("Calling receive failed.")?;
+
+        let mut state_changed_before_call = false;
+        let result = loop {
+            match result {
+                v1::ReceiveResult::Interrupt {
+                    remaining_energy,
+                    state_changed,
+                    logs,
+                    config,
+                    interrupt:
+                        v1::Interrupt::Call {
+                            address,
+                            parameter,
+                            name,
+                            amount,
+                        },
+                    trace,
+                } => {
+                    match self.output_format {
+                        OutputFormat::Text => {
+                            eprintln!(
+                                "\nContract {} calls entrypoint '{}' of contract {} with amount \
+                                 {}. The following logs were produced before the call.",
+                                frame.address, name, address, amount
+                            );
+                            print_logs_v1(logs, schema_event);
+                            if emit_debug {
+                                print_debug(trace);
+                            }
+                        }
+                        OutputFormat::Json => self.chain_trace.push(ChainTraceEntry::call(
+                            frame.address,
+                            address,
+                            &name,
+                            amount,
+                            &parameter,
+                            &logs,
+                            schema_event,
+                            &trace,
+                        )),
+                    }
+                    state_changed_before_call |= state_changed;
+                    let call_result = self.call(
+                        &mut frame,
+                        address,
+                        name,
+                        parameter,
+                        amount,
+                        remaining_energy,
+                        emit_debug,
+                    )?;
+                    let (response, remaining_energy) = match call_result {
+                        CallResult::Response {
+                            response,
+                            remaining_energy,
+                        } => (response, remaining_energy),
+                        CallResult::OutOfEnergy(trace) => {
+                            break v1::ReceiveResult::OutOfEnergy { trace }
+                        }
+                    };
+                    // Reentrant calls are rejected, so the state cannot have been changed by
+                    // the call.
+                    result = v1::resume_receive(
+                        config,
+                        response,
+                        remaining_energy,
+                        state,
+                        false,
+                        loader,
+                    )
+                    .context("Resuming the receive call failed.")?;
+                }
+                v1::ReceiveResult::Interrupt {
+                    remaining_energy,
+                    state_changed,
+                    logs,
+                    config,
+                    interrupt,
+                    trace,
+                } => {
+                    let Some(response) = self.answer_query(&frame, &interrupt)? else {
+                        break v1::ReceiveResult::Interrupt {
+                            remaining_energy,
+                            state_changed,
+                            logs,
+                            config,
+                            interrupt,
+                            trace,
+                        };
+                    };
+                    match self.output_format {
+                        OutputFormat::Text => {
+                            eprintln!(
+                                "\nContract {} queries the chain. The following logs were \
+                                 produced before the query.",
+                                frame.address
+                            );
+                            print_logs_v1(logs, schema_event);
+                            print_interrupt(&interrupt);
+                            if emit_debug {
+                                print_debug(trace);
+                            }
+                        }
+                        OutputFormat::Json => self.chain_trace.push(ChainTraceEntry::query(
+                            frame.address,
+                            interrupt,
+                            &logs,
+                            schema_event,
+                            &trace,
+                        )),
+                    }
+                    state_changed_before_call |= state_changed;
+                    result = v1::resume_receive(
+                        config,
+                        response,
+                        remaining_energy,
+                        state,
+                        false,
+                        loader,
+                    )
+                    .context("Resuming the receive call failed.")?;
+                }
+                v1::ReceiveResult::Success {
+                    logs,
+                    state_changed,
+                    remaining_energy,
+                    return_value,
+                    trace,
+                } => {
+                    break v1::ReceiveResult::Success {
+                        logs,
+                        state_changed: state_changed || state_changed_before_call,
+                        remaining_energy,
+                        return_value,
+                        trace,
+                    }
+                }
+                other => break other,
+            }
+        };
+
+        self.call_stack.pop();
+        if let Some(instance) = self.instances.get_mut(&frame.address) {
+            instance.balance = frame.balance;
+        }
+        Ok(result)
+    }
+
+    /// Call a local contract on behalf of the contract executing in `caller`.
+    /// If the call fails, then all changes made by it are rolled back.
+    #[allow(clippy::too_many_arguments)]
+    fn call(
+        &mut self,
+        caller: &mut Frame,
+        address: ContractAddress,
+        entrypoint: OwnedEntrypointName,
+        parameter: v1::ParameterVec,
+        amount: Amount,
+        energy: InterpreterEnergy,
+        emit_debug: bool,
+    ) -> anyhow::Result<CallResult> {
+        let failure = |kind| CallResult::Response {
+            response: v1::InvokeResponse::Failure { kind },
+            remaining_energy: energy,
+        };
+        if caller.balance < amount {
+            self.call_failed(
+                address,
+                format!("contract {} has insufficient funds", caller.address),
+            );
+            return Ok(failure(v1::InvokeFailure::InsufficientAmount));
+        }
+        let Some(instance) = self.instances.get(&address) else {
+            self.call_failed(
+                address,
+                format!("contract {address} is not in the contracts file"),
+            );
+            return Ok(failure(v1::InvokeFailure::NonExistentContract));
+        };
+        let Some((receive_name, _)) = find_receive_name(
+            instance.artifact.as_ref(),
+            &instance.contract_name,
+            &entrypoint.to_string(),
+        ) else {
+            self.call_failed(
+                address,
+                format!(
+                    "contract {} has neither the entrypoint '{}', nor a fallback entrypoint",
+                    address, entrypoint
+                ),
+            );
+            return Ok(failure(v1::InvokeFailure::NonExistentEntrypoint));
+        };
+
+        let balance = instance.balance;
+        let Some(callee_balance) = balance.checked_add(amount) else {
+            self.call_failed(
+                address,
+                format!(
+                    "adding {} to the balance {} of contract {} overflows",
+                    amount, balance, address
+                ),
+            );
+            return Ok(failure(v1::InvokeFailure::RuntimeError));
+        };
+
+        let snapshot = self.snapshot();
+        let caller_balance = caller.balance;
+        caller.balance = Amount::from_micro_ccd(caller.balance.micro_ccd - amount.micro_ccd);
+        if let Some(caller_instance) = self.instances.get_mut(&caller.address) {
+            caller_instance.balance = caller.balance;
+        }
+        let instance = self
+            .instances
+            .get_mut(&address)
+            .expect("The instance exists, as checked above.");
+        instance.balance = callee_balance;
+        let ctx = v1::ReceiveContext {
+            common: v0::ReceiveContext {
+                metadata: ChainMetadata {
+                    slot_time: caller.slot_time,
+                },
+                invoker: caller.invoker,
+                self_address: address,
+                self_balance: instance.balance,
+                sender: Address::Contract(caller.address),
+                owner: instance.owner.unwrap_or(caller.invoker),
+                sender_policies: caller.sender_policies.clone(),
+            },
+            entrypoint: entrypoint.clone(),
+        };
+        let artifact = Arc::clone(&instance.artifact);
+        let schema = instance.schema.clone();
+        let schema_event = schema.as_ref().and_then(|schema| schema.event.clone());
+        // The schema of the receive function invoked, which is the fallback
+        // entrypoint if the contract does not have the entrypoint.
+        let function_schema = schema.as_ref().and_then(|schema| {
+            let entrypoint = receive_name.as_receive_name().entrypoint_name().to_string();
+            schema.function(Some(&entrypoint)).cloned()
+        });
+        let schemas = ValueSchemas {
+            event: schema_event.as_ref(),
+            return_value: function_schema
+                .as_ref()
+                .and_then(|function| function.return_value.as_ref()),
+            error: function_schema
+                .as_ref()
+                .and_then(|function| function.error.as_ref()),
+        };
+        let mut state = instance.state.thaw();
+        let parameter = OwnedParameter::new_unchecked(parameter);
+
+        let result = self.invoke(
+            artifact,
+            ctx,
+            v1::ReceiveInvocation {
+                amount,
+                receive_name: receive_name.as_receive_name(),
+                parameter: parameter.as_ref(),
+                energy,
+            },
+            &mut state,
+            schema_event.as_ref(),
+            emit_debug,
+        )?;
+
+        let (response, remaining_energy) = match result {
+            v1::ReceiveResult::Success {
+                logs,
+                state_changed,
+                remaining_energy,
+                return_value,
+                trace,
+            } => {
+                match self.output_format {
+                    OutputFormat::Text => {
+                        eprintln!(
+                            "Call to contract {} succeeded. The following logs were produced.",
+                            address
+                        );
+                        print_logs_v1(logs, schema_event.as_ref());
+                        if emit_debug {
+                            print_debug(trace);
+                        }
+                    }
+                    OutputFormat::Json => self.chain_trace.push(ChainTraceEntry::succeeded(
+                        address,
+                        &logs,
+                        &return_value,
+                        schemas,
+                        &trace,
+                    )),
+                }
+                if state_changed {
+                    let mut loader = v1::trie::Loader::new(&[][..]);
+                    let mut collector = v1::trie::SizeCollector::default();
+                    let instance = self
+                        .instances
+                        .get_mut(&address)
+                        .expect("Instances are never removed.");
+                    instance.state = state.freeze(&mut loader, &mut collector);
+                }
+                let response = v1::InvokeResponse::Success {
+                    new_balance: caller.balance,
+                    data: Some(return_value),
+                };
+                return Ok(CallResult::Response {
+                    response,
+                    remaining_energy,
+                });
+            }
+            v1::ReceiveResult::Reject {
+                remaining_energy,
+                reason,
+                return_value,
+                trace,
+            } => {
+                match self.output_format {
+                    OutputFormat::Text => {
+                        eprintln!(
+                            "Call to contract {} rejected with reason {}.",
+                            address, reason
+                        );
+                        if emit_debug {
+                            print_debug(trace);
+                        }
+                    }
+                    OutputFormat::Json => self.chain_trace.push(ChainTraceEntry::rejected(
+                        address,
+                        reason,
+                        &return_value,
+                        schemas,
+                        &trace,
+                    )),
+                }
+                let kind = v1::InvokeFailure::ContractReject {
+                    code: reason,
+                    data: return_value,
+                };
+                (v1::InvokeResponse::Failure { kind }, remaining_energy)
+            }
+            v1::ReceiveResult::Trap {
+                remaining_energy,
+                error,
+                trace,
+            } => {
+                match self.output_format {
+                    OutputFormat::Text => {
+                        eprintln!(
+                            "Call to contract {} triggered a runtime error: {:#}",
+                            address, error
+                        );
+                        if emit_debug {
+                            print_debug(trace);
+                        }
+                    }
+                    OutputFormat::Json => self
+                        .chain_trace
+                        .push(ChainTraceEntry::trapped(address, &error, &trace)),
+                }
+                let kind = v1::InvokeFailure::RuntimeError;
+                (v1::InvokeResponse::Failure { kind }, remaining_energy)
+            }
+            v1::ReceiveResult::OutOfEnergy { trace } => {
+                match self.output_format {
+                    OutputFormat::Text => eprintln!(
+                        "Call to contract {} terminated with: out of energy.",
+                        address
+                    ),
+                    OutputFormat::Json => self
+                        .chain_trace
+                        .push(ChainTraceEntry::CallOutOfEnergy { address }),
+                }
+                return Ok(CallResult::OutOfEnergy(trace));
+            }
+            v1::ReceiveResult::Interrupt { interrupt, .. } => {
+                if self.output_format == OutputFormat::Text {
+                    print_interrupt(&interrupt);
+                }
+                bail!(
+                    "Contract {} was interrupted by an operation that cannot be simulated locally.",
+                    address
+                );
+            }
+        };
+        // The call failed, so roll back its changes.
+        self.restore(snapshot);
+        caller.balance = caller_balance;
+        Ok(CallResult::Response {
+            response,
+            remaining_energy,
+        })
+    }
+
+    /// Answer a query made by the contract executing in `frame`. Returns
+    /// `None` if the interrupt is not a query.
+    fn answer_query(
+        &self,
+        frame: &Frame,
+        interrupt: &v1::Interrupt,
+    ) -> anyhow::Result<Option<v1::InvokeResponse>> {
+        if let Some(response) = self.chain_state.answer_query(interrupt, frame.balance)? {
+            return Ok(Some(response));
+        }
+        let address = match interrupt {
+            v1::Interrupt::QueryContractBalance { address }
+            | v1::Interrupt::QueryContractModuleReference { address }
+            | v1::Interrupt::QueryContractName { address } => address,
+            _ => return Ok(None),
+        };
+        let contract = self.contract_info(frame, address)?;
+        answer_contract_query(interrupt, contract, frame.balance)
+    }
+
+    /// Get what is known about a contract instance. Local instances take
+    /// precedence over the instances of the chain state.
+    fn contract_info(
+        &self,
+        frame: &Frame,
+        address: &ContractAddress,
+    ) -> anyhow::Result<Option<ContractInfo>> {
+        let info = match self.instances.get(address) {
+            Some(instance) => Some(ContractInfo {
+                balance: instance.balance,
+                module_reference: Some(instance.module_reference),
+                name: Some(format!("init_{}", instance.contract_name)),
+            }),
+            None => self.chain_state.contract_info(address)?,
+        };
+        if *address != frame.address {
+            return Ok(info);
+        }
+        // The balance of the executing contract is tracked in its frame.
+        Ok(Some(match info {
+            Some(info) => ContractInfo {
+                balance: frame.balance,
+                ..info
+            },
+            None => ContractInfo {
+                balance: frame.balance,
+                module_reference: None,
+                name: None,
+            },
+        }))
+    }
+
+    /// Take a snapshot of the states and balances of all instances.
+    fn snapshot(&self) -> BTreeMap<ContractAddress, (v1::trie::PersistentState, Amount)> {
+        self.instances
+            .iter()
+            .map(|(address, instance)| (*address, (instance.state.clone(), instance.balance)))
+            .collect()
+    }
+
+    /// Restore the states and balances of all instances from a snapshot.
+    fn restore(
+        &mut self,
+        snapshot: BTreeMap<ContractAddress, (v1::trie::PersistentState, Amount)>,
+    ) {
+        for (address, (state, balance)) in snapshot {
+            if let Some(instance) = self.instances.get_mut(&address) {
+                instance.state = state;
+                instance.balance = balance;
+            }
+        }
+    }
+}
+
+/// Load the contract instances listed in a contracts file.
+fn load_instances(path: &Path) -> anyhow::Result<BTreeMap<ContractAddress, LocalInstance>> {
+    let contents = fs::read(path)
+        .with_context(|| format!("Could not read contracts file {}.", path.display()))?;
+    let specs: Vec<InstanceSpec> =
+        serde_json::from_slice(&contents).context("Could not parse the contracts file.")?;
+    let base_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    let mut instances = BTreeMap::new();
+    for spec in specs {
+        let module_path = base_dir.join(&spec.module);
+        let versioned_module = WasmModule::from_file(&module_path)
+            .with_context(|| format!("Could not read module file {}", module_path.display()))?;
+        ensure!(
+            matches!(versioned_module.version, WasmVersion::V1),
+            "The module of contract {} is not a V1 module. Only calls to V1 contracts can be \
+             simulated.",
+            spec.address
+        );
+        let module_reference = versioned_module.get_module_ref().bytes;
+        let module = versioned_module.source.as_ref();
+        let artifact = concordium_wasm::utils::instantiate_with_metering(
+            ValidationConfig::V1,
+            CostConfigurationV1,
+            &v1::ConcordiumAllowedImports {
+                support_upgrade: true,
+                enable_debug: true,
+            },
+            module,
+        )
+        .with_context(|| format!("Could not instantiate the module of {}.", spec.address))?
+        .artifact;
+        let schema = utils::get_embedded_schema_v1(module)
+            .ok()
+            .and_then(|schema| contract_schemas(&schema).remove(&spec.contract));
+        let instance = LocalInstance {
+            artifact: Arc::new(artifact),
+            module_reference,
+            contract_name: spec.contract,
+            state: read_state_from_file(&base_dir.join(&spec.state_bin))?,
+            balance: spec.balance,
+            owner: spec.owner,
+            schema,
+            out_bin: spec.out_bin.map(|out_bin| base_dir.join(out_bin)),
+        };
+        ensure!(
+            instances.insert(spec.address, instance).is_none(),
+            "Contract {} is listed more than once in the contracts file.",
+            spec.address
+        );
+    }
+    Ok(instances)
+}
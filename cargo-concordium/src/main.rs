@@ -1,6 +1,13 @@
 use crate::{
     build::*,
+    build_manifest::{SchemaFile, SchemaFormat},
+    chain_state::{ChainState, ExchangeRate},
     context::{InitContextOpt, ReceiveContextOpt, ReceiveContextV1Opt},
+    contract_schema::{contract_schemas, ContractSchema},
+    local_chain::LocalChain,
+    profile::{ProbeTransport, Profile},
+    report::{ChainTraceEntry, OutputFormat, RunReport, ValueSchemas},
+    test_report::{self, ReportTarget, TestCase, TestSuite},
 };
 use ansi_term::Color;
 use anyhow::{bail, ensure, Context};
@@ -9,7 +16,8 @@ use concordium_base::{
     contracts_common::{
         self, from_bytes,
         schema::{Type, VersionedModuleSchema},
-        to_bytes, Amount, OwnedParameter, OwnedReceiveName, ReceiveName, WasmVersion,
+        to_bytes, Amount, OwnedEntrypointName, OwnedParameter, OwnedReceiveName, ReceiveName,
+        WasmVersion,
     },
     hashes,
     smart_contracts::WasmModule,
@@ -20,6 +28,7 @@ use concordium_smart_contract_engine::{
     InterpreterEnergy,
 };
 use concordium_wasm::{
+    artifact::{Artifact, CompiledFunction},
     output::{write_custom_section, Output},
     parse::parse_skeleton,
     validate::ValidationConfig,
@@ -31,10 +40,32 @@ use std::{
     fs::{self, File},
     io::Read,
     path::{Path, PathBuf},
+    str::FromStr,
 };
 use structopt::StructOpt;
 mod build;
+mod build_manifest;
+mod chain_state;
+mod codec;
+mod codegen;
 mod context;
+mod contract_schema;
+mod cost;
+mod coverage;
+mod diff;
+mod docs;
+mod inspect;
+mod instrument;
+mod json_schema;
+mod local_chain;
+mod profile;
+mod report;
+mod scenario;
+mod schema_compat;
+mod schema_example;
+mod schema_validate;
+mod test_report;
+mod watch;
 
 /// Versioned schemas always start with two fully set bytes.
 /// This is used to determine whether we are looking at a versioned or
@@ -77,10 +108,10 @@ This command also builds a deployable Wasm module for integration testing, and i
                  To limit the targets tested, use `--test` one or more times."
     )]
     Test {
-        #[structopt(name = "seed", long = "seed", help = "Seed for randomized testing")]
-        seed: Option<u64>,
         #[structopt(flatten)]
         build_options: BuildOptions,
+        #[structopt(flatten)]
+        test_options: TestOptions,
         #[structopt(
             name = "only-unit-tests",
             long = "only-unit-tests",
@@ -102,6 +133,12 @@ This command also builds a deployable Wasm module for integration testing, and i
         )]
         test_targets: Vec<String>,
     },
+    #[structopt(
+        name = "cost",
+        about = "Estimate the NRG and CCD cost of deploying a module, and of the init and update \
+                 transactions of a scenario."
+    )]
+    Cost(CostOptions),
     #[structopt(
         name = "init",
         about = "Create a new Concordium smart contract project. This command requires \
@@ -144,6 +181,15 @@ A schema has to be provided either as part of a smart contract module or with th
                     `./my/path/`)."
         )]
         out: PathBuf,
+        #[structopt(
+            name = "json-schema",
+            long = "json-schema",
+            help = "Represent each parameter, return value, error, event and state type by a JSON \
+                    Schema (draft 2020-12) document describing its JSON representation, instead \
+                    of by the base64 encoding of its schema. The documents can be used for \
+                    validating input and generating forms."
+        )]
+        json_schema: bool,
         #[structopt(
             name = "schema",
             long = "schema",
@@ -270,6 +316,241 @@ A schema has to be provided either as part of a smart contract module or with th
         )]
         module_path: Option<PathBuf>,
     },
+    #[structopt(
+        name = "schema-example",
+        about = "Generate example parameters in JSON for the init function and the entrypoints of \
+                 the contracts, which can be supplied with `--parameter-json`. A schema has to be \
+                 provided either as part of a smart contract module or with the schema flag."
+    )]
+    SchemaExample {
+        #[structopt(flatten)]
+        schema: SchemaSource,
+        #[structopt(
+            name = "contract",
+            long = "contract",
+            short = "c",
+            help = "Only generate examples for the given contract."
+        )]
+        contract_name: Option<String>,
+        #[structopt(
+            name = "seed",
+            long = "seed",
+            help = "Generate random examples from the given seed. Without a seed, the examples \
+                    are deterministic."
+        )]
+        seed: Option<u64>,
+        #[structopt(
+            name = "count",
+            long = "count",
+            requires = "seed",
+            default_value = "1",
+            help = "The number of random examples to generate for each function."
+        )]
+        count: usize,
+        #[structopt(
+            name = "out",
+            long = "out",
+            short = "o",
+            default_value = "-",
+            help = "Path to write the examples to, or `-` to print them to stdout."
+        )]
+        out: PathBuf,
+    },
+    #[structopt(
+        name = "schema-validate",
+        about = "Check values in JSON, such as parameters supplied with `--parameter-json`, \
+                 against a type in the schema without running the contract. Every mismatch is \
+                 reported with its path in the JSON. The command fails if any of the files does \
+                 not match. A schema has to be provided either as part of a smart contract module \
+                 or with the schema flag."
+    )]
+    SchemaValidate {
+        #[structopt(flatten)]
+        schema: SchemaSource,
+        #[structopt(
+            name = "contract",
+            long = "contract",
+            short = "c",
+            help = "Name of the contract the values belong to."
+        )]
+        contract_name: String,
+        #[structopt(
+            name = "entrypoint",
+            long = "entrypoint",
+            short = "f",
+            help = "Name of the entrypoint the values belong to. If not supplied, the values \
+                    belong to the init function."
+        )]
+        entrypoint: Option<String>,
+        #[structopt(
+            name = "kind",
+            long = "kind",
+            short = "k",
+            help = "The kind of the values.",
+            possible_values = &["parameter", "return-value", "error", "event", "state"],
+            default_value = "parameter"
+        )]
+        kind: codec::ValueKind,
+        #[structopt(
+            name = "input",
+            long = "input",
+            short = "i",
+            required = true,
+            help = "Path to a file with a value in JSON. Can be supplied multiple times."
+        )]
+        inputs: Vec<PathBuf>,
+    },
+    #[structopt(
+        name = "schema-compat",
+        about = "Check whether a new schema is compatible with an old one, e.g., before upgrading \
+                 a contract. Removed contracts and entrypoints, and changed parameter, return \
+                 value, error, event and state types are reported, together with whether they \
+                 break existing users of the contracts. The command fails if any change is \
+                 breaking. Each schema has to be provided either as part of a smart contract \
+                 module or as a schema file."
+    )]
+    SchemaCompat {
+        #[structopt(
+            name = "old-schema",
+            long = "old-schema",
+            conflicts_with = "old-module",
+            required_unless = "old-module",
+            help = "Path and filename to a file with the old schema (expected input: \
+                    `./my/path/schema.bin`)."
+        )]
+        old_schema_path: Option<PathBuf>,
+        #[structopt(
+            name = "old-module",
+            long = "old-module",
+            conflicts_with = "old-schema",
+            required_unless = "old-schema",
+            help = "Path and filename to a file with the old smart contract module (expected \
+                    input: `./my/path/module.wasm.v1`)."
+        )]
+        old_module_path: Option<PathBuf>,
+        #[structopt(
+            name = "new-schema",
+            long = "new-schema",
+            conflicts_with = "new-module",
+            required_unless = "new-module",
+            help = "Path and filename to a file with the new schema (expected input: \
+                    `./my/path/schema.bin`)."
+        )]
+        new_schema_path: Option<PathBuf>,
+        #[structopt(
+            name = "new-module",
+            long = "new-module",
+            conflicts_with = "new-schema",
+            required_unless = "new-schema",
+            help = "Path and filename to a file with the new smart contract module (expected \
+                    input: `./my/path/module.wasm.v1`)."
+        )]
+        new_module_path: Option<PathBuf>,
+        #[structopt(
+            name = "wasm-version",
+            long = "wasm-version",
+            short = "v",
+            help = "If the supplied schemas or modules are the unversioned ones this flag should \
+                    be used to supply the version explicitly. Unversioned schemas and modules \
+                    were produced by older versions of `concordium-std` and `cargo-concordium`."
+        )]
+        wasm_version: Option<WasmVersion>,
+        #[structopt(
+            name = "output-format",
+            long = "output-format",
+            help = "The format to output the changes in. With 'json' a single JSON document is \
+                    printed to stdout.",
+            possible_values = &["text", "json"],
+            default_value = "text"
+        )]
+        output_format: OutputFormat,
+    },
+    #[structopt(
+        name = "encode",
+        about = "Serialize a parameter, return value, error, event or state given in JSON, using \
+                 the schema of the contract. A schema has to be provided either as part of a \
+                 smart contract module or with the schema flag."
+    )]
+    Encode {
+        #[structopt(flatten)]
+        value: ValueOptions,
+        #[structopt(
+            name = "input",
+            long = "input",
+            short = "i",
+            default_value = "-",
+            help = "Path to a file with the value in JSON, or `-` to read it from stdin."
+        )]
+        input: PathBuf,
+        #[structopt(
+            name = "out",
+            long = "out",
+            short = "o",
+            default_value = "-",
+            help = "Path to write the serialized value to, or `-` to print it to stdout."
+        )]
+        out: PathBuf,
+    },
+    #[structopt(
+        name = "decode",
+        about = "Deserialize a parameter, return value, error, event or state to JSON, using the \
+                 schema of the contract. A schema has to be provided either as part of a smart \
+                 contract module or with the schema flag."
+    )]
+    Decode {
+        #[structopt(flatten)]
+        value: ValueOptions,
+        #[structopt(
+            name = "input",
+            long = "input",
+            short = "i",
+            default_value = "-",
+            help = "Path to a file with the serialized value, or `-` to read it from stdin."
+        )]
+        input: PathBuf,
+        #[structopt(
+            name = "out",
+            long = "out",
+            short = "o",
+            default_value = "-",
+            help = "Path to write the value in JSON to, or `-` to print it to stdout."
+        )]
+        out: PathBuf,
+    },
+    #[structopt(
+        name = "docs",
+        about = "Generate documentation of the contracts in a schema as Markdown or HTML: the \
+                 init function and entrypoints with their parameter, return value and error \
+                 types, the event and state types, and the build information embedded in the \
+                 module if present. A schema has to be provided either as part of a smart \
+                 contract module or with the schema flag."
+    )]
+    Docs {
+        #[structopt(flatten)]
+        schema: SchemaSource,
+        #[structopt(
+            name = "format",
+            long = "format",
+            help = "The format of the documentation.",
+            possible_values = &["markdown", "html"],
+            default_value = "markdown"
+        )]
+        format: docs::DocsFormat,
+        #[structopt(
+            name = "out",
+            long = "out",
+            short = "o",
+            default_value = "-",
+            help = "Path to write the documentation to, or `-` to print it to stdout."
+        )]
+        out: PathBuf,
+    },
+    #[structopt(
+        name = "codegen",
+        about = "Generate client code from a schema. A schema has to be provided either as part \
+                 of a smart contract module or with the schema flag."
+    )]
+    Codegen(CodegenCommand),
     #[structopt(
         name = "build",
         about = "Build a deployment ready smart-contract module."
@@ -278,6 +559,62 @@ A schema has to be provided either as part of a smart contract module or with th
         #[structopt(flatten)]
         build_options: BuildOptions,
     },
+    #[structopt(
+        name = "inspect",
+        about = "Summarize a module: its version, reference and size, the contracts and their \
+                 entrypoints, the host functions it imports, its custom sections, and whether it \
+                 is valid as a V0 and as a V1 module."
+    )]
+    Inspect {
+        #[structopt(
+            name = "module",
+            long = "module",
+            help = "Path to the module, as produced by `cargo concordium build`."
+        )]
+        module: PathBuf,
+        #[structopt(
+            name = "output-format",
+            long = "output-format",
+            help = "The format to output the summary in. With 'json' a single JSON document is \
+                    printed to stdout.",
+            possible_values = &["text", "json"],
+            default_value = "text"
+        )]
+        output_format: OutputFormat,
+    },
+    #[structopt(
+        name = "diff",
+        about = "Compare two modules: their contracts and entrypoints, the schemas of the \
+                 entrypoints, the host functions they import, their sizes and their embedded \
+                 build information."
+    )]
+    Diff {
+        #[structopt(name = "old", help = "Path to the old module.")]
+        old: PathBuf,
+        #[structopt(name = "new", help = "Path to the new module.")]
+        new: PathBuf,
+        #[structopt(
+            name = "output-format",
+            long = "output-format",
+            help = "The format to output the differences in. With 'json' a single JSON document \
+                    is printed to stdout.",
+            possible_values = &["text", "json"],
+            default_value = "text"
+        )]
+        output_format: OutputFormat,
+    },
+    #[structopt(
+        name = "module-ref",
+        about = "Print the reference of a module, which identifies the module on the chain."
+    )]
+    ModuleRef {
+        #[structopt(
+            name = "module",
+            long = "module",
+            help = "Path to the module, as produced by `cargo concordium build`."
+        )]
+        module: PathBuf,
+    },
     #[structopt(
         name = "print-build-info",
         about = "Print any embedded build information in a module."
@@ -365,6 +702,154 @@ struct EditOptions {
     verify: bool,
 }
 
+#[derive(Debug, StructOpt)]
+enum CodegenCommand {
+    #[structopt(
+        name = "typescript",
+        about = "Generate TypeScript types for the parameters, return values, errors and events \
+                 of each contract, together with functions serializing and deserializing them \
+                 using `@concordium/web-sdk`. Only schemas of V1 modules are supported."
+    )]
+    Typescript {
+        #[structopt(flatten)]
+        schema: SchemaSource,
+        #[structopt(
+            name = "out",
+            long = "out",
+            short = "o",
+            default_value = ".",
+            help = "Writes the bindings to files named after the smart contract names at the \
+                    specified location (expected input: `./my/path/`)."
+        )]
+        out: PathBuf,
+    },
+    #[structopt(
+        name = "rust",
+        about = "Generate a Rust module for each contract, with types for the parameters, return \
+                 values, errors, events and state of the contract. The types implement `Serial` \
+                 and `Deserial` from `concordium_base::contracts_common`, matching the \
+                 serialization described by the schema."
+    )]
+    Rust {
+        #[structopt(flatten)]
+        schema: SchemaSource,
+        #[structopt(
+            name = "out",
+            long = "out",
+            short = "o",
+            default_value = ".",
+            help = "Writes the modules to files named after the smart contract names at the \
+                    specified location (expected input: `./my/path/`)."
+        )]
+        out: PathBuf,
+    },
+}
+
+// A module schema, read either from a smart contract module or from a schema
+// file.
+//
+// This is *not* a doc comment on purpose, as that has the effect of overriding
+// the `about` message in the help menu for the command.
+// The issue is known (https://github.com/TeXitoi/structopt/issues/391) but won't
+// be fixed in `structopt` as it is in maintenance mode and is now integrated
+// in `clap` v3+. Once we migrate to `clap` v3+, this can become a doc comment.
+#[derive(Debug, StructOpt)]
+struct SchemaSource {
+    #[structopt(
+        name = "schema",
+        long = "schema",
+        short = "s",
+        conflicts_with = "module",
+        required_unless = "module",
+        help = "Path and filename to a file with a schema (expected input: \
+                `./my/path/schema.bin`)."
+    )]
+    schema_path: Option<PathBuf>,
+    #[structopt(
+        name = "wasm-version",
+        long = "wasm-version",
+        short = "v",
+        help = "If the supplied schema or module is the unversioned one this flag should be used \
+                to supply the version explicitly. Unversioned schemas and modules were produced \
+                by older versions of `concordium-std` and `cargo-concordium`."
+    )]
+    wasm_version: Option<WasmVersion>,
+    #[structopt(
+        name = "module",
+        long = "module",
+        short = "m",
+        conflicts_with = "schema",
+        required_unless = "schema",
+        help = "Path and filename to a file with a smart contract module (expected input: \
+                `./my/path/module.wasm.v1`)."
+    )]
+    module_path: Option<PathBuf>,
+}
+
+impl SchemaSource {
+    fn get_schema(self) -> anyhow::Result<VersionedModuleSchema> {
+        get_schema(self.module_path, self.schema_path, self.wasm_version)
+            .context("Could not get schema.")
+    }
+}
+
+// The type of the value to encode or decode.
+//
+// This is *not* a doc comment on purpose, as that has the effect of overriding
+// the `about` message in the help menu for the command.
+// The issue is known (https://github.com/TeXitoi/structopt/issues/391) but won't
+// be fixed in `structopt` as it is in maintenance mode and is now integrated
+// in `clap` v3+. Once we migrate to `clap` v3+, this can become a doc comment.
+#[derive(Debug, StructOpt)]
+struct ValueOptions {
+    #[structopt(flatten)]
+    schema: SchemaSource,
+    #[structopt(
+        name = "contract",
+        long = "contract",
+        short = "c",
+        help = "Name of the contract the value belongs to."
+    )]
+    contract_name: String,
+    #[structopt(
+        name = "entrypoint",
+        long = "entrypoint",
+        short = "f",
+        help = "Name of the entrypoint the parameter, return value or error belongs to. If not \
+                supplied, the value belongs to the init function."
+    )]
+    entrypoint: Option<String>,
+    #[structopt(
+        name = "kind",
+        long = "kind",
+        short = "k",
+        help = "The kind of value.",
+        possible_values = &["parameter", "return-value", "error", "event", "state"]
+    )]
+    kind: codec::ValueKind,
+    #[structopt(
+        name = "encoding",
+        long = "encoding",
+        help = "The encoding of the binary value.",
+        possible_values = &["hex", "base64", "binary"],
+        default_value = "hex"
+    )]
+    encoding: codec::Encoding,
+}
+
+impl ValueOptions {
+    /// Get the type of the value from the schema.
+    fn value_type(self) -> anyhow::Result<Type> {
+        let schema = self.schema.get_schema()?;
+        codec::value_type(
+            &schema,
+            &self.contract_name,
+            self.entrypoint.as_deref(),
+            self.kind,
+        )
+    }
+}
+
 // The build options used in the build and test command.
 //
 // This is *not* a doc comment on purpose, as that has the effect of overriding
@@ -483,6 +968,39 @@ struct BuildOptions {
         help = "Skip step using wasm-opt to optimize the resulting Wasm module."
     )]
     skip_wasm_opt: bool,
+    #[structopt(
+        name = "manifest-out",
+        long = "manifest-out",
+        help = "Write a JSON file describing the artifacts of the build to the specified \
+                location: the module with its reference, size and version, the contracts and \
+                their entrypoints, the schema files written and the embedded build information."
+    )]
+    manifest_out: Option<PathBuf>,
+    #[structopt(
+        name = "workspace",
+        long = "workspace",
+        help = "Build all the packages of the workspace that are smart contracts, i.e., that \
+                depend on `concordium-std` and have a `cdylib` library. When more than one \
+                package is built, the files given by the output options are written in \
+                directories named after the packages."
+    )]
+    workspace: bool,
+    #[structopt(
+        name = "package",
+        long = "package",
+        number_of_values = 1,
+        conflicts_with = "workspace",
+        help = "Build the given package of the workspace (can be provided multiple times)."
+    )]
+    packages: Vec<String>,
+    #[structopt(
+        name = "watch",
+        long = "watch",
+        conflicts_with = "verifiable",
+        help = "Watch the files of the package, and run again whenever they change. Files ignored \
+                by Git are not watched."
+    )]
+    watch: bool,
     #[structopt(
         raw = true,
         help = "Extra arguments passed to `cargo build` when building Wasm module."
@@ -491,6 +1009,34 @@ struct BuildOptions {
 }
 
 impl BuildOptions {
+    /// Whether packages of the workspace are selected with `--workspace` or
+    /// `--package`.
+    fn selects_packages(&self) -> bool {
+        self.workspace || !self.packages.is_empty()
+    }
+
+    /// The options for building one of the selected packages. If `out_dir` is
+    /// given, the output files are placed in a directory of that name.
+    fn for_package(&self, package: &cargo_metadata::Package, out_dir: Option<&str>) -> Self {
+        let mut options = self.clone();
+        options.workspace = false;
+        options.packages = Vec::new();
+        options.cargo_args = with_manifest_path(&self.cargo_args, package);
+        if let Some(out_dir) = out_dir {
+            options.out = options.out.map(|path| in_out_dir(path, out_dir));
+            options.schema_out = options.schema_out.map(|path| in_out_dir(path, out_dir));
+            options.schema_json_out = options.schema_json_out.map(|dir| dir.join(out_dir));
+            options.schema_template_out = options
+                .schema_template_out
+                .map(|path| in_out_dir(path, out_dir));
+            options.schema_base64_out = options
+                .schema_base64_out
+                .map(|path| in_out_dir(path, out_dir));
+            options.manifest_out = options.manifest_out.map(|path| in_out_dir(path, out_dir));
+        }
+        options
+    }
+
     /// Determine the [`SchemaBuildOptions`] based on the input from the user.
     fn schema_build_options(&self) -> SchemaBuildOptions {
         if !self.no_schema_embed {
@@ -573,6 +1119,34 @@ struct Runner {
         default_value = "1000000"
     )]
     energy: InterpreterEnergy,
+    #[structopt(
+        name = "output-format",
+        long = "output-format",
+        help = "The format to output the result in. With 'json' a single JSON document \
+                describing the result is printed to stdout.",
+        possible_values = &["text", "json"],
+        default_value = "text"
+    )]
+    output_format: OutputFormat,
+    #[structopt(
+        name = "energy-profile",
+        long = "energy-profile",
+        help = "Profile the interpreter energy spent by the functions of the contract, and \
+                write the profile to this file in the folded stack format used by flamegraph \
+                tools. The call is run a second time with an instrumented module for this. This \
+                only applies to V1 contracts."
+    )]
+    energy_profile: Option<PathBuf>,
+    #[structopt(
+        name = "energy-profile-symbols",
+        long = "energy-profile-symbols",
+        requires = "energy-profile",
+        help = "Path to a Wasm module with a name section to read the names of the functions \
+                from, for example the module built by cargo in \
+                `target/concordium/wasm32-unknown-unknown/release` when building with \
+                `--skip-wasm-opt`. Defaults to the names in the module itself."
+    )]
+    energy_profile_symbols: Option<PathBuf>,
 }
 
 #[derive(Debug, StructOpt)]
@@ -652,6 +1226,27 @@ enum RunCommand {
             help = "Path to the receive context file."
         )]
         context: Option<PathBuf>,
+        #[structopt(
+            name = "contracts",
+            long = "contracts",
+            help = "Path to a JSON file listing contract instances that the invoked contract can \
+                    call. Each instance is given by its address, module, contract name, state \
+                    and balance. Calls to these instances are executed and their results are \
+                    passed back to the caller. This requires a complete receive context, \
+                    except for the sender, owner, sender policies and entrypoint. Only \
+                    supported for V1 contracts."
+        )]
+        contracts: Option<PathBuf>,
+        #[structopt(
+            name = "chain-state",
+            long = "chain-state",
+            help = "Path to a JSON file describing the accounts, contracts and exchange rates \
+                    that the invoked contract can query. Queries are answered from this file, \
+                    and accounts and contracts not listed in it are treated as non-existent. \
+                    This requires a complete receive context, except for the sender, owner, \
+                    sender policies and entrypoint. Only supported for V1 contracts."
+        )]
+        chain_state: Option<PathBuf>,
         #[structopt(
             name = "display-state",
             long = "display-state",
@@ -667,44 +1262,335 @@ enum RunCommand {
         #[structopt(flatten)]
         runner: Runner,
     },
+    #[structopt(
+        name = "scenario",
+        about = "Run a sequence of init and update calls against a single contract instance. \
+                 Only supported for V1 contracts."
+    )]
+    Scenario(ScenarioOptions),
 }
 
-const WARNING_STYLE: ansi_term::Color = ansi_term::Color::Yellow;
-
-pub fn main() -> anyhow::Result<()> {
-    #[cfg(target_os = "windows")]
-    {
-        let _ = ansi_term::enable_ansi_support();
-    }
-    let cmd = {
-        let app = CargoCommand::clap()
-            .setting(AppSettings::ArgRequiredElseHelp)
-            .global_setting(AppSettings::TrailingVarArg)
-            .global_setting(AppSettings::ColoredHelp);
-        let matches = app.get_matches();
-        let CargoCommand::Concordium(cmd) = CargoCommand::from_clap(&matches);
-        cmd
-    };
-
-    match cmd {
-        Command::Run(run_cmd) => {
-            let runner = match *run_cmd {
-                RunCommand::Init { ref runner, .. } => runner,
-                RunCommand::Receive { ref runner, .. } => runner,
-            };
-            let versioned_module = WasmModule::from_file(&runner.module).with_context(|| {
-                format!("Could not read module file {}", runner.module.display())
-            })?;
-            let module = versioned_module.source.as_ref();
-            match versioned_module.version {
-                WasmVersion::V0 => handle_run_v0(*run_cmd, module)?,
-                WasmVersion::V1 => handle_run_v1(*run_cmd, module)?,
-            }
-        }
-        Command::Test {
-            seed,
-            build_options,
-            test_threads,
+// Options for running a scenario.
+//
+// This is *not* a doc comment on purpose, see the comment on `VerifyOptions`.
+#[derive(Debug, StructOpt)]
+struct ScenarioOptions {
+    #[structopt(name = "module", long = "module", help = "Binary module source.")]
+    module: PathBuf,
+    #[structopt(
+        name = "scenario",
+        long = "scenario",
+        short = "s",
+        help = "Path to the scenario file describing the steps to run. The file is parsed as \
+                TOML if it has the `toml` extension, and as JSON otherwise."
+    )]
+    scenario: PathBuf,
+    #[structopt(
+        name = "schema",
+        long = "schema",
+        help = "Path to a file with a schema for parsing parameters and displaying return \
+                values, errors and events. If not given, the schema embedded in the module is \
+                used, if any."
+    )]
+    schema_path: Option<PathBuf>,
+    #[structopt(
+        name = "state-bin",
+        long = "state-bin",
+        help = "File with the initial state of the contract instance in binary. If not given, \
+                the first step of the scenario must be an init step."
+    )]
+    state_bin_path: Option<PathBuf>,
+    #[structopt(
+        name = "balance",
+        long = "balance",
+        help = "Balance in microCCD of the contract instance given by --state-bin. Init steps \
+                set the balance to the amount they are invoked with, and update steps add the \
+                amount they are invoked with."
+    )]
+    balance: Option<u64>,
+    #[structopt(
+        name = "out-bin",
+        long = "out-bin",
+        help = "Where to write the contract state at the end of the scenario in binary format."
+    )]
+    out_bin: Option<PathBuf>,
+    #[structopt(
+        name = "display-state",
+        long = "display-state",
+        help = "Pretty print the contract state at the end of the scenario."
+    )]
+    should_display_state: bool,
+    #[structopt(
+        name = "emit-debug",
+        long = "emit-debug",
+        help = "Emit debug information after each step."
+    )]
+    emit_debug: bool,
+}
+
+#[derive(Debug, Clone, StructOpt)]
+struct TestOptions {
+    #[structopt(name = "seed", long = "seed", help = "Seed for randomized testing")]
+    seed: Option<u64>,
+    #[structopt(
+        name = "runs",
+        long = "runs",
+        help = "The number of seeds to run each unit test using randomness with. The seeds are \
+                derived from the seed of the first run. The random input of a failing test is \
+                shrunk to a smaller input that still fails."
+    )]
+    runs: Option<u32>,
+    #[structopt(
+        name = "replay",
+        long = "replay",
+        conflicts_with_all = &["seed", "runs"],
+        help = "Run only the given unit test, with the seed of a failure reported earlier, given \
+                as `<test>=<seed>`."
+    )]
+    replay: Option<TestReplay>,
+    #[structopt(
+        name = "filter",
+        help = "Run only the tests whose names contain one of the filters. The filters also \
+                apply to the integration tests."
+    )]
+    filters: Vec<String>,
+    #[structopt(
+        name = "exact",
+        long = "exact",
+        help = "Match the filters and the skip patterns against the whole names of the tests."
+    )]
+    exact: bool,
+    #[structopt(
+        name = "skip",
+        long = "skip",
+        number_of_values = 1,
+        help = "Skip the tests whose names contain the pattern (can be provided multiple times)."
+    )]
+    skip: Vec<String>,
+    #[structopt(
+        name = "list",
+        long = "list",
+        help = "List the tests that would be run, without running them."
+    )]
+    list: bool,
+    #[structopt(
+        name = "report",
+        long = "report",
+        number_of_values = 1,
+        help = "Write the results of the tests to a file, given as `junit=<path>` for the JUnit \
+                XML format or `json=<path>` for JSON (can be provided multiple times)."
+    )]
+    reports: Vec<ReportTarget>,
+    #[structopt(
+        name = "test-energy-limit",
+        long = "test-energy-limit",
+        help = "The interpreter energy each unit test may use. Tests that use more fail."
+    )]
+    test_energy_limit: Option<u64>,
+    #[structopt(
+        name = "test-timeout",
+        long = "test-timeout",
+        help = "The number of seconds each unit test may run for. Tests that run longer fail."
+    )]
+    test_timeout: Option<u64>,
+    #[structopt(
+        name = "energy-profile",
+        long = "energy-profile",
+        help = "Profile the interpreter energy spent by the functions called in each unit test. \
+                The profiles are written in the folded stack format used by flamegraph tools, to \
+                files named after the tests in the given directory."
+    )]
+    energy_profile: Option<PathBuf>,
+    #[structopt(
+        name = "coverage",
+        long = "coverage",
+        conflicts_with = "energy-profile",
+        help = "Record the code executed by the unit tests, and write the coverage of the source \
+                files of the package to the given file in the lcov format. The unit tests are \
+                built with debug information for mapping the code to lines of source code."
+    )]
+    coverage: Option<PathBuf>,
+}
+
+impl TestOptions {
+    /// Whether the test with the given name is selected by the filters and
+    /// skip patterns, following the rules of `cargo test`.
+    fn selects(&self, name: &str) -> bool {
+        if let Some(replay) = &self.replay {
+            return name == replay.test;
+        }
+        let matches = |pattern: &String| {
+            if self.exact {
+                name == pattern
+            } else {
+                name.contains(pattern.as_str())
+            }
+        };
+        (self.filters.is_empty() || self.filters.iter().any(matches))
+            && !self.skip.iter().any(matches)
+    }
+
+    /// The options for testing one of the selected packages. If `out_dir` is
+    /// given, the reports are placed in a directory of that name.
+    fn for_package(&self, out_dir: Option<&str>) -> Self {
+        let mut options = self.clone();
+        if let Some(out_dir) = out_dir {
+            for report in options.reports.iter_mut() {
+                report.path = in_out_dir(std::mem::take(&mut report.path), out_dir);
+            }
+            options.energy_profile = options.energy_profile.map(|dir| dir.join(out_dir));
+            options.coverage = options.coverage.map(|path| in_out_dir(path, out_dir));
+        }
+        options
+    }
+
+    /// The arguments selecting the same tests in the test harness used by
+    /// `cargo test`.
+    fn harness_args(&self) -> Vec<String> {
+        let mut args = self.filters.clone();
+        if self.exact {
+            args.push("--exact".to_string());
+        }
+        for pattern in self.skip.iter() {
+            args.push("--skip".to_string());
+            args.push(pattern.clone());
+        }
+        if self.list {
+            args.push("--list".to_string());
+        }
+        args
+    }
+}
+
+/// A unit test to run with a given seed, given as `<test>=<seed>`.
+#[derive(Debug, Clone)]
+struct TestReplay {
+    test: String,
+    seed: u64,
+}
+
+impl FromStr for TestReplay {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (test, seed) = s
+            .rsplit_once('=')
+            .context("Expected a test to replay of the form '<test>=<seed>'.")?;
+        let seed = seed
+            .parse()
+            .with_context(|| format!("Invalid seed '{}'.", seed))?;
+        Ok(Self {
+            test: test.to_string(),
+            seed,
+        })
+    }
+}
+
+#[derive(Debug, StructOpt)]
+struct CostOptions {
+    #[structopt(
+        name = "module",
+        long = "module",
+        short = "m",
+        help = "The built module to deploy, as produced by `cargo concordium build`."
+    )]
+    module: PathBuf,
+    #[structopt(
+        name = "scenario",
+        long = "scenario",
+        short = "s",
+        help = "Path to a scenario file with the calls to estimate the cost of. The scenario is \
+                run as with `cargo concordium run scenario`, and the interpreter energy spent by \
+                each step is converted to NRG. Only supported for V1 contracts."
+    )]
+    scenario: Option<PathBuf>,
+    #[structopt(
+        name = "schema",
+        long = "schema",
+        requires = "scenario",
+        help = "Path to a file with a schema for parsing the parameters of the scenario. If not \
+                given, the schema embedded in the module is used, if any."
+    )]
+    schema_path: Option<PathBuf>,
+    #[structopt(
+        name = "state-bin",
+        long = "state-bin",
+        requires = "scenario",
+        help = "File with the initial state of the contract instance of the scenario in binary."
+    )]
+    state_bin_path: Option<PathBuf>,
+    #[structopt(
+        name = "balance",
+        long = "balance",
+        requires = "scenario",
+        help = "Balance in microCCD of the contract instance given by --state-bin."
+    )]
+    balance: Option<u64>,
+    #[structopt(
+        name = "signatures",
+        long = "signatures",
+        default_value = "1",
+        help = "The number of signatures on each transaction."
+    )]
+    signatures: u32,
+    #[structopt(
+        name = "euro-per-energy",
+        long = "euro-per-energy",
+        help = "The exchange rate from NRG to euro as a fraction, e.g. `1/50000`. Together with \
+                --micro-ccd-per-euro this is used for estimating the cost in CCD."
+    )]
+    euro_per_energy: Option<ExchangeRate>,
+    #[structopt(
+        name = "micro-ccd-per-euro",
+        long = "micro-ccd-per-euro",
+        help = "The exchange rate from euro to microCCD as a fraction, e.g. `100000000/1`. \
+                Together with --euro-per-energy this is used for estimating the cost in CCD."
+    )]
+    micro_ccd_per_euro: Option<ExchangeRate>,
+}
+
+const WARNING_STYLE: ansi_term::Color = ansi_term::Color::Yellow;
+
+pub fn main() -> anyhow::Result<()> {
+    #[cfg(target_os = "windows")]
+    {
+        let _ = ansi_term::enable_ansi_support();
+    }
+    let cmd = {
+        let app = CargoCommand::clap()
+            .setting(AppSettings::ArgRequiredElseHelp)
+            .global_setting(AppSettings::TrailingVarArg)
+            .global_setting(AppSettings::ColoredHelp);
+        let matches = app.get_matches();
+        let CargoCommand::Concordium(cmd) = CargoCommand::from_clap(&matches);
+        cmd
+    };
+
+    match cmd {
+        Command::Run(run_cmd) => {
+            let module_path = match *run_cmd {
+                RunCommand::Init { ref runner, .. } => &runner.module,
+                RunCommand::Receive { ref runner, .. } => &runner.module,
+                RunCommand::Scenario(ref options) => &options.module,
+            };
+            let versioned_module = WasmModule::from_file(module_path)
+                .with_context(|| format!("Could not read module file {}", module_path.display()))?;
+            let module = versioned_module.source.as_ref();
+            match (versioned_module.version, *run_cmd) {
+                (WasmVersion::V0, RunCommand::Scenario(_)) => {
+                    bail!("Scenarios are only supported for V1 contracts.")
+                }
+                (WasmVersion::V1, RunCommand::Scenario(options)) => {
+                    scenario::run_scenario(options, module)?;
+                }
+                (WasmVersion::V0, run_cmd) => handle_run_v0(run_cmd, module)?,
+                (WasmVersion::V1, run_cmd) => handle_run_v1(run_cmd, module)?,
+            }
+        }
+        Command::Test {
+            build_options,
+            test_options,
+            test_threads,
             only_unit_tests,
             test_targets,
         } => {
@@ -713,30 +1599,43 @@ pub fn main() -> anyhow::Result<()> {
                     .num_threads(test_threads)
                     .build_global()?;
             }
-            let unit_test_success = build_and_run_wasm_test(
-                build_options.allow_debug,
-                &build_options.profile,
-                &build_options.cargo_args,
-                seed,
-                build_options.skip_wasm_opt,
-            )
-            .context("Could not build and run unit tests.")?;
-            if !only_unit_tests {
-                build_and_run_integration_tests(build_options, test_targets)
-                    .context("Could not build and run integration tests.")?;
-            }
-            if !unit_test_success {
-                anyhow::bail!("One or more unit tests failed.");
+            let test = || -> anyhow::Result<()> {
+                let Some(packages) = selected_packages(&build_options)? else {
+                    return handle_test(
+                        &build_options,
+                        &test_options,
+                        only_unit_tests,
+                        &test_targets,
+                    );
+                };
+                for_each_package(&build_options, &packages, "Testing", |options, out_dir| {
+                    handle_test(
+                        &options,
+                        &test_options.for_package(out_dir),
+                        only_unit_tests,
+                        &test_targets,
+                    )?;
+                    Ok(String::new())
+                })
+            };
+            if build_options.watch {
+                watch::watch_package(
+                    &build_options.cargo_args,
+                    build_options.selects_packages(),
+                    test,
+                )?;
+            } else {
+                test()?;
             }
-
-            eprintln!("{}", Color::Green.bold().paint("All tests passed"));
         }
+        Command::Cost(options) => cost::print_costs(options)?,
         Command::Init { path, tag } => {
             init_concordium_project(path, &tag)
                 .context("Could not create a new Concordium smart contract project.")?;
         }
         Command::SchemaJSON {
             out,
+            json_schema,
             module_path,
             schema_path,
             wasm_version,
@@ -744,7 +1643,13 @@ pub fn main() -> anyhow::Result<()> {
             let schema = get_schema(module_path, schema_path, wasm_version)
                 .context("Could not get schema.")?;
 
-            write_json_schema(&out, &schema).context("Could not write JSON schema files.")?
+            let format = if json_schema {
+                SchemaTypeFormat::JsonSchema
+            } else {
+                SchemaTypeFormat::Base64
+            };
+            write_json_schema(&out, &schema, format)
+                .context("Could not write JSON schema files.")?
         }
         Command::SchemaBase64 {
             out,
@@ -795,12 +1700,125 @@ pub fn main() -> anyhow::Result<()> {
                     .context("Could not write template schema files.")?;
             }
         }
+        Command::SchemaExample {
+            schema,
+            contract_name,
+            seed,
+            count,
+            out,
+        } => {
+            let examples = schema_example::schema_examples(
+                &schema.get_schema()?,
+                contract_name.as_deref(),
+                seed,
+                count,
+            )?;
+            let json = serde_json::to_string_pretty(&examples)
+                .context("Could not output the examples as JSON.")?;
+            codec::write_output(&out, format!("{}\n", json).as_bytes())?
+        }
+        Command::SchemaValidate {
+            schema,
+            contract_name,
+            entrypoint,
+            kind,
+            inputs,
+        } => {
+            let ty = codec::value_type(
+                &schema.get_schema()?,
+                &contract_name,
+                entrypoint.as_deref(),
+                kind,
+            )?;
+            schema_validate::validate_files(&ty, &inputs)?
+        }
+        Command::SchemaCompat {
+            old_schema_path,
+            old_module_path,
+            new_schema_path,
+            new_module_path,
+            wasm_version,
+            output_format,
+        } => {
+            let old = get_schema(old_module_path, old_schema_path, wasm_version)
+                .context("Could not get the old schema.")?;
+            let new = get_schema(new_module_path, new_schema_path, wasm_version)
+                .context("Could not get the new schema.")?;
+            schema_compat::check_compatibility(&old, &new, output_format)?
+        }
+        Command::Encode { value, input, out } => {
+            let encoding = value.encoding;
+            let ty = value.value_type()?;
+            codec::encode_value(&ty, &input, &out, encoding)?
+        }
+        Command::Decode { value, input, out } => {
+            let encoding = value.encoding;
+            let ty = value.value_type()?;
+            codec::decode_value(&ty, &input, &out, encoding)?
+        }
+        Command::Docs {
+            schema,
+            format,
+            out,
+        } => {
+            let build_info = schema
+                .module_path
+                .as_deref()
+                .and_then(|path| docs::module_build_info(path, schema.wasm_version));
+            let docs = docs::render_docs(&schema.get_schema()?, build_info.as_ref(), format);
+            codec::write_output(&out, docs.as_bytes())?
+        }
+        Command::Codegen(CodegenCommand::Typescript { schema, out }) => {
+            codegen::write_typescript(&out, &schema.get_schema()?)
+                .context("Could not write TypeScript bindings.")?
+        }
+        Command::Codegen(CodegenCommand::Rust { schema, out }) => {
+            codegen::write_rust(&out, &schema.get_schema()?)
+                .context("Could not write Rust bindings.")?
+        }
         Command::Build { build_options } => {
-            handle_build(build_options, true)?;
+            let build = || -> anyhow::Result<()> {
+                let Some(packages) = selected_packages(&build_options)? else {
+                    handle_build(build_options.clone(), true)?;
+                    return Ok(());
+                };
+                for_each_package(&build_options, &packages, "Building", |options, _| {
+                    let build_info = handle_build(options, true)?;
+                    Ok(format!(
+                        "{} ({}) with module reference {}",
+                        build_info.out_filename.display(),
+                        format_module_size(build_info.total_module_len),
+                        build_info.module_ref
+                    ))
+                })
+            };
+            if build_options.watch {
+                watch::watch_package(
+                    &build_options.cargo_args,
+                    build_options.selects_packages(),
+                    build,
+                )?;
+            } else {
+                build()?;
+            }
         }
         Command::EditBuildInfo { edit_options } => {
             handle_edit(edit_options)?;
         }
+        Command::Inspect {
+            module,
+            output_format,
+        } => inspect::inspect_module(&module, output_format)?,
+        Command::Diff {
+            old,
+            new,
+            output_format,
+        } => diff::diff_modules(&old, &new, output_format)?,
+        Command::ModuleRef { module } => {
+            let module = inspect::read_module(&module)?;
+            // The reference is printed to stdout so that it can be used by scripts.
+            println!("{}", module.get_module_ref());
+        }
         Command::PrintBuildInfo { source } => {
             handle_print_build_info(source)?;
         }
@@ -1014,6 +2032,125 @@ fn handle_print_build_info(source: PathBuf) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Place an output file in the directory `out_dir` next to it. The path `-`,
+/// used for printing to the console, is not changed.
+fn in_out_dir(path: PathBuf, out_dir: &str) -> PathBuf {
+    if path.as_path() == Path::new("-") {
+        return path;
+    }
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(file_name)) => parent.join(out_dir).join(file_name),
+        _ => path.join(out_dir),
+    }
+}
+
+/// Run `run` with the build options of each of the selected packages, and
+/// print a summary of the results. The output files of the packages are
+/// placed in directories named after them if there is more than one. A
+/// failure does not stop the remaining packages from running, but fails the
+/// whole run.
+fn for_each_package(
+    build_options: &BuildOptions,
+    packages: &[cargo_metadata::Package],
+    action: &str,
+    mut run: impl FnMut(BuildOptions, Option<&str>) -> anyhow::Result<String>,
+) -> anyhow::Result<()> {
+    let bold_style = ansi_term::Style::new().bold();
+    let mut results = Vec::new();
+    for package in packages {
+        eprintln!(
+            "\n{} package {}",
+            Color::Green.bold().paint(action),
+            bold_style.paint(&package.name)
+        );
+        let out_dir = (packages.len() > 1).then_some(package.name.as_str());
+        let result = run(build_options.for_package(package, out_dir), out_dir);
+        if let Err(err) = &result {
+            eprintln!("{} {:#}", Color::Red.bold().paint("Error:"), err);
+        }
+        results.push((package.name.as_str(), result));
+    }
+    eprintln!("\n{}", bold_style.paint("Summary of the packages:"));
+    let mut num_failed = 0;
+    for (name, result) in results {
+        match result {
+            Ok(summary) if summary.is_empty() => {
+                eprintln!("  - {} ... {}", name, Color::Green.bold().paint("ok"))
+            }
+            Ok(summary) => eprintln!(
+                "  - {} ... {}: {}",
+                name,
+                Color::Green.bold().paint("ok"),
+                summary
+            ),
+            Err(_) => {
+                num_failed += 1;
+                eprintln!("  - {} ... {}", name, Color::Red.bold().paint("FAILED"));
+            }
+        }
+    }
+    ensure!(
+        num_failed == 0,
+        "{} of {} packages failed.",
+        num_failed,
+        packages.len()
+    );
+    Ok(())
+}
+
+/// Build and run the unit tests and, unless `only_unit_tests` is set, the
+/// integration tests of the package.
+fn handle_test(
+    build_options: &BuildOptions,
+    test_options: &TestOptions,
+    only_unit_tests: bool,
+    test_targets: &[String],
+) -> anyhow::Result<()> {
+    let unit_test_results = build_and_run_wasm_test(build_options, test_options)
+        .context("Could not build and run unit tests.")?;
+    // No tests are run when listing them, so there is nothing to report.
+    let write_reports = !test_options.reports.is_empty() && !test_options.list;
+    let mut suites = vec![TestSuite {
+        name: "unit",
+        tests: unit_test_results,
+    }];
+    // Replaying a seed only concerns a unit test.
+    let run_integration_tests = !only_unit_tests && test_options.replay.is_none();
+    let integration_test_success = if run_integration_tests {
+        let (success, integration_test_results) = build_and_run_integration_tests(
+            build_options.clone(),
+            test_targets.to_vec(),
+            &test_options.harness_args(),
+            write_reports,
+        )
+        .context("Could not build and run integration tests.")?;
+        suites.push(TestSuite {
+            name: "integration",
+            tests: integration_test_results,
+        });
+        success
+    } else {
+        true
+    };
+    if write_reports {
+        test_report::write_reports(&test_options.reports, &suites)?;
+    }
+    ensure!(
+        integration_test_success,
+        Color::Red
+            .bold()
+            .paint("One or more integration tests failed.")
+    );
+    if suites[0].tests.iter().any(TestCase::failed) {
+        anyhow::bail!("One or more unit tests failed.");
+    }
+
+    if !test_options.list {
+        eprintln!("{}", Color::Green.bold().paint("All tests passed"));
+    }
+    Ok(())
+}
+
 /// Build the smart contract module using the provided options.
 ///
 /// This method is used by both the build and test command.
@@ -1037,35 +2174,15 @@ fn handle_build(options: BuildOptions, print_extra_info: bool) -> anyhow::Result
 
     let build_info =
         build_contract(options.clone(), &cargo_args).context("Could not build smart contract.")?;
+    // The schema files written, for the build manifest.
+    let mut schema_files = Vec::new();
     if let Some(module_schema) = &build_info.schema {
         let module_schema_bytes = to_bytes(module_schema);
         if print_extra_info {
-            match module_schema {
-                VersionedModuleSchema::V0(module_schema) => {
-                    eprintln!("\n   Module schema includes:");
-                    for (contract_name, contract_schema) in module_schema.contracts.iter() {
-                        print_contract_schema_v0(contract_name, contract_schema);
-                    }
-                }
-                VersionedModuleSchema::V1(module_schema) => {
-                    eprintln!("\n   Module schema includes:");
-                    for (contract_name, contract_schema) in module_schema.contracts.iter() {
-                        print_contract_schema_v1(contract_name, contract_schema);
-                    }
-                }
-                VersionedModuleSchema::V2(module_schema) => {
-                    eprintln!("\n   Module schema includes:");
-                    for (contract_name, contract_schema) in module_schema.contracts.iter() {
-                        print_contract_schema_v2(contract_name, contract_schema);
-                    }
-                }
-                VersionedModuleSchema::V3(module_schema) => {
-                    eprintln!("\n   Module schema includes:");
-                    for (contract_name, contract_schema) in module_schema.contracts.iter() {
-                        print_contract_schema_v3(contract_name, contract_schema);
-                    }
-                }
-            };
+            eprintln!("\n   Module schema includes:");
+            for (contract_name, contract_schema) in contract_schemas(module_schema).iter() {
+                print_contract_schema(contract_name, contract_schema);
+            }
             eprintln!(
                 "\n   Total size of the module schema is {} {}",
                 bold_style.paint(module_schema_bytes.len().to_string()),
@@ -1087,11 +2204,19 @@ fn handle_build(options: BuildOptions, print_extra_info: bool) -> anyhow::Result
                 fs::create_dir_all(out_dir)
                     .context("Unable to create directory for the resulting schema.")?;
             }
-            fs::write(schema_out, &module_schema_bytes).context("Could not write schema file.")?;
+            fs::write(&schema_out, &module_schema_bytes).context("Could not write schema file.")?;
+            schema_files.push(SchemaFile {
+                format: SchemaFormat::Binary,
+                path: schema_out,
+            });
         }
         if let Some(schema_json_out) = options.schema_json_out {
-            write_json_schema(&schema_json_out, module_schema)
+            write_json_schema(&schema_json_out, module_schema, SchemaTypeFormat::Base64)
                 .context("Could not write JSON schema files.")?;
+            schema_files.push(SchemaFile {
+                format: SchemaFormat::Json,
+                path: schema_json_out,
+            });
         }
         if let Some(schema_template_out) = options.schema_template_out {
             if schema_template_out.as_path() == Path::new("-") {
@@ -1105,8 +2230,12 @@ fn handle_build(options: BuildOptions, print_extra_info: bool) -> anyhow::Result
                     );
                 }
 
-                write_schema_template(Some(schema_template_out), module_schema)
+                write_schema_template(Some(schema_template_out.clone()), module_schema)
                     .context("Could not write template schema files.")?;
+                schema_files.push(SchemaFile {
+                    format: SchemaFormat::Template,
+                    path: schema_template_out,
+                });
             }
         }
         if let Some(schema_base64_out) = options.schema_base64_out {
@@ -1121,8 +2250,12 @@ fn handle_build(options: BuildOptions, print_extra_info: bool) -> anyhow::Result
                     );
                 }
 
-                write_schema_base64(Some(schema_base64_out), module_schema)
+                write_schema_base64(Some(schema_base64_out.clone()), module_schema)
                     .context("Could not write base64 schema file.")?;
+                schema_files.push(SchemaFile {
+                    format: SchemaFormat::Base64,
+                    path: schema_base64_out,
+                });
             }
         }
         if !options.no_schema_embed && print_extra_info {
@@ -1140,17 +2273,25 @@ fn handle_build(options: BuildOptions, print_extra_info: bool) -> anyhow::Result
             }
         }
 
-        let size = format!(
-            "{}.{:03} kB",
-            build_info.total_module_len / 1000,
-            build_info.total_module_len % 1000
-        );
         eprintln!(
             "    {} smart contract module {}",
             success_style.paint("Finished"),
-            bold_style.paint(size)
+            bold_style.paint(format_module_size(build_info.total_module_len))
+        );
+        eprintln!(
+            "    Module reference: {}",
+            bold_style.paint(build_info.module_ref.to_string())
         );
     }
+    if let Some(manifest_out) = &options.manifest_out {
+        build_manifest::write_manifest(
+            manifest_out,
+            &build_info,
+            options.version,
+            build_info.schema.is_some() && !options.no_schema_embed,
+            &schema_files,
+        )?;
+    }
     if options.schema_embed {
         let error_style = ansi_term::Color::Yellow;
         eprintln!(
@@ -1175,16 +2316,25 @@ fn handle_build(options: BuildOptions, print_extra_info: bool) -> anyhow::Result
     Ok(build_info)
 }
 
+/// Format the size of a module in kB.
+fn format_module_size(len: usize) -> String {
+    format!("{}.{:03} kB", len / 1000, len % 1000)
+}
+
 /// Loads the contract state from file and displays it as a tree by printing to
 /// stdout.
 fn display_state_from_file(file_path: PathBuf) -> anyhow::Result<()> {
-    let file = File::open(&file_path)
+    let state = read_state_from_file(&file_path)?;
+    display_state(&state)
+}
+
+/// Loads the contract state from a file with the state in binary.
+fn read_state_from_file(file_path: &Path) -> anyhow::Result<v1::trie::PersistentState> {
+    let file = File::open(file_path)
         .with_context(|| format!("Could not read state file {}.", file_path.display()))?;
     let mut reader = std::io::BufReader::new(file);
-    let state = v1::trie::PersistentState::deserialize(&mut reader)
-        .context("Could not deserialize the provided state.")?;
-
-    display_state(&state)
+    v1::trie::PersistentState::deserialize(&mut reader)
+        .context("Could not deserialize the provided state.")
 }
 
 /// Displays the contract state as a tree by printing to stdout.
@@ -1216,115 +2366,30 @@ fn get_colon_position<'a>(iter: impl Iterator<Item = &'a str>) -> usize {
     max_length_receive_opt.map_or(5, |m| m.max(5))
 }
 
-/// Print the contract name and its entrypoints
-fn print_contract_schema_v0(
-    contract_name: &str,
-    contract_schema: &contracts_common::schema::ContractV0,
-) {
+/// Print the contract name and its entrypoints.
+fn print_contract_schema(contract_name: &str, contract_schema: &ContractSchema) {
     let receive_iter = contract_schema.receive.keys().map(|n| n.as_str());
     let colon_position = get_colon_position(receive_iter);
 
-    print_schema_info(contract_name, to_bytes(contract_schema).len());
+    print_schema_info(contract_name, contract_schema.size);
 
     if let Some(state_schema) = &contract_schema.state {
         eprintln!("       state   : {} B", to_bytes(state_schema).len());
     }
     if let Some(init_schema) = &contract_schema.init {
-        eprintln!("       init    : {} B", to_bytes(init_schema).len())
-    }
-
-    if !contract_schema.receive.is_empty() {
-        eprintln!("       receive");
-        for (method_name, param_type) in contract_schema.receive.iter() {
-            eprintln!(
-                "        - {:width$} : {} B",
-                format!("'{}'", method_name),
-                to_bytes(param_type).len(),
-                width = colon_position + 2
-            );
-        }
-    }
-}
-
-/// Print the contract name and its entrypoints.
-fn print_contract_schema_v1(
-    contract_name: &str,
-    contract_schema: &contracts_common::schema::ContractV1,
-) {
-    let receive_iter = contract_schema.receive.keys().map(|n| n.as_str());
-    let colon_position = get_colon_position(receive_iter);
-
-    print_schema_info(contract_name, to_bytes(contract_schema).len());
-
-    if let Some(init_schema) = &contract_schema.init {
-        eprintln!("       init    : {} B", to_bytes(init_schema).len())
-    }
-
-    if !contract_schema.receive.is_empty() {
-        eprintln!("       receive");
-        for (method_name, param_type) in contract_schema.receive.iter() {
-            eprintln!(
-                "        - {:width$} : {} B",
-                format!("'{}'", method_name),
-                to_bytes(param_type).len(),
-                width = colon_position + 2
-            );
-        }
-    }
-}
-
-/// Print the contract name and its entrypoints.
-fn print_contract_schema_v2(
-    contract_name: &str,
-    contract_schema: &contracts_common::schema::ContractV2,
-) {
-    let receive_iter = contract_schema.receive.keys().map(|n| n.as_str());
-    let colon_position = get_colon_position(receive_iter);
-
-    print_schema_info(contract_name, to_bytes(contract_schema).len());
-
-    if let Some(init_schema) = &contract_schema.init {
-        eprintln!("       init    : {} B", to_bytes(init_schema).len())
-    }
-
-    if !contract_schema.receive.is_empty() {
-        eprintln!("       receive");
-        for (method_name, param_type) in contract_schema.receive.iter() {
-            eprintln!(
-                "        - {:width$} : {} B",
-                format!("'{}'", method_name),
-                to_bytes(param_type).len(),
-                width = colon_position + 2
-            );
-        }
-    }
-}
-
-/// Print the contract name and its entrypoints.
-fn print_contract_schema_v3(
-    contract_name: &str,
-    contract_schema: &contracts_common::schema::ContractV3,
-) {
-    let receive_iter = contract_schema.receive.keys().map(|n| n.as_str());
-    let colon_position = get_colon_position(receive_iter);
-
-    print_schema_info(contract_name, to_bytes(contract_schema).len());
-
-    if let Some(init_schema) = &contract_schema.init {
-        eprintln!("       init    : {} B", to_bytes(init_schema).len())
+        eprintln!("       init    : {} B", init_schema.size)
     }
-
     if let Some(event_schema) = &contract_schema.event {
         eprintln!("       event   : {} B", to_bytes(event_schema).len())
     }
 
     if !contract_schema.receive.is_empty() {
         eprintln!("       receive");
-        for (method_name, param_type) in contract_schema.receive.iter() {
+        for (method_name, function_schema) in contract_schema.receive.iter() {
             eprintln!(
                 "        - {:width$} : {} B",
                 format!("'{}'", method_name),
-                to_bytes(param_type).len(),
+                function_schema.size,
                 width = colon_position + 2
             );
         }
@@ -1344,7 +2409,12 @@ fn handle_run_v0(run_cmd: RunCommand, module: &[u8]) -> anyhow::Result<()> {
             ref entrypoint,
             ..
         } => (contract_name, runner, Some(entrypoint)),
+        RunCommand::Scenario(_) => unreachable!("Scenarios are run by `scenario::run_scenario`."),
     };
+    ensure!(
+        runner.energy_profile.is_none(),
+        "Energy profiling is only supported for V1 contracts."
+    );
 
     // get the module schema if available.
     let module_schema_opt = if let Some(schema_path) = &runner.schema_path {
@@ -1386,28 +2456,8 @@ fn handle_run_v0(run_cmd: RunCommand, module: &[u8]) -> anyhow::Result<()> {
         }
     });
 
-    let print_result = |state: v0::State, logs: v0::Logs| -> anyhow::Result<()> {
-        for (i, item) in logs.iterate().enumerate() {
-            eprintln!("{}: {:?}", i, item)
-        }
-        let state = &state.state;
-        match (runner.ignore_state_schema, &contract_schema_state_opt) {
-            (false, Some(state_schema)) => {
-                let s = state_schema
-                    .to_json_string_pretty(state)
-                    .map_err(|_| anyhow::anyhow!("Could not encode state to JSON."))?;
-                if runner.schema_path.is_some() {
-                    eprintln!("The new state is: (Using provided schema)\n{}", s)
-                } else {
-                    eprintln!("The new state is: (Using embedded schema)\n{}", s)
-                }
-            }
-            _ => eprintln!(
-                "The new state is: (No schema found for contract state) {:?}\n",
-                state
-            ),
-        };
-
+    // Write the new state to the files requested by the user.
+    let write_state = |state: &[u8]| -> anyhow::Result<()> {
         if let Some(file_path) = &runner.out_bin {
             if let Some(out_dir) = file_path.parent() {
                 fs::create_dir_all(out_dir)
@@ -1437,6 +2487,35 @@ fn handle_run_v0(run_cmd: RunCommand, module: &[u8]) -> anyhow::Result<()> {
         Ok(())
     };
 
+    let print_result = |state: v0::State, logs: v0::Logs| -> anyhow::Result<()> {
+        for (i, item) in logs.iterate().enumerate() {
+            eprintln!("{}: {:?}", i, item)
+        }
+        let state = &state.state;
+        match (runner.ignore_state_schema, &contract_schema_state_opt) {
+            (false, Some(state_schema)) => {
+                let s = state_schema
+                    .to_json_string_pretty(state)
+                    .map_err(|_| anyhow::anyhow!("Could not encode state to JSON."))?;
+                if runner.schema_path.is_some() {
+                    eprintln!("The new state is: (Using provided schema)\n{}", s)
+                } else {
+                    eprintln!("The new state is: (Using embedded schema)\n{}", s)
+                }
+            }
+            _ => eprintln!(
+                "The new state is: (No schema found for contract state) {:?}\n",
+                state
+            ),
+        };
+        write_state(state)
+    };
+
+    // The schema used for displaying the state in the JSON output.
+    let state_schema = contract_schema_state_opt
+        .as_ref()
+        .filter(|_| !runner.ignore_state_schema);
+
     let parameter = get_parameter(
         runner.parameter_bin_path.as_deref(),
         runner.parameter_json_path.as_deref(),
@@ -1467,6 +2546,9 @@ fn handle_run_v0(run_cmd: RunCommand, module: &[u8]) -> anyhow::Result<()> {
                 runner.energy,
             )
             .context("Initialization failed due to a runtime error.")?;
+            if runner.output_format == OutputFormat::Json {
+                return RunReport::init_v0(res, runner.energy, state_schema, write_state)?.print();
+            }
             match res {
                 v0::InitResult::Success {
                     logs,
@@ -1566,6 +2648,10 @@ fn handle_run_v0(run_cmd: RunCommand, module: &[u8]) -> anyhow::Result<()> {
                 CostConfigurationV1,
             )
             .context("Calling receive failed.")?;
+            if runner.output_format == OutputFormat::Json {
+                return RunReport::receive_v0(res, runner.energy, state_schema, write_state)?
+                    .print();
+            }
             match res {
                 v0::ReceiveResult::Success {
                     logs,
@@ -1629,6 +2715,7 @@ fn handle_run_v0(run_cmd: RunCommand, module: &[u8]) -> anyhow::Result<()> {
                 }
             }
         }
+        RunCommand::Scenario(_) => unreachable!("Scenarios are run by `scenario::run_scenario`."),
     }
     Ok(())
 }
@@ -1659,32 +2746,88 @@ fn print_debug(trace: DebugTracker) {
     }
 }
 
-fn handle_run_v1(run_cmd: RunCommand, module: &[u8]) -> anyhow::Result<()> {
-    let (contract_name, runner, is_receive, emit_debug) = match run_cmd {
-        RunCommand::Init {
-            ref runner,
-            ref contract_name,
-            emit_debug,
-            ..
-        } => (contract_name, runner, None, emit_debug),
-        RunCommand::Receive {
-            ref runner,
-            ref contract_name,
-            ref entrypoint,
-            emit_debug,
-            ..
-        } => (contract_name, runner, Some(entrypoint), emit_debug),
-    };
+/// Print the logs produced by a V1 contract. If a schema for the events is
+/// provided the events are printed as JSON, otherwise the raw bytes are
+/// printed.
+fn print_logs_v1(logs: v0::Logs, schema_event: Option<&Type>) {
+    for (i, item) in logs.iterate().enumerate() {
+        match schema_event {
+            Some(schema) => {
+                let out = schema
+                    .to_json_string_pretty(item)
+                    .map_err(|_| anyhow::anyhow!("Could not output event value in JSON"));
+                match out {
+                    Ok(event_json) => {
+                        // Print JSON representation of the event value if the event schema is
+                        // available.
+                        eprintln!("The JSON representation of event {} is:\n{}", i, event_json);
+                    }
+                    Err(error) => {
+                        // Print the raw event value if there is an error in the event schema.
+                        eprintln!(
+                            "Event schema had an error. {:?}. The raw value of event {} is:\n{:?}",
+                            error, i, item
+                        );
+                    }
+                }
+            }
+            None => {
+                eprintln!("The raw value of event {} is:\n{:?}", i, item);
+            }
+        }
+    }
+}
 
-    // get the module schema if available.
-    let module_schema_opt = if let Some(schema_path) = &runner.schema_path {
+/// Print the return value of a V1 contract, using the schema if available.
+fn print_return_value(rv: ReturnValue, schema_return_value: Option<&Type>) -> anyhow::Result<()> {
+    if let Some(schema) = schema_return_value {
+        let out = schema
+            .to_json_string_pretty(&rv)
+            .map_err(|_| anyhow::anyhow!("Could not output return value in JSON"))?;
+        eprintln!("Return value: {}", out);
+    } else {
+        eprintln!(
+            "No schema for the return value. The raw return value is {:?}.",
+            rv
+        );
+    }
+    Ok(())
+}
+
+/// Print the error value of a rejected V1 contract call, using the schema if
+/// available.
+fn print_error_value(rv: ReturnValue, schema_error: Option<&Type>) -> anyhow::Result<()> {
+    if let Some(schema) = schema_error {
+        let out = schema
+            .to_json_string_pretty(&rv)
+            .map_err(|_| anyhow::anyhow!("Could not output error value in JSON"))?;
+        eprintln!("Error: {}", out);
+    } else {
+        eprintln!(
+            "No schema for the error value. The raw error value is {:?}.",
+            rv
+        );
+    }
+    Ok(())
+}
+
+/// Get the schema of a V1 module, either from the given schema file or, if
+/// none is given, from the schema embedded in the module. A warning is printed
+/// if no schema file is given and the module has no valid embedded schema.
+fn get_module_schema_v1(
+    schema_path: Option<&Path>,
+    module: &[u8],
+) -> anyhow::Result<Option<VersionedModuleSchema>> {
+    if let Some(schema_path) = schema_path {
         let bytes = fs::read(schema_path).context("Could not read schema file.")?;
         let schema = if bytes.starts_with(VERSIONED_SCHEMA_MAGIC_HASH) {
             from_bytes::<VersionedModuleSchema>(&bytes)
         } else {
             from_bytes(&bytes).map(VersionedModuleSchema::V1)
         };
-        Some(schema.map_err(|_| anyhow::anyhow!("Could not deserialize schema file."))?)
+        Ok(Some(schema.map_err(|_| {
+            anyhow::anyhow!("Could not deserialize schema file.")
+        })?))
     } else {
         let res = utils::get_embedded_schema_v1(module);
         if let Err(err) = &res {
@@ -1697,8 +2840,100 @@ fn handle_run_v1(run_cmd: RunCommand, module: &[u8]) -> anyhow::Result<()> {
                 ))
             );
         }
-        res.ok()
+        Ok(res.ok())
+    }
+}
+
+/// Print a description of the interrupt a receive call was interrupted with.
+fn print_interrupt(interrupt: &v1::Interrupt) {
+    match interrupt {
+        v1::Interrupt::Transfer { to, amount } => eprintln!(
+            "Receive call invoked a transfer of {} CCD to {}.",
+            amount, to
+        ),
+        v1::Interrupt::Call {
+            address,
+            parameter,
+            name,
+            amount,
+        } => eprintln!(
+            "Receive call invoked contract at ({}, {}), calling method {} with \
+             amount {} and parameter {:?}.",
+            address.index, address.subindex, name, amount, parameter
+        ),
+        v1::Interrupt::Upgrade { module_ref } => eprintln!(
+            "Receive call requested to upgrade the contract to module reference \
+             {module_ref}.",
+        ),
+
+        v1::Interrupt::QueryAccountBalance { address } => {
+            eprintln!("Receive call requested balance of the account {}.", address)
+        }
+
+        v1::Interrupt::QueryContractBalance { address } => eprintln!(
+            "Receive call requested balance of the contract {}.",
+            address
+        ),
+        v1::Interrupt::QueryExchangeRates => {
+            eprintln!("Receive call requested exchange rates.")
+        }
+        v1::Interrupt::CheckAccountSignature { address, payload } => {
+            eprintln!(
+                "Receive call requested account signature check for address \
+                 {address}. The payload is {}.",
+                hex::encode(payload)
+            );
+        }
+        v1::Interrupt::QueryAccountKeys { address } => {
+            eprintln!("Receive call requested public keys of account {address}.");
+        }
+        v1::Interrupt::QueryContractModuleReference { address } => {
+            eprintln!("Receive call requested module reference of contract {address}.");
+        }
+        v1::Interrupt::QueryContractName { address } => {
+            eprintln!("Receive call requested init-name of contract {address}.");
+        }
+    }
+}
+
+fn handle_run_v1(run_cmd: RunCommand, module: &[u8]) -> anyhow::Result<()> {
+    let (contract_name, runner, is_receive, emit_debug, should_display_state) = match run_cmd {
+        RunCommand::Init {
+            ref runner,
+            ref contract_name,
+            emit_debug,
+            should_display_state,
+            ..
+        } => (
+            contract_name,
+            runner,
+            None,
+            emit_debug,
+            should_display_state,
+        ),
+        RunCommand::Receive {
+            ref runner,
+            ref contract_name,
+            ref entrypoint,
+            emit_debug,
+            should_display_state,
+            ..
+        } => (
+            contract_name,
+            runner,
+            Some(entrypoint),
+            emit_debug,
+            should_display_state,
+        ),
+        RunCommand::Scenario(_) => unreachable!("Scenarios are run by `scenario::run_scenario`."),
     };
+    ensure!(
+        !(should_display_state && runner.output_format == OutputFormat::Json),
+        "--display-state cannot be combined with --output-format json."
+    );
+
+    // get the module schema if available.
+    let module_schema_opt = get_module_schema_v1(runner.schema_path.as_deref(), module)?;
 
     let (contract_has_schema, schema_parameter, schema_return_value, schema_error, schema_event) =
         match module_schema_opt.as_ref() {
@@ -1783,46 +3018,21 @@ fn handle_run_v1(run_cmd: RunCommand, module: &[u8]) -> anyhow::Result<()> {
             None => (false, None, None, None, None),
         };
 
-    let print_logs = |logs: v0::Logs| {
-        for (i, item) in logs.iterate().enumerate() {
-            match schema_event {
-                Some(schema) => {
-                    let out = schema
-                        .to_json_string_pretty(item)
-                        .map_err(|_| anyhow::anyhow!("Could not output event value in JSON"));
-                    match out {
-                        Ok(event_json) => {
-                            // Print JSON representation of the event value if the event schema is
-                            // available.
-                            eprintln!("The JSON representation of event {} is:\n{}", i, event_json);
-                        }
-                        Err(error) => {
-                            // Print the raw event value if there is an error in the event schema.
-                            eprintln!(
-                                "Event schema had an error. {:?}. The raw value of event {} \
-                                 is:\n{:?}",
-                                error, i, item
-                            );
-                        }
-                    }
-                }
-                None => {
-                    eprintln!("The raw value of event {} is:\n{:?}", i, item);
-                }
-            }
-        }
-    };
-
+    // Output the new state and return the size of the additional state the
+    // contract will be charged for.
     let print_state = |mut state: v1::trie::MutableState,
                        loader: &mut v1::trie::Loader<&[u8]>,
                        should_display_state: bool|
-     -> anyhow::Result<()> {
+     -> anyhow::Result<u64> {
         let mut collector = v1::trie::SizeCollector::default();
         let frozen = state.freeze(loader, &mut collector);
-        println!(
-            "\nThe contract will produce {}B of additional state that will be charged for.",
-            collector.collect()
-        );
+        let additional_state_size = collector.collect();
+        if runner.output_format == OutputFormat::Text {
+            println!(
+                "\nThe contract will produce {}B of additional state that will be charged for.",
+                additional_state_size
+            );
+        }
         if let Some(file_path) = &runner.out_bin {
             let mut out_file = std::fs::File::create(file_path)
                 .context("Could not create file to write state into.")?;
@@ -1834,39 +3044,13 @@ fn handle_run_v1(run_cmd: RunCommand, module: &[u8]) -> anyhow::Result<()> {
         if should_display_state {
             display_state(&frozen)?;
         }
-        Ok(())
-    };
-
-    let print_return_value = |rv: ReturnValue| {
-        if let Some(schema) = schema_return_value {
-            let out = schema
-                .to_json_string_pretty(&rv)
-                .map_err(|_| anyhow::anyhow!("Could not output return value in JSON"))?;
-            eprintln!("Return value: {}", out);
-            Ok::<_, anyhow::Error>(())
-        } else {
-            eprintln!(
-                "No schema for the return value. The raw return value is {:?}.",
-                rv
-            );
-            Ok(())
-        }
+        Ok(additional_state_size)
     };
 
-    let print_error = |rv: ReturnValue| {
-        if let Some(schema) = schema_error {
-            let out = schema
-                .to_json_string_pretty(&rv)
-                .map_err(|_| anyhow::anyhow!("Could not output error value in JSON"))?;
-            eprintln!("Error: {}", out);
-            Ok::<_, anyhow::Error>(())
-        } else {
-            eprintln!(
-                "No schema for the error value. The raw error value is {:?}.",
-                rv
-            );
-            Ok(())
-        }
+    let schemas = ValueSchemas {
+        event: schema_event,
+        return_value: schema_return_value,
+        error: schema_error,
     };
 
     let parameter = get_parameter(
@@ -1878,11 +3062,7 @@ fn handle_run_v1(run_cmd: RunCommand, module: &[u8]) -> anyhow::Result<()> {
     .context("Could not get parameter.")?;
 
     match run_cmd {
-        RunCommand::Init {
-            ref context,
-            should_display_state,
-            ..
-        } => {
+        RunCommand::Init { ref context, .. } => {
             let init_ctx: InitContextOpt = match context {
                 Some(context_file) => {
                     let ctx_content =
@@ -1892,6 +3072,27 @@ fn handle_run_v1(run_cmd: RunCommand, module: &[u8]) -> anyhow::Result<()> {
                 None => InitContextOpt::default(),
             };
             let name = format!("init_{}", contract_name);
+            if let Some(profile_path) = &runner.energy_profile {
+                let instrumented = instrument_for_profiling(runner, module)?;
+                let res = v1::invoke_init_with_metering_from_source::<_, DebugTracker>(
+                    v1::InvokeFromSourceCtx {
+                        source: &instrumented.module,
+                        amount: runner.amount,
+                        parameter: parameter.as_ref(),
+                        energy: runner.energy,
+                        support_upgrade: true,
+                    },
+                    init_ctx.clone(),
+                    &name,
+                    v1::trie::Loader::new(&[][..]),
+                    ValidationConfig::V1,
+                    CostConfigurationV1,
+                    false,
+                )
+                .context("Profiling failed due to a runtime error.")?;
+                let profile = Profile::from_init_result(&instrumented, &res, runner.energy);
+                output_profile(&profile, profile_path)?;
+            }
             // empty initial backing store.
             let mut loader = v1::trie::Loader::new(&[][..]);
             let res = v1::invoke_init_with_metering_from_source::<_, DebugTracker>(
@@ -1911,6 +3112,12 @@ fn handle_run_v1(run_cmd: RunCommand, module: &[u8]) -> anyhow::Result<()> {
                         * Limits removed in PV5. */
             )
             .context("Initialization failed due to a runtime error.")?;
+            if runner.output_format == OutputFormat::Json {
+                return RunReport::init_v1(res, runner.energy, schemas, |state| {
+                    print_state(state, &mut loader, should_display_state)
+                })?
+                .print();
+            }
             match res {
                 v1::InitResult::Success {
                     logs,
@@ -1920,10 +3127,10 @@ fn handle_run_v1(run_cmd: RunCommand, module: &[u8]) -> anyhow::Result<()> {
                     trace,
                 } => {
                     eprintln!("\nInit call succeeded. The following logs were produced:");
-                    print_logs(logs);
+                    print_logs_v1(logs, schema_event);
                     print_state(state, &mut loader, should_display_state)?;
                     eprintln!("\nThe following return value was returned:");
-                    print_return_value(return_value)?;
+                    print_return_value(return_value, schema_return_value)?;
                     eprintln!(
                         "\nInterpreter energy spent is {}",
                         runner.energy.subtract(remaining_energy.energy)
@@ -1940,7 +3147,7 @@ fn handle_run_v1(run_cmd: RunCommand, module: &[u8]) -> anyhow::Result<()> {
                 } => {
                     eprintln!("Init call rejected with reason {}.", reason);
                     eprintln!("\nThe following error value was returned:");
-                    print_error(return_value)?;
+                    print_error_value(return_value, schema_error)?;
                     eprintln!(
                         "\nInterpreter energy spent is {}",
                         runner.energy.subtract(remaining_energy.energy)
@@ -1975,7 +3182,8 @@ fn handle_run_v1(run_cmd: RunCommand, module: &[u8]) -> anyhow::Result<()> {
             ref state_bin_path,
             balance,
             ref context,
-            should_display_state,
+            ref contracts,
+            ref chain_state,
             ..
         } => {
             let mut receive_ctx: ReceiveContextV1Opt = match context {
@@ -2001,10 +3209,7 @@ fn handle_run_v1(run_cmd: RunCommand, module: &[u8]) -> anyhow::Result<()> {
                      instance. Use --state-bin."
                 ),
                 Some(file_path) => {
-                    let file = File::open(file_path).context("Could not read state file.")?;
-                    let mut reader = std::io::BufReader::new(file);
-                    let init_state = v1::trie::PersistentState::deserialize(&mut reader)
-                        .context("Could not deserialize the provided state.")?;
+                    let init_state = read_state_from_file(file_path)?;
                     // Since we deserialized the entire state we do not need a loader.
                     // Once this is changed to load data lazily from a file, the loader will be
                     // needed.
@@ -2026,200 +3231,321 @@ fn handle_run_v1(run_cmd: RunCommand, module: &[u8]) -> anyhow::Result<()> {
                 module,
             )?
             .artifact;
-            let name = {
-                let chosen_name = format!("{}.{}", contract_name, entrypoint);
-                if let Err(e) = ReceiveName::is_valid_receive_name(&chosen_name) {
-                    anyhow::bail!("Invalid contract or receive function name: {}", e)
-                }
-                if artifact.has_entrypoint(chosen_name.as_str()) {
-                    OwnedReceiveName::new_unchecked(chosen_name)
-                } else {
-                    let fallback_name = format!("{}.", contract_name);
-                    if artifact.has_entrypoint(fallback_name.as_str()) {
-                        eprintln!(
-                            "The contract '{}' does not have the entrypoint '{}'. Using the \
-                             fallback entrypoint instead.",
-                            contract_name, entrypoint
-                        );
-                        OwnedReceiveName::new_unchecked(fallback_name)
-                    } else {
-                        anyhow::bail!(
-                            "The contract '{}' has neither the requested entrypoint '{}', nor a \
-                             fallback entrypoint.",
-                            contract_name,
-                            entrypoint
-                        );
-                    }
-                }
-            };
+            let name = resolve_receive_name(&artifact, contract_name, entrypoint)?;
+
+            if let (Some(profile_path), Some(state_path)) = (&runner.energy_profile, state_bin_path)
+            {
+                ensure!(
+                    contracts.is_none() && chain_state.is_none(),
+                    "Energy profiling is not supported together with --contracts or \
+                     --chain-state."
+                );
+                let instrumented = instrument_for_profiling(runner, module)?;
+                let artifact = concordium_wasm::utils::instantiate_with_metering(
+                    ValidationConfig::V1,
+                    CostConfigurationV1,
+                    &v1::ConcordiumAllowedImports {
+                        support_upgrade: true,
+                        // Probes are emitted as debug events.
+                        enable_debug: true,
+                    },
+                    &instrumented.module,
+                )?
+                .artifact;
+                let mut loader = v1::trie::Loader::new(&[][..]);
+                let mut state = read_state_from_file(state_path)?.thaw();
+                let inner = state.get_inner(&mut loader);
+                let res = v1::invoke_receive::<
+                    _,
+                    _,
+                    _,
+                    _,
+                    ReceiveContextV1Opt,
+                    ReceiveContextV1Opt,
+                    DebugTracker,
+                >(
+                    std::sync::Arc::new(artifact),
+                    receive_ctx.clone(),
+                    v1::ReceiveInvocation {
+                        amount: runner.amount,
+                        receive_name: name.as_receive_name(),
+                        parameter: parameter.as_ref(),
+                        energy: runner.energy,
+                    },
+                    v1::InstanceState::new(loader, inner),
+                    v1::ReceiveParams::new_p6(),
+                )
+                .context("Profiling failed.")?;
+                let profile = Profile::from_receive_result(&instrumented, &res, runner.energy);
+                output_profile(&profile, profile_path)?;
+            }
 
             let mut mutable_state = init_state.thaw();
-            let inner = mutable_state.get_inner(&mut loader);
-            let instance_state = v1::InstanceState::new(loader, inner);
-            let res = v1::invoke_receive::<
-                _,
-                _,
-                _,
-                _,
-                ReceiveContextV1Opt,
-                ReceiveContextV1Opt,
-                DebugTracker,
-            >(
-                std::sync::Arc::new(artifact),
-                receive_ctx,
-                v1::ReceiveInvocation {
-                    amount: runner.amount,
-                    receive_name: name.as_receive_name(),
-                    parameter: parameter.as_ref(),
-                    energy: runner.energy,
-                },
-                instance_state,
-                v1::ReceiveParams::new_p6(),
-            )
-            .context("Calling receive failed.")?;
-            match res {
-                v1::ReceiveResult::Success {
-                    logs,
-                    state_changed,
-                    remaining_energy,
-                    return_value,
-                    trace,
-                } => {
-                    eprintln!("\nReceive method succeeded. The following logs were produced.");
-                    print_logs(logs);
-                    if state_changed {
-                        print_state(mutable_state, &mut loader, should_display_state)?;
-                    } else {
-                        eprintln!("The state of the contract did not change.");
-                    }
-                    eprintln!("\nThe following return value was returned:");
-                    print_return_value(return_value)?;
-                    eprintln!(
-                        "\nInterpreter energy spent is {}",
-                        runner.energy.signed_diff(remaining_energy)
-                    );
-                    if emit_debug {
-                        print_debug(trace);
-                    }
-                }
-                v1::ReceiveResult::Reject {
-                    remaining_energy,
-                    reason,
-                    return_value,
-                    trace,
-                } => {
-                    eprintln!("Receive call rejected with reason {}", reason);
-                    eprintln!("\nThe following error value was returned:");
-                    print_error(return_value)?;
-                    eprintln!(
-                        "\nInterpreter energy spent is {}",
-                        runner.energy.signed_diff(remaining_energy)
-                    );
-                    if emit_debug {
-                        print_debug(trace);
-                    }
-                }
-                v1::ReceiveResult::OutOfEnergy { trace } => {
-                    eprintln!("Receive call terminated with: out of energy.");
-                    if emit_debug {
-                        print_debug(trace);
-                    }
+            let invocation = v1::ReceiveInvocation {
+                amount: runner.amount,
+                receive_name: name.as_receive_name(),
+                parameter: parameter.as_ref(),
+                energy: runner.energy,
+            };
+            if contracts.is_some() || chain_state.is_some() {
+                let chain_state = match chain_state {
+                    Some(path) => ChainState::from_file(path)?,
+                    None => ChainState::default(),
+                };
+                let mut local_chain =
+                    LocalChain::new(contracts.as_deref(), chain_state, runner.output_format)?;
+                let receive_ctx = receive_ctx
+                    .into_complete(OwnedEntrypointName::new_unchecked(entrypoint.clone()))
+                    .context(
+                        "A complete receive context is required for simulating the interaction \
+                         with the chain.",
+                    )?;
+                let res = local_chain.invoke(
+                    std::sync::Arc::new(artifact),
+                    receive_ctx,
+                    invocation,
+                    &mut mutable_state,
+                    schema_event,
+                    emit_debug,
+                )?;
+                let succeeded = matches!(res, v1::ReceiveResult::Success { .. });
+                print_receive_result_v1(
+                    res,
+                    || print_state(mutable_state, &mut loader, should_display_state),
+                    runner.energy,
+                    schemas,
+                    emit_debug,
+                    runner.output_format,
+                    local_chain.take_chain_trace(),
+                )?;
+                if succeeded {
+                    local_chain.write_states()?;
                 }
-                v1::ReceiveResult::Interrupt {
-                    remaining_energy,
-                    state_changed,
-                    logs,
-                    config: _,
-                    interrupt,
-                    trace,
-                } => {
-                    eprintln!(
-                        "Receive method was interrupted. The following logs were produced by the \
-                         time of the interrupt."
-                    );
-                    print_logs(logs);
-                    if state_changed {
-                        print_state(mutable_state, &mut loader, should_display_state)?;
-                    } else {
-                        eprintln!("The state of the contract did not change.");
-                    }
-                    match interrupt {
-                        v1::Interrupt::Transfer { to, amount } => eprintln!(
-                            "Receive call invoked a transfer of {} CCD to {}.",
-                            amount, to
-                        ),
-                        v1::Interrupt::Call {
-                            address,
-                            parameter,
-                            name,
-                            amount,
-                        } => eprintln!(
-                            "Receive call invoked contract at ({}, {}), calling method {} with \
-                             amount {} and parameter {:?}.",
-                            address.index, address.subindex, name, amount, parameter
-                        ),
-                        v1::Interrupt::Upgrade { module_ref } => eprintln!(
-                            "Receive call requested to upgrade the contract to module reference \
-                             {module_ref}.",
-                        ),
-
-                        v1::Interrupt::QueryAccountBalance { address } => {
-                            eprintln!("Receive call requested balance of the account {}.", address)
-                        }
+            } else {
+                let inner = mutable_state.get_inner(&mut loader);
+                let instance_state = v1::InstanceState::new(loader, inner);
+                let res = v1::invoke_receive::<
+                    _,
+                    _,
+                    _,
+                    _,
+                    ReceiveContextV1Opt,
+                    ReceiveContextV1Opt,
+                    DebugTracker,
+                >(
+                    std::sync::Arc::new(artifact),
+                    receive_ctx,
+                    invocation,
+                    instance_state,
+                    v1::ReceiveParams::new_p6(),
+                )
+                .context("Calling receive failed.")?;
+                print_receive_result_v1(
+                    res,
+                    || print_state(mutable_state, &mut loader, should_display_state),
+                    runner.energy,
+                    schemas,
+                    emit_debug,
+                    runner.output_format,
+                    Vec::new(),
+                )?;
+            }
+        }
+        RunCommand::Scenario(_) => unreachable!("Scenarios are run by `scenario::run_scenario`."),
+    }
+    Ok(())
+}
 
-                        v1::Interrupt::QueryContractBalance { address } => eprintln!(
-                            "Receive call requested balance of the contract {}.",
-                            address
-                        ),
-                        v1::Interrupt::QueryExchangeRates => {
-                            eprintln!("Receive call requested exchange rates.")
-                        }
-                        v1::Interrupt::CheckAccountSignature { address, payload } => {
-                            eprintln!(
-                                "Receive call requested account signature check for address \
-                                 {address}. The payload is {}.",
-                                hex::encode(payload)
-                            );
-                        }
-                        v1::Interrupt::QueryAccountKeys { address } => {
-                            eprintln!("Receive call requested public keys of account {address}.");
-                        }
-                        v1::Interrupt::QueryContractModuleReference { address } => {
-                            eprintln!(
-                                "Receive call requested module reference of contract {address}."
-                            );
-                        }
-                        v1::Interrupt::QueryContractName { address } => {
-                            eprintln!("Receive call requested init-name of contract {address}.");
-                        }
-                    }
-                    eprintln!(
-                        "Interpreter energy spent is {}",
-                        runner.energy.signed_diff(remaining_energy)
-                    );
-                    if emit_debug {
-                        print_debug(trace);
-                    }
-                }
-                v1::ReceiveResult::Trap {
-                    remaining_energy,
-                    error,
-                    trace,
-                } => {
-                    if emit_debug {
-                        print_debug(trace);
-                    }
-                    return Err(error.context(format!(
-                        "Execution triggered a runtime error after spending {} interpreter energy.",
-                        runner.energy.signed_diff(remaining_energy)
-                    )));
-                }
+/// Print the result of invoking a receive function of a V1 contract in the
+/// requested format. `print_state` is used for outputting the state if it
+/// changed. The `chain_trace` of the simulated calls and queries is included in
+/// the JSON report, and is empty when the output is text, since those are
+/// printed as they happen.
+fn print_receive_result_v1<Ctx>(
+    res: v1::ReceiveResult<CompiledFunction, DebugTracker, Ctx>,
+    print_state: impl FnOnce() -> anyhow::Result<u64>,
+    energy: InterpreterEnergy,
+    schemas: ValueSchemas,
+    emit_debug: bool,
+    output_format: OutputFormat,
+    chain_trace: Vec<ChainTraceEntry>,
+) -> anyhow::Result<()> {
+    if output_format == OutputFormat::Json {
+        return RunReport::receive_v1(res, energy, schemas, print_state)?
+            .with_chain_trace(chain_trace)
+            .print();
+    }
+    let ValueSchemas {
+        event: schema_event,
+        return_value: schema_return_value,
+        error: schema_error,
+    } = schemas;
+    match res {
+        v1::ReceiveResult::Success {
+            logs,
+            state_changed,
+            remaining_energy,
+            return_value,
+            trace,
+        } => {
+            eprintln!("\nReceive method succeeded. The following logs were produced.");
+            print_logs_v1(logs, schema_event);
+            if state_changed {
+                print_state()?;
+            } else {
+                eprintln!("The state of the contract did not change.");
+            }
+            eprintln!("\nThe following return value was returned:");
+            print_return_value(return_value, schema_return_value)?;
+            eprintln!(
+                "\nInterpreter energy spent is {}",
+                energy.signed_diff(remaining_energy)
+            );
+            if emit_debug {
+                print_debug(trace);
+            }
+        }
+        v1::ReceiveResult::Reject {
+            remaining_energy,
+            reason,
+            return_value,
+            trace,
+        } => {
+            eprintln!("Receive call rejected with reason {}", reason);
+            eprintln!("\nThe following error value was returned:");
+            print_error_value(return_value, schema_error)?;
+            eprintln!(
+                "\nInterpreter energy spent is {}",
+                energy.signed_diff(remaining_energy)
+            );
+            if emit_debug {
+                print_debug(trace);
+            }
+        }
+        v1::ReceiveResult::OutOfEnergy { trace } => {
+            eprintln!("Receive call terminated with: out of energy.");
+            if emit_debug {
+                print_debug(trace);
+            }
+        }
+        v1::ReceiveResult::Interrupt {
+            remaining_energy,
+            state_changed,
+            logs,
+            config: _,
+            interrupt,
+            trace,
+        } => {
+            eprintln!(
+                "Receive method was interrupted. The following logs were produced by the \
+                 time of the interrupt."
+            );
+            print_logs_v1(logs, schema_event);
+            if state_changed {
+                print_state()?;
+            } else {
+                eprintln!("The state of the contract did not change.");
+            }
+            print_interrupt(&interrupt);
+            eprintln!(
+                "Interpreter energy spent is {}",
+                energy.signed_diff(remaining_energy)
+            );
+            if emit_debug {
+                print_debug(trace);
             }
         }
+        v1::ReceiveResult::Trap {
+            remaining_energy,
+            error,
+            trace,
+        } => {
+            if emit_debug {
+                print_debug(trace);
+            }
+            return Err(error.context(format!(
+                "Execution triggered a runtime error after spending {} interpreter energy.",
+                energy.signed_diff(remaining_energy)
+            )));
+        }
     }
     Ok(())
 }
 
+/// Instrument the module for profiling. The names of the functions are read
+/// from the symbols file if one is given.
+fn instrument_for_profiling(
+    runner: &Runner,
+    module: &[u8],
+) -> anyhow::Result<profile::Instrumented> {
+    let symbols = match &runner.energy_profile_symbols {
+        Some(path) => Some(fs::read(path).context("Could not read the symbols file.")?),
+        None => None,
+    };
+    profile::instrument(module, symbols.as_deref(), ProbeTransport::DebugEvent)
+        .context("Could not instrument the module for profiling.")
+}
+
+/// Write the energy profile to the file, and print a summary of it.
+fn output_profile(profile: &Profile, path: &Path) -> anyhow::Result<()> {
+    profile.write_folded(path)?;
+    profile.print_summary();
+    eprintln!("Energy profile written to {}.", path.display());
+    Ok(())
+}
+
+/// Find the receive function to invoke for a call to the entrypoint, falling
+/// back to the fallback entrypoint if the contract does not have the
+/// entrypoint. Returns the name of the receive function, and whether it is the
+/// fallback entrypoint, or `None` if the contract has neither.
+fn find_receive_name<I, R>(
+    artifact: &Artifact<I, R>,
+    contract_name: &str,
+    entrypoint: &str,
+) -> Option<(OwnedReceiveName, bool)> {
+    let chosen_name = format!("{}.{}", contract_name, entrypoint);
+    if artifact.has_entrypoint(chosen_name.as_str()) {
+        return Some((OwnedReceiveName::new_unchecked(chosen_name), false));
+    }
+    let fallback_name = format!("{}.", contract_name);
+    if artifact.has_entrypoint(fallback_name.as_str()) {
+        Some((OwnedReceiveName::new_unchecked(fallback_name), true))
+    } else {
+        None
+    }
+}
+
+/// Determine the name of the receive function to invoke. If the contract does
+/// not have the requested entrypoint, but it does have a fallback entrypoint,
+/// then the name of the fallback entrypoint is returned.
+fn resolve_receive_name<I, R>(
+    artifact: &Artifact<I, R>,
+    contract_name: &str,
+    entrypoint: &str,
+) -> anyhow::Result<OwnedReceiveName> {
+    let chosen_name = format!("{}.{}", contract_name, entrypoint);
+    if let Err(e) = ReceiveName::is_valid_receive_name(&chosen_name) {
+        anyhow::bail!("Invalid contract or receive function name: {}", e)
+    }
+    match find_receive_name(artifact, contract_name, entrypoint) {
+        Some((name, false)) => Ok(name),
+        Some((name, true)) => {
+            eprintln!(
+                "The contract '{}' does not have the entrypoint '{}'. Using the fallback \
+                 entrypoint instead.",
+                contract_name, entrypoint
+            );
+            Ok(name)
+        }
+        None => anyhow::bail!(
+            "The contract '{}' has neither the requested entrypoint '{}', nor a fallback \
+             entrypoint.",
+            contract_name,
+            entrypoint
+        ),
+    }
+}
+
 /// Attempt to get a parameter (for either init or receive function) from the
 /// supplied paths, signalling failure if this is not possible.
 fn get_parameter(
@@ -2315,35 +3641,63 @@ fn get_schema(
 /// Write the JSON representation of the schema into files in the `out`
 /// directory. The files are named after contract_names, except if a
 /// contract_name contains unsuitable characters. Then the counter is used to
-/// name the file.
-fn write_json_schema(out: &Path, schema: &VersionedModuleSchema) -> anyhow::Result<()> {
+/// name the file. The types are represented as given by `format`.
+fn write_json_schema(
+    out: &Path,
+    schema: &VersionedModuleSchema,
+    format: SchemaTypeFormat,
+) -> anyhow::Result<()> {
     match schema {
         VersionedModuleSchema::V0(module_schema) => {
             for (contract_counter, (contract_name, contract_schema)) in
                 module_schema.contracts.iter().enumerate()
             {
-                write_json_schema_to_file_v0(out, contract_name, contract_counter, contract_schema)?
+                write_json_schema_to_file_v0(
+                    out,
+                    contract_name,
+                    contract_counter,
+                    contract_schema,
+                    format,
+                )?
             }
         }
         VersionedModuleSchema::V1(module_schema) => {
             for (contract_counter, (contract_name, contract_schema)) in
                 module_schema.contracts.iter().enumerate()
             {
-                write_json_schema_to_file_v1(out, contract_name, contract_counter, contract_schema)?
+                write_json_schema_to_file_v1(
+                    out,
+                    contract_name,
+                    contract_counter,
+                    contract_schema,
+                    format,
+                )?
             }
         }
         VersionedModuleSchema::V2(module_schema) => {
             for (contract_counter, (contract_name, contract_schema)) in
                 module_schema.contracts.iter().enumerate()
             {
-                write_json_schema_to_file_v2(out, contract_name, contract_counter, contract_schema)?
+                write_json_schema_to_file_v2(
+                    out,
+                    contract_name,
+                    contract_counter,
+                    contract_schema,
+                    format,
+                )?
             }
         }
         VersionedModuleSchema::V3(module_schema) => {
             for (contract_counter, (contract_name, contract_schema)) in
                 module_schema.contracts.iter().enumerate()
             {
-                write_json_schema_to_file_v3(out, contract_name, contract_counter, contract_schema)?
+                write_json_schema_to_file_v3(
+                    out,
+                    contract_name,
+                    contract_counter,
+                    contract_schema,
+                    format,
+                )?
             }
         }
     }
@@ -0,0 +1,504 @@
+//! Profiling of the interpreter energy spent by the functions of a contract.
+//!
+//! The module is instrumented so that every function reports when it is
+//! entered, and every call to a function of the module reports when it
+//! returns to the caller. From these reports, called probes, the call stack at
+//! any point of the execution is reconstructed, and the energy spent between
+//! two probes is attributed to the call stack at the first of them. The energy
+//! spent by the probes themselves is measured when the module is instrumented,
+//! and is not attributed to any function.
+//!
+//! Unit tests emit probes by calling a function handled by the
+//! [`InstrumentedHost`](crate::instrument::InstrumentedHost) running them.
+//! Calls run by `cargo concordium run` only have the host functions of the
+//! chain, so there probes are emitted as debug events using `debug_print`,
+//! which records the energy at the time it is called. Each call to
+//! `debug_print` by the module itself is preceded by a probe marking it, so
+//! that its debug event is never taken for a probe.
+use crate::{
+    context::InitContextOpt,
+    instrument::{
+        self, Instruction, InstrumentedHost, ProbeImport, ProbeWriter, Probes, TestLimits,
+        PROBE_COST_PLAIN, PROBE_COST_PROBE,
+    },
+};
+use anyhow::{bail, Context};
+use concordium_base::contracts_common::{Amount, OwnedParameter};
+use concordium_smart_contract_engine::{
+    utils::{NoDuplicateImport, TestHost},
+    v1::{self, trie, DebugTracker, InstanceState},
+    InterpreterEnergy,
+};
+use concordium_wasm::{
+    artifact::{ArtifactNamedImport, CompiledFunction},
+    machine::Value,
+    utils::instantiate_with_metering,
+    validate::ValidationConfig,
+    CostConfigurationV1,
+};
+use rand::{rngs::SmallRng, SeedableRng};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    io::Write,
+    path::Path,
+};
+
+/// The host function used for emitting probes in unit tests. It is not
+/// provided by the chain, but handled by the host running the unit tests.
+pub(crate) const PROBE: ProbeImport = ProbeImport {
+    module: "cargo_concordium",
+    name: "profile",
+    num_params: 2,
+};
+
+/// The host function used for emitting probes as debug events.
+const DEBUG_PRINT_PROBE: ProbeImport = ProbeImport {
+    module: "concordium",
+    name: "debug_print",
+    num_params: 6,
+};
+
+/// The kind of a probe emitted when a function is entered.
+const PROBE_ENTER: u32 = 0;
+/// The kind of a probe emitted when a call returns to the function.
+const PROBE_RESUME: u32 = 1;
+/// The kind of a probe emitted before the module itself calls the probe
+/// function.
+const PROBE_USER_CALL: u32 = 2;
+
+/// The interpreter energy available for measuring the energy spent by a
+/// probe.
+const PROBE_COST_ENERGY: u64 = 1_000_000;
+
+/// The number of functions listed in the summary of a profile.
+const SUMMARY_LENGTH: usize = 20;
+
+/// How an instrumented module emits probes.
+#[derive(Clone, Copy)]
+pub(crate) enum ProbeTransport {
+    /// By calling [`PROBE`], for running unit tests.
+    Host,
+    /// As debug events, for running the module with the host functions of the
+    /// chain.
+    DebugEvent,
+}
+
+impl ProbeTransport {
+    fn import(self) -> &'static ProbeImport {
+        match self {
+            Self::Host => &PROBE,
+            Self::DebugEvent => &DEBUG_PRINT_PROBE,
+        }
+    }
+
+    /// The arguments of a probe of the given kind emitted by the function. The
+    /// function and the kind are the line and column of a debug event.
+    fn args(self, function: u32, kind: u32) -> Vec<u32> {
+        match self {
+            Self::Host => vec![function, kind],
+            Self::DebugEvent => vec![0, 0, 0, 0, function, kind],
+        }
+    }
+
+    /// Measure the interpreter energy spent by a probe, by running a function
+    /// emitting a single probe and a function that does nothing.
+    fn probe_cost(self) -> anyhow::Result<u64> {
+        let module = instrument::probe_cost_module(self.import(), &self.args(0, PROBE_RESUME))?;
+        let with_probe = self.energy_used(&module, PROBE_COST_PROBE)?;
+        let without_probe = self.energy_used(&module, PROBE_COST_PLAIN)?;
+        Ok(with_probe.saturating_sub(without_probe))
+    }
+
+    /// The interpreter energy used by calling the init function of the module,
+    /// using the host that handles the probes of this transport.
+    fn energy_used(self, module: &[u8], name: &str) -> anyhow::Result<u64> {
+        match self {
+            Self::Host => {
+                let artifact = instantiate_with_metering::<ArtifactNamedImport, _>(
+                    ValidationConfig::V1,
+                    CostConfigurationV1,
+                    &NoDuplicateImport,
+                    module,
+                )?
+                .artifact;
+                let mut initial_state = trie::MutableState::initial_state();
+                let mut loader = trie::Loader::new(Vec::new());
+                let mut host = {
+                    let inner = initial_state.get_inner(&mut loader);
+                    let state = InstanceState::new(loader, inner);
+                    InstrumentedHost::new(
+                        TestHost::new(SmallRng::seed_from_u64(0), state),
+                        TestLimits::default(),
+                    )
+                };
+                artifact.run(&mut host, name, &[Value::I64(0)])?;
+                Ok(host.energy_used)
+            }
+            Self::DebugEvent => {
+                let parameter = OwnedParameter::empty();
+                let res = v1::invoke_init_with_metering_from_source::<_, DebugTracker>(
+                    v1::InvokeFromSourceCtx {
+                        source: module,
+                        amount: Amount::zero(),
+                        parameter: parameter.as_ref(),
+                        energy: InterpreterEnergy {
+                            energy: PROBE_COST_ENERGY,
+                        },
+                        support_upgrade: true,
+                    },
+                    InitContextOpt::default(),
+                    name,
+                    trie::Loader::new(&[][..]),
+                    ValidationConfig::V1,
+                    CostConfigurationV1,
+                    false,
+                )?;
+                match res {
+                    v1::InitResult::Success {
+                        remaining_energy, ..
+                    } => Ok(PROBE_COST_ENERGY.saturating_sub(remaining_energy.energy)),
+                    _ => bail!("The function emitting a probe did not succeed."),
+                }
+            }
+        }
+    }
+}
+
+/// A module instrumented for profiling.
+pub(crate) struct Instrumented {
+    /// The instrumented module.
+    pub module: Vec<u8>,
+    /// The names of the functions of the original module, by index.
+    names: BTreeMap<u32, String>,
+    /// The interpreter energy spent by a single probe.
+    probe_cost: u64,
+}
+
+/// Instrument a Wasm module for profiling, emitting probes using `transport`.
+/// The names of the functions are read from the name section of `symbols` if
+/// given, and otherwise from the name section of the module itself.
+pub(crate) fn instrument(
+    module: &[u8],
+    symbols: Option<&[u8]>,
+    transport: ProbeTransport,
+) -> anyhow::Result<Instrumented> {
+    let names = instrument::function_names(symbols.unwrap_or(module))?;
+    if names.is_empty() {
+        eprintln!(
+            "The module has no function names. Functions are shown by their index in the \
+             profile."
+        );
+    }
+    let module = instrument::instrument_module(
+        module,
+        transport.import(),
+        &mut ProfileProbes { transport },
+    )?;
+    let probe_cost = transport
+        .probe_cost()
+        .context("Could not measure the energy spent by a probe.")?;
+    Ok(Instrumented {
+        module,
+        names,
+        probe_cost,
+    })
+}
+
+/// Emits a probe when a function is entered, and when a call to a function
+/// of the module returns. Calls to host functions do not emit probes, so the
+/// caller does not need to be restored after them.
+struct ProfileProbes {
+    transport: ProbeTransport,
+}
+
+impl Probes for ProfileProbes {
+    fn function_start(&mut self, probe: &mut ProbeWriter, function: u32, _offset: usize) {
+        probe.emit(&self.transport.args(function, PROBE_ENTER));
+    }
+
+    fn before_instruction(
+        &mut self,
+        probe: &mut ProbeWriter,
+        function: u32,
+        instruction: &Instruction,
+    ) {
+        if instruction.calls_probe {
+            probe.emit(&self.transport.args(function, PROBE_USER_CALL));
+        }
+    }
+
+    fn after_instruction(
+        &mut self,
+        probe: &mut ProbeWriter,
+        function: u32,
+        instruction: &Instruction,
+    ) {
+        if instruction.calls_module_function {
+            probe.emit(&self.transport.args(function, PROBE_RESUME));
+        }
+    }
+}
+
+/// A probe emitted by an instrumented module.
+#[derive(Clone, Copy)]
+enum Probe {
+    /// The function was entered.
+    Enter(u32),
+    /// A call returned to the function.
+    Resume(u32),
+    /// The module itself calls the probe function next.
+    UserCall,
+}
+
+impl Probe {
+    /// Recognize the probes among the calls to the probe function, given by
+    /// their function and kind together with the energy used when they were
+    /// made. The call following a [`Probe::UserCall`] is made by the module
+    /// itself, and is not a probe.
+    fn recognize(calls: impl IntoIterator<Item = ([u32; 2], u64)>) -> Vec<(Self, u64)> {
+        let mut probes = Vec::new();
+        let mut user_call = false;
+        for ([function, kind], used) in calls {
+            if std::mem::take(&mut user_call) {
+                continue;
+            }
+            let probe = match kind {
+                PROBE_ENTER => Self::Enter(function),
+                PROBE_RESUME => Self::Resume(function),
+                PROBE_USER_CALL => {
+                    user_call = true;
+                    Self::UserCall
+                }
+                _ => continue,
+            };
+            probes.push((probe, used));
+        }
+        probes
+    }
+}
+
+/// The energy spent by a single function.
+#[derive(Default)]
+struct FunctionCost {
+    /// The energy spent in the function itself.
+    self_energy: u64,
+    /// The energy spent in the function and the functions it called.
+    total_energy: u64,
+    /// The number of times the function was called.
+    calls: u64,
+}
+
+/// The interpreter energy spent by an execution, attributed to functions.
+pub(crate) struct Profile<'a> {
+    instrumented: &'a Instrumented,
+    /// The energy spent by each call stack.
+    stacks: BTreeMap<Vec<u32>, u64>,
+    functions: BTreeMap<u32, FunctionCost>,
+    total: u64,
+}
+
+impl<'a> Profile<'a> {
+    /// Construct a profile from probes paired with the energy used when they
+    /// were emitted, and the energy used by the entire execution. The energy
+    /// spent by each probe is subtracted from the energy spent up to it, or
+    /// later if the probe was charged together with code after it.
+    fn new(instrumented: &'a Instrumented, probes: Vec<(Probe, u64)>, total: u64) -> Self {
+        let probe_cost = instrumented.probe_cost;
+        let mut profile = Self {
+            instrumented,
+            stacks: BTreeMap::new(),
+            functions: BTreeMap::new(),
+            total: total.saturating_sub(probe_cost.saturating_mul(probes.len() as u64)),
+        };
+        let mut stack = Vec::new();
+        let mut last = 0;
+        // The energy spent by probes that is not yet subtracted.
+        let mut pending = 0u64;
+        for (probe, used) in probes {
+            let spent = used.saturating_sub(last);
+            last = used;
+            pending = pending.saturating_add(probe_cost);
+            profile.charge(&stack, spent.saturating_sub(pending));
+            pending = pending.saturating_sub(spent);
+            match probe {
+                Probe::Enter(function) => {
+                    stack.push(function);
+                    profile.functions.entry(function).or_default().calls += 1;
+                }
+                Probe::Resume(function) => {
+                    while stack.last().is_some_and(|top| *top != function) {
+                        stack.pop();
+                    }
+                    if stack.is_empty() {
+                        stack.push(function);
+                    }
+                }
+                Probe::UserCall => {}
+            }
+        }
+        profile.charge(&stack, total.saturating_sub(last).saturating_sub(pending));
+        profile
+    }
+
+    /// Construct the profile of an execution from the debug events recorded
+    /// by the tracker.
+    fn from_trace(
+        instrumented: &'a Instrumented,
+        trace: &DebugTracker,
+        energy: InterpreterEnergy,
+        remaining_energy: Option<InterpreterEnergy>,
+    ) -> Self {
+        let calls = trace.emitted_events.iter().map(|(_, event)| {
+            (
+                [event.line, event.column],
+                energy.energy.saturating_sub(event.remaining_energy.energy),
+            )
+        });
+        let total = energy
+            .energy
+            .saturating_sub(remaining_energy.map_or(0, |e| e.energy));
+        Self::new(instrumented, Probe::recognize(calls), total)
+    }
+
+    /// Construct the profile of calling an init function of the instrumented
+    /// module.
+    pub(crate) fn from_init_result(
+        instrumented: &'a Instrumented,
+        res: &v1::InitResult<DebugTracker>,
+        energy: InterpreterEnergy,
+    ) -> Self {
+        let (trace, remaining_energy) = match res {
+            v1::InitResult::Success {
+                trace,
+                remaining_energy,
+                ..
+            }
+            | v1::InitResult::Reject {
+                trace,
+                remaining_energy,
+                ..
+            }
+            | v1::InitResult::Trap {
+                trace,
+                remaining_energy,
+                ..
+            } => (trace, Some(*remaining_energy)),
+            v1::InitResult::OutOfEnergy { trace } => (trace, None),
+        };
+        Self::from_trace(instrumented, trace, energy, remaining_energy)
+    }
+
+    /// Construct the profile of calling a receive function of the
+    /// instrumented module.
+    pub(crate) fn from_receive_result<Ctx>(
+        instrumented: &'a Instrumented,
+        res: &v1::ReceiveResult<CompiledFunction, DebugTracker, Ctx>,
+        energy: InterpreterEnergy,
+    ) -> Self {
+        let (trace, remaining_energy) = match res {
+            v1::ReceiveResult::Success {
+                trace,
+                remaining_energy,
+                ..
+            }
+            | v1::ReceiveResult::Reject {
+                trace,
+                remaining_energy,
+                ..
+            }
+            | v1::ReceiveResult::Trap {
+                trace,
+                remaining_energy,
+                ..
+            }
+            | v1::ReceiveResult::Interrupt {
+                trace,
+                remaining_energy,
+                ..
+            } => (trace, Some(*remaining_energy)),
+            v1::ReceiveResult::OutOfEnergy { trace } => (trace, None),
+        };
+        Self::from_trace(instrumented, trace, energy, remaining_energy)
+    }
+
+    /// Construct the profile of a unit test from the probes emitted by the
+    /// test, the energy used when each of them was emitted, and the energy used
+    /// by the entire test. These are recorded by the
+    /// [`InstrumentedHost`](crate::instrument::InstrumentedHost) running the
+    /// test.
+    pub(crate) fn from_test(
+        instrumented: &'a Instrumented,
+        probes: &[([u32; 2], u64)],
+        energy_used: u64,
+    ) -> Self {
+        Self::new(
+            instrumented,
+            Probe::recognize(probes.iter().copied()),
+            energy_used,
+        )
+    }
+
+    fn charge(&mut self, stack: &[u32], energy: u64) {
+        let Some(top) = stack.last() else {
+            return;
+        };
+        if energy == 0 {
+            return;
+        }
+        *self.stacks.entry(stack.to_vec()).or_default() += energy;
+        self.functions.entry(*top).or_default().self_energy += energy;
+        let distinct: BTreeSet<_> = stack.iter().collect();
+        for function in distinct {
+            self.functions.entry(*function).or_default().total_energy += energy;
+        }
+    }
+
+    fn name(&self, function: u32) -> String {
+        match self.instrumented.names.get(&function) {
+            Some(name) => name.replace(';', ":"),
+            None => format!("func[{}]", function),
+        }
+    }
+
+    /// Write the profile in the folded stack format used by flamegraph tools.
+    pub(crate) fn write_folded(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(out_dir) = path.parent() {
+            fs::create_dir_all(out_dir)
+                .context("Unable to create directory for the energy profile.")?;
+        }
+        let mut file = fs::File::create(path).with_context(|| {
+            format!(
+                "Could not create the energy profile file {}.",
+                path.display()
+            )
+        })?;
+        for (stack, energy) in self.stacks.iter() {
+            let names: Vec<_> = stack.iter().map(|function| self.name(*function)).collect();
+            writeln!(file, "{} {}", names.join(";"), energy)
+                .context("Could not write the energy profile.")?;
+        }
+        Ok(())
+    }
+
+    /// Print the functions that spent the most energy.
+    pub(crate) fn print_summary(&self) {
+        let mut functions: Vec<_> = self.functions.iter().collect();
+        functions.sort_by(|(_, a), (_, b)| b.self_energy.cmp(&a.self_energy));
+        eprintln!(
+            "\nInterpreter energy spent by function ({} in total, without the energy spent by \
+             the instrumentation for profiling):",
+            self.total
+        );
+        eprintln!("{:>12} {:>12} {:>8}  function", "self", "total", "calls");
+        for (function, cost) in functions.into_iter().take(SUMMARY_LENGTH) {
+            eprintln!(
+                "{:>12} {:>12} {:>8}  {}",
+                cost.self_energy,
+                cost.total_energy,
+                cost.calls,
+                self.name(*function)
+            );
+        }
+    }
+}
@@ -0,0 +1,756 @@
+//! Machine-readable reports of the result of running an init or receive
+//! function with `cargo concordium run`.
+use anyhow::Context;
+use concordium_base::contracts_common::{
+    schema::Type, AccountAddress, Amount, ContractAddress, OwnedEntrypointName,
+};
+use concordium_smart_contract_engine::{
+    v0,
+    v1::{self, DebugTracker},
+    InterpreterEnergy,
+};
+use concordium_wasm::artifact::CompiledFunction;
+use serde::Serialize;
+use std::{collections::BTreeMap, str::FromStr};
+
+/// The format the result of a run is output in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OutputFormat {
+    /// Human readable text.
+    Text,
+    /// A single JSON document printed to stdout.
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => anyhow::bail!("Unsupported output format '{}'. Use 'text' or 'json'.", s),
+        }
+    }
+}
+
+/// The schemas used for decoding the values produced by a call.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct ValueSchemas<'a> {
+    pub event: Option<&'a Type>,
+    pub return_value: Option<&'a Type>,
+    pub error: Option<&'a Type>,
+}
+
+/// The outcome of a call.
+#[derive(Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+enum Outcome {
+    Success,
+    Reject,
+    Trap,
+    OutOfEnergy,
+    Interrupt,
+}
+
+/// A value produced by a call. It is decoded to JSON if a schema is
+/// available.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ValueReport {
+    /// The value in hex.
+    hex: String,
+    /// The value decoded using the schema.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    json: Option<serde_json::Value>,
+}
+
+impl ValueReport {
+    fn new(bytes: &[u8], schema: Option<&Type>) -> Self {
+        let json = schema
+            .and_then(|schema| schema.to_json_string_pretty(bytes).ok())
+            .and_then(|s| serde_json::from_str(&s).ok());
+        Self {
+            hex: hex::encode(bytes),
+            json,
+        }
+    }
+}
+
+/// The number of calls to a host function, and the energy they spent.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HostCallReport {
+    count: usize,
+    energy: u64,
+}
+
+/// The interpreter energy spent by a call.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct EnergyReport {
+    initial: u64,
+    remaining: u64,
+    spent: u64,
+    /// The energy spent on executing Wasm instructions. Only for V1 contracts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    instructions: Option<u64>,
+    /// The energy spent on memory allocation. Only for V1 contracts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    memory_allocation: Option<u64>,
+    /// The calls to host functions by name. Only for V1 contracts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    host_calls: Option<BTreeMap<String, HostCallReport>>,
+}
+
+impl EnergyReport {
+    fn new(initial: InterpreterEnergy, remaining: Option<InterpreterEnergy>) -> Self {
+        let remaining = remaining.map_or(0, |remaining| remaining.energy);
+        Self {
+            initial: initial.energy,
+            remaining,
+            spent: initial.energy.saturating_sub(remaining),
+            instructions: None,
+            memory_allocation: None,
+            host_calls: None,
+        }
+    }
+
+    /// Add the breakdown of the spent energy recorded by the tracker, and
+    /// return the debug events emitted by the contract.
+    fn add_trace(&mut self, trace: &DebugTracker) -> Vec<String> {
+        let host_calls = trace
+            .host_call_summary()
+            .into_iter()
+            .map(|(host_fn, (count, energy))| {
+                (
+                    host_fn.to_string(),
+                    HostCallReport {
+                        count,
+                        energy: energy.energy,
+                    },
+                )
+            })
+            .collect();
+        self.instructions = Some(trace.operation.energy);
+        self.memory_allocation = Some(trace.memory_alloc.energy);
+        self.host_calls = Some(host_calls);
+        debug_events(trace)
+    }
+}
+
+/// The debug events emitted by the contract, as recorded by the tracker.
+fn debug_events(trace: &DebugTracker) -> Vec<String> {
+    trace
+        .emitted_events
+        .iter()
+        .map(|(_, event)| event.to_string())
+        .collect()
+}
+
+fn events(logs: &v0::Logs, schema_event: Option<&Type>) -> Vec<ValueReport> {
+    logs.iterate()
+        .map(|event| ValueReport::new(event, schema_event))
+        .collect()
+}
+
+/// The operation that interrupted the execution of a V1 contract.
+#[derive(Serialize)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub(crate) enum InterruptReport {
+    Transfer {
+        to: AccountAddress,
+        amount: Amount,
+    },
+    Call {
+        address: ContractAddress,
+        entrypoint: String,
+        amount: Amount,
+        /// The parameter in hex.
+        parameter: String,
+    },
+    Upgrade {
+        module_reference: String,
+    },
+    QueryAccountBalance {
+        address: AccountAddress,
+    },
+    QueryContractBalance {
+        address: ContractAddress,
+    },
+    QueryExchangeRates,
+    CheckAccountSignature {
+        address: AccountAddress,
+        /// The payload in hex.
+        payload: String,
+    },
+    QueryAccountKeys {
+        address: AccountAddress,
+    },
+    QueryContractModuleReference {
+        address: ContractAddress,
+    },
+    QueryContractName {
+        address: ContractAddress,
+    },
+}
+
+impl From<v1::Interrupt> for InterruptReport {
+    fn from(interrupt: v1::Interrupt) -> Self {
+        match interrupt {
+            v1::Interrupt::Transfer { to, amount } => Self::Transfer { to, amount },
+            v1::Interrupt::Call {
+                address,
+                parameter,
+                name,
+                amount,
+            } => Self::Call {
+                address,
+                entrypoint: name.to_string(),
+                amount,
+                parameter: hex::encode(parameter),
+            },
+            v1::Interrupt::Upgrade { module_ref } => Self::Upgrade {
+                module_reference: module_ref.to_string(),
+            },
+            v1::Interrupt::QueryAccountBalance { address } => Self::QueryAccountBalance { address },
+            v1::Interrupt::QueryContractBalance { address } => {
+                Self::QueryContractBalance { address }
+            }
+            v1::Interrupt::QueryExchangeRates => Self::QueryExchangeRates,
+            v1::Interrupt::CheckAccountSignature { address, payload } => {
+                Self::CheckAccountSignature {
+                    address,
+                    payload: hex::encode(payload),
+                }
+            }
+            v1::Interrupt::QueryAccountKeys { address } => Self::QueryAccountKeys { address },
+            v1::Interrupt::QueryContractModuleReference { address } => {
+                Self::QueryContractModuleReference { address }
+            }
+            v1::Interrupt::QueryContractName { address } => Self::QueryContractName { address },
+        }
+    }
+}
+
+/// An action produced by a V0 contract.
+#[derive(Serialize)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+enum ActionReport {
+    Send {
+        to: ContractAddress,
+        entrypoint: String,
+        amount: Amount,
+        /// The parameter in hex.
+        parameter: String,
+    },
+    SimpleTransfer {
+        to: AccountAddress,
+        amount: Amount,
+    },
+    And {
+        l: u32,
+        r: u32,
+    },
+    Or {
+        l: u32,
+        r: u32,
+    },
+    Accept,
+}
+
+impl From<&v0::Action> for ActionReport {
+    fn from(action: &v0::Action) -> Self {
+        match action {
+            v0::Action::Send { data } => Self::Send {
+                to: data.to_addr,
+                entrypoint: data.name.as_receive_name().entrypoint_name().to_string(),
+                amount: data.amount,
+                parameter: hex::encode(&data.parameter),
+            },
+            v0::Action::SimpleTransfer { data } => Self::SimpleTransfer {
+                to: data.to_addr,
+                amount: data.amount,
+            },
+            v0::Action::And { l, r } => Self::And { l: *l, r: *r },
+            v0::Action::Or { l, r } => Self::Or { l: *l, r: *r },
+            v0::Action::Accept => Self::Accept,
+        }
+    }
+}
+
+/// A step of simulating the interactions of a contract with the chain, i.e.,
+/// a call to a local contract or a query answered from the chain state, and
+/// the result of each call.
+#[derive(Serialize)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub(crate) enum ChainTraceEntry {
+    /// The contract `from` called the contract `to`. The events are those
+    /// produced by `from` before the call.
+    Call {
+        from: ContractAddress,
+        to: ContractAddress,
+        entrypoint: String,
+        amount: Amount,
+        /// The parameter in hex.
+        parameter: String,
+        events: Vec<ValueReport>,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        debug_events: Vec<String>,
+    },
+    /// The contract `from` queried the chain. The events are those produced
+    /// by `from` before the query.
+    Query {
+        from: ContractAddress,
+        query: InterruptReport,
+        events: Vec<ValueReport>,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        debug_events: Vec<String>,
+    },
+    CallSucceeded {
+        address: ContractAddress,
+        events: Vec<ValueReport>,
+        return_value: ValueReport,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        debug_events: Vec<String>,
+    },
+    CallRejected {
+        address: ContractAddress,
+        reject_reason: i32,
+        error_value: ValueReport,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        debug_events: Vec<String>,
+    },
+    CallTrapped {
+        address: ContractAddress,
+        error: String,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        debug_events: Vec<String>,
+    },
+    CallOutOfEnergy {
+        address: ContractAddress,
+    },
+    /// The call failed before the contract was executed, e.g., since it is
+    /// not in the contracts file.
+    CallFailed {
+        address: ContractAddress,
+        reason: String,
+    },
+}
+
+impl ChainTraceEntry {
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn call(
+        from: ContractAddress,
+        to: ContractAddress,
+        entrypoint: &OwnedEntrypointName,
+        amount: Amount,
+        parameter: &[u8],
+        logs: &v0::Logs,
+        schema_event: Option<&Type>,
+        trace: &DebugTracker,
+    ) -> Self {
+        Self::Call {
+            from,
+            to,
+            entrypoint: entrypoint.to_string(),
+            amount,
+            parameter: hex::encode(parameter),
+            events: events(logs, schema_event),
+            debug_events: debug_events(trace),
+        }
+    }
+
+    pub(crate) fn query(
+        from: ContractAddress,
+        query: v1::Interrupt,
+        logs: &v0::Logs,
+        schema_event: Option<&Type>,
+        trace: &DebugTracker,
+    ) -> Self {
+        Self::Query {
+            from,
+            query: query.into(),
+            events: events(logs, schema_event),
+            debug_events: debug_events(trace),
+        }
+    }
+
+    pub(crate) fn succeeded(
+        address: ContractAddress,
+        logs: &v0::Logs,
+        return_value: &[u8],
+        schemas: ValueSchemas,
+        trace: &DebugTracker,
+    ) -> Self {
+        Self::CallSucceeded {
+            address,
+            events: events(logs, schemas.event),
+            return_value: ValueReport::new(return_value, schemas.return_value),
+            debug_events: debug_events(trace),
+        }
+    }
+
+    pub(crate) fn rejected(
+        address: ContractAddress,
+        reason: i32,
+        error_value: &[u8],
+        schemas: ValueSchemas,
+        trace: &DebugTracker,
+    ) -> Self {
+        Self::CallRejected {
+            address,
+            reject_reason: reason,
+            error_value: ValueReport::new(error_value, schemas.error),
+            debug_events: debug_events(trace),
+        }
+    }
+
+    pub(crate) fn trapped(
+        address: ContractAddress,
+        error: &anyhow::Error,
+        trace: &DebugTracker,
+    ) -> Self {
+        Self::CallTrapped {
+            address,
+            error: format!("{:#}", error),
+            debug_events: debug_events(trace),
+        }
+    }
+}
+
+/// The result of running an init or receive function.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RunReport {
+    outcome: Outcome,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reject_reason: Option<i32>,
+    /// The runtime error, if the outcome is a trap.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    events: Vec<ValueReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    return_value: Option<ValueReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_value: Option<ValueReport>,
+    energy: EnergyReport,
+    /// Debug events emitted by the contract. Only for V1 contracts.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    debug_events: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state_changed: Option<bool>,
+    /// The size of the additional state the contract will be charged for.
+    /// Only for V1 contracts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    additional_state_size: Option<u64>,
+    /// The new state. Only for V0 contracts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state: Option<ValueReport>,
+    /// The actions produced. Only for V0 contracts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    actions: Option<Vec<ActionReport>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    interrupt: Option<InterruptReport>,
+    /// The calls to local contracts and the queries answered from the chain
+    /// state, in the order they were made. Only for V1 contracts.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    chain_trace: Vec<ChainTraceEntry>,
+}
+
+impl RunReport {
+    fn new(outcome: Outcome, energy: EnergyReport) -> Self {
+        Self {
+            outcome,
+            reject_reason: None,
+            error: None,
+            events: Vec::new(),
+            return_value: None,
+            error_value: None,
+            energy,
+            debug_events: Vec::new(),
+            state_changed: None,
+            additional_state_size: None,
+            state: None,
+            actions: None,
+            interrupt: None,
+            chain_trace: Vec::new(),
+        }
+    }
+
+    fn with_logs(mut self, logs: &v0::Logs, schema_event: Option<&Type>) -> Self {
+        self.events = events(logs, schema_event);
+        self
+    }
+
+    /// Add the interactions with the chain that were simulated.
+    pub(crate) fn with_chain_trace(mut self, chain_trace: Vec<ChainTraceEntry>) -> Self {
+        self.chain_trace = chain_trace;
+        self
+    }
+
+    fn with_trace(mut self, trace: &DebugTracker) -> Self {
+        self.debug_events = self.energy.add_trace(trace);
+        self
+    }
+
+    /// Print the report as JSON to stdout. If the call trapped, an error is
+    /// returned after printing, as when the result is printed as text.
+    pub(crate) fn print(&self) -> anyhow::Result<()> {
+        let json =
+            serde_json::to_string_pretty(self).context("Could not output the result as JSON.")?;
+        println!("{}", json);
+        if self.outcome == Outcome::Trap {
+            anyhow::bail!(
+                "Execution triggered a runtime error: {}",
+                self.error.as_deref().unwrap_or("unknown error")
+            );
+        }
+        Ok(())
+    }
+
+    /// Construct the report of calling an init function of a V1 contract.
+    /// `write_state` is used for outputting the state, and returns the size of
+    /// the additional state.
+    pub(crate) fn init_v1(
+        res: v1::InitResult<DebugTracker>,
+        energy: InterpreterEnergy,
+        schemas: ValueSchemas,
+        write_state: impl FnOnce(v1::trie::MutableState) -> anyhow::Result<u64>,
+    ) -> anyhow::Result<Self> {
+        let report = match res {
+            v1::InitResult::Success {
+                logs,
+                state,
+                remaining_energy,
+                return_value,
+                trace,
+            } => {
+                let mut report = Self::new(
+                    Outcome::Success,
+                    EnergyReport::new(energy, Some(remaining_energy)),
+                )
+                .with_logs(&logs, schemas.event)
+                .with_trace(&trace);
+                report.return_value = Some(ValueReport::new(&return_value, schemas.return_value));
+                report.state_changed = Some(true);
+                report.additional_state_size = Some(write_state(state)?);
+                report
+            }
+            v1::InitResult::Reject {
+                remaining_energy,
+                reason,
+                return_value,
+                trace,
+            } => {
+                let mut report = Self::new(
+                    Outcome::Reject,
+                    EnergyReport::new(energy, Some(remaining_energy)),
+                )
+                .with_trace(&trace);
+                report.reject_reason = Some(reason);
+                report.error_value = Some(ValueReport::new(&return_value, schemas.error));
+                report
+            }
+            v1::InitResult::Trap {
+                remaining_energy,
+                error,
+                trace,
+            } => {
+                let mut report = Self::new(
+                    Outcome::Trap,
+                    EnergyReport::new(energy, Some(remaining_energy)),
+                )
+                .with_trace(&trace);
+                report.error = Some(format!("{:#}", error));
+                report
+            }
+            v1::InitResult::OutOfEnergy { trace } => {
+                Self::new(Outcome::OutOfEnergy, EnergyReport::new(energy, None)).with_trace(&trace)
+            }
+        };
+        Ok(report)
+    }
+
+    /// Construct the report of calling a receive function of a V1 contract.
+    /// `write_state` is used for outputting the state if it changed, and
+    /// returns the size of the additional state.
+    pub(crate) fn receive_v1<Ctx>(
+        res: v1::ReceiveResult<CompiledFunction, DebugTracker, Ctx>,
+        energy: InterpreterEnergy,
+        schemas: ValueSchemas,
+        write_state: impl FnOnce() -> anyhow::Result<u64>,
+    ) -> anyhow::Result<Self> {
+        let report = match res {
+            v1::ReceiveResult::Success {
+                logs,
+                state_changed,
+                remaining_energy,
+                return_value,
+                trace,
+            } => {
+                let mut report = Self::new(
+                    Outcome::Success,
+                    EnergyReport::new(energy, Some(remaining_energy)),
+                )
+                .with_logs(&logs, schemas.event)
+                .with_trace(&trace);
+                report.return_value = Some(ValueReport::new(&return_value, schemas.return_value));
+                report.state_changed = Some(state_changed);
+                if state_changed {
+                    report.additional_state_size = Some(write_state()?);
+                }
+                report
+            }
+            v1::ReceiveResult::Reject {
+                remaining_energy,
+                reason,
+                return_value,
+                trace,
+            } => {
+                let mut report = Self::new(
+                    Outcome::Reject,
+                    EnergyReport::new(energy, Some(remaining_energy)),
+                )
+                .with_trace(&trace);
+                report.reject_reason = Some(reason);
+                report.error_value = Some(ValueReport::new(&return_value, schemas.error));
+                report
+            }
+            v1::ReceiveResult::Trap {
+                remaining_energy,
+                error,
+                trace,
+            } => {
+                let mut report = Self::new(
+                    Outcome::Trap,
+                    EnergyReport::new(energy, Some(remaining_energy)),
+                )
+                .with_trace(&trace);
+                report.error = Some(format!("{:#}", error));
+                report
+            }
+            v1::ReceiveResult::OutOfEnergy { trace } => {
+                Self::new(Outcome::OutOfEnergy, EnergyReport::new(energy, None)).with_trace(&trace)
+            }
+            v1::ReceiveResult::Interrupt {
+                remaining_energy,
+                state_changed,
+                logs,
+                config: _,
+                interrupt,
+                trace,
+            } => {
+                let mut report = Self::new(
+                    Outcome::Interrupt,
+                    EnergyReport::new(energy, Some(remaining_energy)),
+                )
+                .with_logs(&logs, schemas.event)
+                .with_trace(&trace);
+                report.state_changed = Some(state_changed);
+                if state_changed {
+                    report.additional_state_size = Some(write_state()?);
+                }
+                report.interrupt = Some(interrupt.into());
+                report
+            }
+        };
+        Ok(report)
+    }
+
+    /// Construct the report of calling an init function of a V0 contract.
+    /// `write_state` is used for outputting the state.
+    pub(crate) fn init_v0(
+        res: v0::InitResult,
+        energy: InterpreterEnergy,
+        schema_state: Option<&Type>,
+        write_state: impl FnOnce(&[u8]) -> anyhow::Result<()>,
+    ) -> anyhow::Result<Self> {
+        let report = match res {
+            v0::InitResult::Success {
+                logs,
+                state,
+                remaining_energy,
+            } => {
+                let mut report = Self::new(
+                    Outcome::Success,
+                    EnergyReport::new(energy, Some(remaining_energy)),
+                )
+                .with_logs(&logs, None);
+                write_state(&state.state)?;
+                report.state = Some(ValueReport::new(&state.state, schema_state));
+                report
+            }
+            v0::InitResult::Reject {
+                remaining_energy,
+                reason,
+            } => {
+                let mut report = Self::new(
+                    Outcome::Reject,
+                    EnergyReport::new(energy, Some(remaining_energy)),
+                );
+                report.reject_reason = Some(reason);
+                report
+            }
+            v0::InitResult::OutOfEnergy => {
+                Self::new(Outcome::OutOfEnergy, EnergyReport::new(energy, None))
+            }
+        };
+        Ok(report)
+    }
+
+    /// Construct the report of calling a receive function of a V0 contract.
+    /// `write_state` is used for outputting the state.
+    pub(crate) fn receive_v0(
+        res: v0::ReceiveResult,
+        energy: InterpreterEnergy,
+        schema_state: Option<&Type>,
+        write_state: impl FnOnce(&[u8]) -> anyhow::Result<()>,
+    ) -> anyhow::Result<Self> {
+        let report = match res {
+            v0::ReceiveResult::Success {
+                logs,
+                state,
+                actions,
+                remaining_energy,
+            } => {
+                let mut report = Self::new(
+                    Outcome::Success,
+                    EnergyReport::new(energy, Some(remaining_energy)),
+                )
+                .with_logs(&logs, None);
+                write_state(&state.state)?;
+                report.state = Some(ValueReport::new(&state.state, schema_state));
+                report.actions = Some(actions.iter().map(ActionReport::from).collect());
+                report
+            }
+            v0::ReceiveResult::Reject {
+                remaining_energy,
+                reason,
+            } => {
+                let mut report = Self::new(
+                    Outcome::Reject,
+                    EnergyReport::new(energy, Some(remaining_energy)),
+                );
+                report.reject_reason = Some(reason);
+                report
+            }
+            v0::ReceiveResult::OutOfEnergy => {
+                Self::new(Outcome::OutOfEnergy, EnergyReport::new(energy, None))
+            }
+        };
+        Ok(report)
+    }
+}
@@ -0,0 +1,755 @@
+//! Running a sequence of init and update calls against a single evolving
+//! contract instance, as described by a scenario file.
+use crate::{
+    context::{InitContextOpt, ReceiveContextV1Opt},
+    contract_schema::{contract_schemas, ContractSchema, FunctionSchema},
+    display_state, get_module_schema_v1, print_debug, print_error_value, print_interrupt,
+    print_logs_v1, print_return_value, read_state_from_file, resolve_receive_name, ScenarioOptions,
+};
+use ansi_term::{Color, Style};
+use anyhow::{ensure, Context};
+use concordium_base::contracts_common::{schema::Type, to_bytes, Amount, OwnedParameter};
+use concordium_smart_contract_engine::{
+    v1::{self, DebugTracker, ReturnValue},
+    InterpreterEnergy,
+};
+use concordium_wasm::{
+    artifact::{Artifact, CompiledFunction},
+    validate::ValidationConfig,
+    CostConfigurationV1,
+};
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// The interpreter energy used for steps that neither specify energy
+/// themselves nor have a default specified in the scenario. This matches the
+/// default of `cargo concordium run`.
+const DEFAULT_STEP_ENERGY: u64 = 1_000_000;
+
+/// A scenario file. It describes a sequence of steps that are executed in
+/// order against a single contract instance.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Scenario {
+    /// The contract used by steps that do not specify one.
+    contract: Option<String>,
+    /// The interpreter energy used by steps that do not specify it.
+    energy: Option<u64>,
+    /// The steps to execute.
+    steps: Vec<Step>,
+}
+
+/// The kind of call a step makes.
+#[derive(Deserialize)]
+#[serde(tag = "action", rename_all = "camelCase")]
+enum StepAction {
+    /// Initialize a new instance. This replaces the current state.
+    Init,
+    /// Invoke an entrypoint of the current instance.
+    Update { entrypoint: String },
+}
+
+/// A single step of a scenario.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Step {
+    /// An optional label that is used when reporting on the step.
+    name: Option<String>,
+    #[serde(flatten)]
+    action: StepAction,
+    /// The contract to call, overriding the scenario default.
+    contract: Option<String>,
+    /// The amount of CCD to invoke the function with, in micro CCD.
+    amount: Option<Amount>,
+    /// The parameter as JSON. This requires a schema for the parameter.
+    parameter: Option<serde_json::Value>,
+    /// Path to a file with the parameter in binary, relative to the scenario
+    /// file.
+    parameter_bin: Option<PathBuf>,
+    /// The interpreter energy to invoke the function with.
+    energy: Option<u64>,
+    /// The init or receive context, in the same format as the context files
+    /// used by `cargo concordium run`.
+    context: Option<serde_json::Value>,
+    /// The expected result of the step.
+    expect: Option<Expectation>,
+}
+
+impl Step {
+    /// A description of the step used as a heading when reporting on it.
+    fn describe(&self, contract_name: &str) -> String {
+        let call = match &self.action {
+            StepAction::Init => format!("init '{contract_name}'"),
+            StepAction::Update { entrypoint } => format!("update '{contract_name}.{entrypoint}'"),
+        };
+        match &self.name {
+            Some(name) => format!("{name} ({call})"),
+            None => call,
+        }
+    }
+}
+
+/// The possible outcomes of executing a step.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+enum Outcome {
+    Success,
+    Reject,
+    OutOfEnergy,
+    Trap,
+    Interrupt,
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Outcome::Success => f.write_str("success"),
+            Outcome::Reject => f.write_str("reject"),
+            Outcome::OutOfEnergy => f.write_str("outOfEnergy"),
+            Outcome::Trap => f.write_str("trap"),
+            Outcome::Interrupt => f.write_str("interrupt"),
+        }
+    }
+}
+
+/// The expected result of a step. Only the fields that are present are
+/// checked.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Expectation {
+    outcome: Option<Outcome>,
+    reject_reason: Option<i32>,
+    state_changed: Option<bool>,
+    /// The expected return value as JSON. This requires a schema for the
+    /// return value.
+    return_value: Option<serde_json::Value>,
+    /// The expected error value as JSON. This requires a schema for the error.
+    error: Option<serde_json::Value>,
+}
+
+/// The result of executing a single step.
+struct StepResult {
+    outcome: Outcome,
+    reject_reason: Option<i32>,
+    state_changed: bool,
+    return_value: Option<ReturnValue>,
+    error_value: Option<ReturnValue>,
+    /// The interpreter energy spent by the step.
+    energy_used: u64,
+    /// The size of the parameter as serialized in a transaction.
+    parameter_size: usize,
+}
+
+/// The state of the contract instance that evolves over the scenario.
+struct ScenarioRunner<'a> {
+    /// The source of the module, used for init calls.
+    module: &'a [u8],
+    /// The artifact of the module, used for update calls.
+    artifact: Arc<Artifact<v1::ProcessedImports, CompiledFunction>>,
+    /// The schemas of the contracts in the module, if any schema is available.
+    schemas: Option<BTreeMap<String, ContractSchema>>,
+    /// The current state of the instance. This is `None` until the instance
+    /// is initialized.
+    state: Option<v1::trie::PersistentState>,
+    /// The current balance of the instance.
+    balance: Amount,
+    /// Whether to print the debug information after each step.
+    emit_debug: bool,
+    /// The directory of the scenario file that relative paths in the scenario
+    /// are resolved against.
+    base_dir: PathBuf,
+}
+
+/// A step of a scenario that was executed.
+pub(crate) struct ExecutedStep {
+    /// The description of the step used when reporting on it.
+    pub description: String,
+    /// The contract that was called.
+    pub contract_name: String,
+    /// The entrypoint that was invoked, or `None` for init steps.
+    pub entrypoint: Option<String>,
+    /// The size of the parameter as serialized in a transaction.
+    pub parameter_size: usize,
+    /// The interpreter energy spent by the step.
+    pub energy_used: u64,
+}
+
+/// Run the scenario described by the options against the module, and return
+/// the steps that were executed. The module must be a V1 module.
+pub(crate) fn run_scenario(
+    options: ScenarioOptions,
+    module: &[u8],
+) -> anyhow::Result<Vec<ExecutedStep>> {
+    let scenario = read_scenario(&options.scenario)?;
+    let module_schema = get_module_schema_v1(options.schema_path.as_deref(), module)?;
+
+    let artifact = concordium_wasm::utils::instantiate_with_metering(
+        ValidationConfig::V1,
+        CostConfigurationV1,
+        &v1::ConcordiumAllowedImports {
+            support_upgrade: true,
+            enable_debug: true,
+        },
+        module,
+    )?
+    .artifact;
+
+    let state = match &options.state_bin_path {
+        Some(path) => Some(read_state_from_file(path)?),
+        None => None,
+    };
+
+    let mut runner = ScenarioRunner {
+        module,
+        artifact: Arc::new(artifact),
+        schemas: module_schema.as_ref().map(contract_schemas),
+        state,
+        balance: Amount::from_micro_ccd(options.balance.unwrap_or_default()),
+        emit_debug: options.emit_debug,
+        base_dir: options
+            .scenario
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default(),
+    };
+
+    let num_steps = scenario.steps.len();
+    let mut executed = Vec::with_capacity(num_steps);
+    for (i, step) in scenario.steps.iter().enumerate() {
+        let contract_name = step
+            .contract
+            .as_deref()
+            .or(scenario.contract.as_deref())
+            .with_context(|| {
+                format!(
+                    "Step {} does not specify a contract, and the scenario has no default \
+                     contract.",
+                    i + 1
+                )
+            })?;
+        let description = step.describe(contract_name);
+        eprintln!(
+            "\n{} {}",
+            Style::new()
+                .bold()
+                .paint(format!("Step {}/{}:", i + 1, num_steps)),
+            description
+        );
+        let energy = InterpreterEnergy {
+            energy: step
+                .energy
+                .or(scenario.energy)
+                .unwrap_or(DEFAULT_STEP_ENERGY),
+        };
+        let result = runner
+            .run_step(step, contract_name, energy)
+            .with_context(|| format!("Step {} ({}) could not be executed.", i + 1, description))?;
+        let entrypoint = match &step.action {
+            StepAction::Init => None,
+            StepAction::Update { entrypoint } => Some(entrypoint.as_str()),
+        };
+        if let Some(expect) = &step.expect {
+            let func_schema = runner
+                .contract_schema(contract_name)
+                .and_then(|s| s.function(entrypoint));
+            check_expectation(expect, &result, func_schema).with_context(|| {
+                format!(
+                    "Step {} ({}) did not produce the expected result.",
+                    i + 1,
+                    description
+                )
+            })?;
+            eprintln!(
+                "{}",
+                Color::Green.paint("The step produced the expected result.")
+            );
+        }
+        executed.push(ExecutedStep {
+            description,
+            contract_name: contract_name.to_string(),
+            entrypoint: entrypoint.map(str::to_string),
+            parameter_size: result.parameter_size,
+            energy_used: result.energy_used,
+        });
+    }
+
+    eprintln!(
+        "\n{}",
+        Color::Green.bold().paint(format!(
+            "Scenario finished. All {num_steps} steps were executed."
+        ))
+    );
+
+    if let Some(state) = &runner.state {
+        let mut loader = v1::trie::Loader::new(&[][..]);
+        if let Some(file_path) = &options.out_bin {
+            let mut out_file = std::fs::File::create(file_path)
+                .context("Could not create file to write state into.")?;
+            state
+                .serialize(&mut loader, &mut out_file)
+                .context("Could not write the state.")?;
+            eprintln!("Resulting state written to {}.", file_path.display());
+        }
+        if options.should_display_state {
+            display_state(state)?;
+        }
+    } else if options.out_bin.is_some() || options.should_display_state {
+        eprintln!("No contract instance was initialized, so there is no state to output.");
+    }
+    Ok(executed)
+}
+
+/// Read and parse a scenario file. Files with the `toml` extension are parsed
+/// as TOML, all other files are parsed as JSON.
+fn read_scenario(path: &Path) -> anyhow::Result<Scenario> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Could not read scenario file {}.", path.display()))?;
+    let scenario = if path.extension().is_some_and(|ext| ext == "toml") {
+        toml::from_str(&contents).context("Could not parse the scenario file as TOML.")?
+    } else {
+        serde_json::from_str(&contents).context("Could not parse the scenario file as JSON.")?
+    };
+    Ok(scenario)
+}
+
+impl ScenarioRunner<'_> {
+    /// Get the schema of the contract, if any schema is available.
+    fn contract_schema(&self, contract_name: &str) -> Option<&ContractSchema> {
+        self.schemas.as_ref()?.get(contract_name)
+    }
+
+    /// Construct the parameter of the step, either from a binary file or from
+    /// JSON using the schema.
+    fn get_parameter(
+        &self,
+        step: &Step,
+        contract_name: &str,
+        entrypoint: Option<&str>,
+    ) -> anyhow::Result<OwnedParameter> {
+        match (&step.parameter_bin, &step.parameter) {
+            (Some(_), Some(_)) => {
+                anyhow::bail!("Only one of `parameter` and `parameterBin` can be specified.")
+            }
+            (Some(path), None) => {
+                let path = self.base_dir.join(path);
+                let bytes = fs::read(&path).with_context(|| {
+                    format!("Could not read parameter file {}.", path.display())
+                })?;
+                Ok(OwnedParameter::new_unchecked(bytes))
+            }
+            (None, Some(json)) => {
+                let contract_schema = self.contract_schema(contract_name).context(
+                    "No schema found for contract, a schema is required for using a JSON \
+                     parameter. Either embed the schema in the module or provide it using the \
+                     `--schema` option.",
+                )?;
+                let parameter_schema = contract_schema
+                    .function(entrypoint)
+                    .and_then(|f| f.parameter.as_ref())
+                    .context("Contract schema did not contain a schema for this parameter.")?;
+                let mut parameter_bytes = Vec::new();
+                parameter_schema
+                    .serial_value_into(json, &mut parameter_bytes)
+                    .context("Could not generate parameter bytes using schema and JSON.")?;
+                Ok(OwnedParameter::new_unchecked(parameter_bytes))
+            }
+            (None, None) => Ok(OwnedParameter::empty()),
+        }
+    }
+
+    /// Execute a single step and print its result.
+    fn run_step(
+        &mut self,
+        step: &Step,
+        contract_name: &str,
+        energy: InterpreterEnergy,
+    ) -> anyhow::Result<StepResult> {
+        let amount = step.amount.unwrap_or_default();
+        let func_schema = match &step.action {
+            StepAction::Init => self
+                .contract_schema(contract_name)
+                .and_then(|s| s.function(None)),
+            StepAction::Update { entrypoint } => self
+                .contract_schema(contract_name)
+                .and_then(|s| s.function(Some(entrypoint))),
+        };
+        let schema_return_value = func_schema.and_then(|f| f.return_value.clone());
+        let schema_error = func_schema.and_then(|f| f.error.clone());
+        let schema_event = self
+            .contract_schema(contract_name)
+            .and_then(|s| s.event.clone());
+        match &step.action {
+            StepAction::Init => {
+                let parameter = self.get_parameter(step, contract_name, None)?;
+                let init_ctx: InitContextOpt = match &step.context {
+                    Some(ctx) => serde_json::from_value(ctx.clone())
+                        .context("Could not parse init context.")?,
+                    None => InitContextOpt::default(),
+                };
+                let name = format!("init_{}", contract_name);
+                // empty initial backing store.
+                let mut loader = v1::trie::Loader::new(&[][..]);
+                let res = v1::invoke_init_with_metering_from_source::<_, DebugTracker>(
+                    v1::InvokeFromSourceCtx {
+                        source: self.module,
+                        amount,
+                        parameter: parameter.as_ref(),
+                        energy,
+                        support_upgrade: true, // Upgrades are supported in PV5 and onward.
+                    },
+                    init_ctx,
+                    &name,
+                    loader,
+                    ValidationConfig::V1,
+                    CostConfigurationV1,
+                    false, /* Whether number of logs and size of return values should be
+                            * limited. Limits removed in PV5. */
+                )
+                .context("Initialization failed due to a runtime error.")?;
+                let (mut result, trace, remaining_energy) = match res {
+                    v1::InitResult::Success {
+                        logs,
+                        state,
+                        remaining_energy,
+                        return_value,
+                        trace,
+                    } => {
+                        eprintln!("Init call succeeded. The following logs were produced:");
+                        print_logs_v1(logs, schema_event.as_ref());
+                        let mut collector = v1::trie::SizeCollector::default();
+                        self.state = Some(state.freeze(&mut loader, &mut collector));
+                        self.balance = amount;
+                        eprintln!("The following return value was returned:");
+                        print_return_value(return_value.clone(), schema_return_value.as_ref())?;
+                        eprintln!(
+                            "Interpreter energy spent is {}",
+                            energy.subtract(remaining_energy.energy)
+                        );
+                        let result = StepResult {
+                            outcome: Outcome::Success,
+                            reject_reason: None,
+                            state_changed: true,
+                            return_value: Some(return_value),
+                            error_value: None,
+                            energy_used: 0,
+                            parameter_size: 0,
+                        };
+                        (result, trace, Some(remaining_energy))
+                    }
+                    v1::InitResult::Reject {
+                        remaining_energy,
+                        reason,
+                        return_value,
+                        trace,
+                    } => {
+                        eprintln!("Init call rejected with reason {}.", reason);
+                        eprintln!("The following error value was returned:");
+                        print_error_value(return_value.clone(), schema_error.as_ref())?;
+                        eprintln!(
+                            "Interpreter energy spent is {}",
+                            energy.subtract(remaining_energy.energy)
+                        );
+                        (
+                            StepResult::rejected(reason, return_value),
+                            trace,
+                            Some(remaining_energy),
+                        )
+                    }
+                    v1::InitResult::Trap {
+                        remaining_energy,
+                        error,
+                        trace,
+                    } => {
+                        eprintln!(
+                            "Execution triggered a runtime error after spending {} interpreter \
+                             energy: {:#}",
+                            energy.subtract(remaining_energy.energy),
+                            error
+                        );
+                        (
+                            StepResult::without_value(Outcome::Trap),
+                            trace,
+                            Some(remaining_energy),
+                        )
+                    }
+                    v1::InitResult::OutOfEnergy { trace } => {
+                        eprintln!("Init call terminated with out of energy.");
+                        (StepResult::without_value(Outcome::OutOfEnergy), trace, None)
+                    }
+                };
+                result.energy_used = energy
+                    .energy
+                    .saturating_sub(remaining_energy.map_or(0, |remaining| remaining.energy));
+                result.parameter_size = to_bytes(&parameter).len();
+                if self.emit_debug {
+                    print_debug(trace);
+                }
+                Ok(result)
+            }
+            StepAction::Update { entrypoint } => {
+                let parameter = self.get_parameter(step, contract_name, Some(entrypoint))?;
+                let mut receive_ctx: ReceiveContextV1Opt = match &step.context {
+                    Some(ctx) => serde_json::from_value(ctx.clone())
+                        .context("Could not parse receive context.")?,
+                    None => ReceiveContextV1Opt::default(),
+                };
+                // Unless the context states otherwise, the balance of the instance is the
+                // one tracked over the scenario.
+                if receive_ctx.common.self_balance.is_none() {
+                    receive_ctx.common.self_balance = Some(self.balance);
+                }
+                let init_state = self.state.as_ref().context(
+                    "There is no contract instance to update. Start the scenario with an init \
+                     step or provide the initial state using `--state-bin`.",
+                )?;
+                let name = resolve_receive_name(&self.artifact, contract_name, entrypoint)?;
+
+                let mut loader = v1::trie::Loader::new(&[][..]);
+                let mut mutable_state = init_state.thaw();
+                let inner = mutable_state.get_inner(&mut loader);
+                let instance_state = v1::InstanceState::new(loader, inner);
+                let res = v1::invoke_receive::<
+                    _,
+                    _,
+                    _,
+                    _,
+                    ReceiveContextV1Opt,
+                    ReceiveContextV1Opt,
+                    DebugTracker,
+                >(
+                    Arc::clone(&self.artifact),
+                    receive_ctx,
+                    v1::ReceiveInvocation {
+                        amount,
+                        receive_name: name.as_receive_name(),
+                        parameter: parameter.as_ref(),
+                        energy,
+                    },
+                    instance_state,
+                    v1::ReceiveParams::new_p6(),
+                )
+                .context("Calling receive failed.")?;
+                let (mut result, trace, remaining_energy) = match res {
+                    v1::ReceiveResult::Success {
+                        logs,
+                        state_changed,
+                        remaining_energy,
+                        return_value,
+                        trace,
+                    } => {
+                        eprintln!("Receive method succeeded. The following logs were produced.");
+                        print_logs_v1(logs, schema_event.as_ref());
+                        if state_changed {
+                            let mut collector = v1::trie::SizeCollector::default();
+                            self.state = Some(mutable_state.freeze(&mut loader, &mut collector));
+                            eprintln!("The state of the contract was updated.");
+                        } else {
+                            eprintln!("The state of the contract did not change.");
+                        }
+                        self.balance = self.balance.checked_add(amount).with_context(|| {
+                            format!(
+                                "Adding {} to the balance {} of the contract overflows.",
+                                amount, self.balance
+                            )
+                        })?;
+                        eprintln!("The following return value was returned:");
+                        print_return_value(return_value.clone(), schema_return_value.as_ref())?;
+                        eprintln!(
+                            "Interpreter energy spent is {}",
+                            energy.signed_diff(remaining_energy)
+                        );
+                        let result = StepResult {
+                            outcome: Outcome::Success,
+                            reject_reason: None,
+                            state_changed,
+                            return_value: Some(return_value),
+                            error_value: None,
+                            energy_used: 0,
+                            parameter_size: 0,
+                        };
+                        (result, trace, Some(remaining_energy))
+                    }
+                    v1::ReceiveResult::Reject {
+                        remaining_energy,
+                        reason,
+                        return_value,
+                        trace,
+                    } => {
+                        eprintln!("Receive call rejected with reason {}", reason);
+                        eprintln!("The following error value was returned:");
+                        print_error_value(return_value.clone(), schema_error.as_ref())?;
+                        eprintln!(
+                            "Interpreter energy spent is {}",
+                            energy.signed_diff(remaining_energy)
+                        );
+                        (
+                            StepResult::rejected(reason, return_value),
+                            trace,
+                            Some(remaining_energy),
+                        )
+                    }
+                    v1::ReceiveResult::OutOfEnergy { trace } => {
+                        eprintln!("Receive call terminated with: out of energy.");
+                        (StepResult::without_value(Outcome::OutOfEnergy), trace, None)
+                    }
+                    v1::ReceiveResult::Interrupt {
+                        remaining_energy,
+                        state_changed: _,
+                        logs,
+                        config: _,
+                        interrupt,
+                        trace,
+                    } => {
+                        eprintln!(
+                            "Receive method was interrupted. The following logs were produced by \
+                             the time of the interrupt."
+                        );
+                        print_logs_v1(logs, schema_event.as_ref());
+                        print_interrupt(&interrupt);
+                        eprintln!(
+                            "The execution cannot be resumed, so any changes to the state are \
+                             discarded."
+                        );
+                        eprintln!(
+                            "Interpreter energy spent is {}",
+                            energy.signed_diff(remaining_energy)
+                        );
+                        (
+                            StepResult::without_value(Outcome::Interrupt),
+                            trace,
+                            Some(remaining_energy),
+                        )
+                    }
+                    v1::ReceiveResult::Trap {
+                        remaining_energy,
+                        error,
+                        trace,
+                    } => {
+                        eprintln!(
+                            "Execution triggered a runtime error after spending {} interpreter \
+                             energy: {:#}",
+                            energy.signed_diff(remaining_energy),
+                            error
+                        );
+                        (
+                            StepResult::without_value(Outcome::Trap),
+                            trace,
+                            Some(remaining_energy),
+                        )
+                    }
+                };
+                result.energy_used = energy
+                    .energy
+                    .saturating_sub(remaining_energy.map_or(0, |remaining| remaining.energy));
+                result.parameter_size = to_bytes(&parameter).len();
+                if self.emit_debug {
+                    print_debug(trace);
+                }
+                Ok(result)
+            }
+        }
+    }
+}
+
+impl StepResult {
+    /// The result of a call that was rejected with the given reason and error
+    /// value.
+    fn rejected(reason: i32, error_value: ReturnValue) -> Self {
+        Self {
+            outcome: Outcome::Reject,
+            reject_reason: Some(reason),
+            state_changed: false,
+            return_value: None,
+            error_value: Some(error_value),
+            energy_used: 0,
+            parameter_size: 0,
+        }
+    }
+
+    /// The result of a call that produced neither a return nor an error value.
+    fn without_value(outcome: Outcome) -> Self {
+        Self {
+            outcome,
+            reject_reason: None,
+            state_changed: false,
+            return_value: None,
+            error_value: None,
+            energy_used: 0,
+            parameter_size: 0,
+        }
+    }
+}
+
+/// Check that the result of a step matches the expectation.
+fn check_expectation(
+    expect: &Expectation,
+    result: &StepResult,
+    func_schema: Option<&FunctionSchema>,
+) -> anyhow::Result<()> {
+    if let Some(outcome) = expect.outcome {
+        ensure!(
+            outcome == result.outcome,
+            "Expected the outcome '{}', but the outcome was '{}'.",
+            outcome,
+            result.outcome
+        );
+    }
+    if let Some(reason) = expect.reject_reason {
+        ensure!(
+            result.reject_reason == Some(reason),
+            "Expected the call to be rejected with reason {}.",
+            reason
+        );
+    }
+    if let Some(state_changed) = expect.state_changed {
+        ensure!(
+            result.state_changed == state_changed,
+            "Expected the state to {}change.",
+            if state_changed { "" } else { "not " }
+        );
+    }
+    if let Some(expected) = &expect.return_value {
+        check_value(
+            "return value",
+            expected,
+            result.return_value.as_ref(),
+            func_schema.and_then(|f| f.return_value.as_ref()),
+        )?;
+    }
+    if let Some(expected) = &expect.error {
+        check_value(
+            "error value",
+            expected,
+            result.error_value.as_ref(),
+            func_schema.and_then(|f| f.error.as_ref()),
+        )?;
+    }
+    Ok(())
+}
+
+/// Check that the value produced by a call matches the expected JSON value.
+/// The produced value is converted to JSON using the schema.
+fn check_value(
+    what: &str,
+    expected: &serde_json::Value,
+    actual: Option<&ReturnValue>,
+    schema: Option<&Type>,
+) -> anyhow::Result<()> {
+    let actual = actual.with_context(|| format!("Expected a {what}, but none was produced."))?;
+    let schema =
+        schema.with_context(|| format!("A schema for the {what} is required to check it."))?;
+    let actual_json = schema
+        .to_json_string_pretty(actual)
+        .map_err(|_| anyhow::anyhow!("Could not output the {what} in JSON."))?;
+    let actual_json: serde_json::Value = serde_json::from_str(&actual_json)?;
+    ensure!(
+        &actual_json == expected,
+        "Expected the {what} to be {expected}, but it was {actual_json}."
+    );
+    Ok(())
+}
@@ -0,0 +1,327 @@
+//! A check of whether a new module schema is compatible with an old one, for
+//! `cargo concordium schema-compat`, e.g., before upgrading a contract.
+//!
+//! A change is breaking if existing users of the contract, such as frontends
+//! using the old schema, can no longer interact with it. Removing contracts,
+//! functions or types from the schema is breaking, while adding them is not.
+//! Parameters may be extended with new enum variants, since all parameters
+//! that were valid before remain valid. Any other change of a type is
+//! breaking, including extending the types of return values, errors, events
+//! and the state, since values of the new type cannot be read with the old
+//! schema.
+use crate::{
+    contract_schema::{contract_schemas, enum_tag_size, ContractSchema},
+    report::OutputFormat,
+};
+use ansi_term::{Color, Style};
+use anyhow::Context;
+use concordium_base::contracts_common::{
+    schema::{Fields, Type, VersionedModuleSchema},
+    to_bytes,
+};
+use serde::Serialize;
+use std::collections::BTreeSet;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+enum Severity {
+    Breaking,
+    NonBreaking,
+}
+
+/// A change of the schema of a contract.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Finding {
+    severity: Severity,
+    contract: String,
+    /// The function the change belongs to, `init` or the name of an
+    /// entrypoint, or `None` for changes of the whole contract and of its
+    /// event and state types.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    function: Option<String>,
+    /// The type that changed: `parameter`, `return value`, `error`, `event` or
+    /// `state`, or `None` if the contract or function itself was added or
+    /// removed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    part: Option<&'static str>,
+    change: &'static str,
+}
+
+impl Finding {
+    fn describe(&self) -> String {
+        match (&self.function, self.part) {
+            (None, None) => format!("contract '{}' {}", self.contract, self.change),
+            (Some(function), None) => format!(
+                "function '{}' of contract '{}' {}",
+                function, self.contract, self.change
+            ),
+            (Some(function), Some(part)) => format!(
+                "{} of function '{}' of contract '{}' {}",
+                part, function, self.contract, self.change
+            ),
+            (None, Some(part)) => format!(
+                "{} type of contract '{}' {}",
+                part, self.contract, self.change
+            ),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Compatibility {
+    compatible: bool,
+    findings: Vec<Finding>,
+}
+
+/// Check whether the schema `new` is compatible with the schema `old`, and
+/// print the changes with the verdict. Fails if the schemas are not
+/// compatible.
+pub(crate) fn check_compatibility(
+    old: &VersionedModuleSchema,
+    new: &VersionedModuleSchema,
+    output_format: OutputFormat,
+) -> anyhow::Result<()> {
+    let old = contract_schemas(old);
+    let new = contract_schemas(new);
+
+    let mut findings = Vec::new();
+    for (name, old_contract) in old.iter() {
+        match new.get(name) {
+            Some(new_contract) => {
+                contract_findings(name, old_contract, new_contract, &mut findings)
+            }
+            None => findings.push(Finding {
+                severity: Severity::Breaking,
+                contract: name.clone(),
+                function: None,
+                part: None,
+                change: "removed",
+            }),
+        }
+    }
+    for name in new.keys().filter(|name| !old.contains_key(*name)) {
+        findings.push(Finding {
+            severity: Severity::NonBreaking,
+            contract: name.clone(),
+            function: None,
+            part: None,
+            change: "added",
+        });
+    }
+
+    let compatibility = Compatibility {
+        compatible: findings
+            .iter()
+            .all(|finding| finding.severity == Severity::NonBreaking),
+        findings,
+    };
+    match output_format {
+        OutputFormat::Json => {
+            let json = serde_json::to_string_pretty(&compatibility)
+                .context("Could not output the compatibility check as JSON.")?;
+            println!("{}", json);
+        }
+        OutputFormat::Text => compatibility.print(),
+    }
+    if !compatibility.compatible {
+        anyhow::bail!("The new schema is not compatible with the old one.");
+    }
+    Ok(())
+}
+
+/// The changes of a contract present in both schemas.
+fn contract_findings(
+    contract: &str,
+    old: &ContractSchema,
+    new: &ContractSchema,
+    findings: &mut Vec<Finding>,
+) {
+    let mut push = |function: Option<&str>,
+                    part: Option<&'static str>,
+                    (severity, change): (Severity, &'static str)| {
+        findings.push(Finding {
+            severity,
+            contract: contract.to_string(),
+            function: function.map(str::to_string),
+            part,
+            change,
+        })
+    };
+
+    let mut functions: BTreeSet<Option<&str>> = BTreeSet::new();
+    functions.insert(None);
+    functions.extend(old.receive.keys().map(|name| Some(name.as_str())));
+    functions.extend(new.receive.keys().map(|name| Some(name.as_str())));
+    for function in functions {
+        let name = Some(function.unwrap_or("init"));
+        let (old_function, new_function) = match (old.function(function), new.function(function)) {
+            (None, None) => continue,
+            (Some(_), None) => {
+                push(name, None, (Severity::Breaking, "removed"));
+                continue;
+            }
+            (None, Some(_)) => {
+                push(name, None, (Severity::NonBreaking, "added"));
+                continue;
+            }
+            (Some(old_function), Some(new_function)) => (old_function, new_function),
+        };
+        if let Some(change) = input_change(
+            old_function.parameter.as_ref(),
+            new_function.parameter.as_ref(),
+        ) {
+            push(name, Some("parameter"), change);
+        }
+        let outputs = [
+            (
+                "return value",
+                &old_function.return_value,
+                &new_function.return_value,
+            ),
+            ("error", &old_function.error, &new_function.error),
+        ];
+        for (part, old_type, new_type) in outputs {
+            if let Some(change) = output_change(old_type.as_ref(), new_type.as_ref()) {
+                push(name, Some(part), change);
+            }
+        }
+    }
+    if let Some(change) = output_change(old.event.as_ref(), new.event.as_ref()) {
+        push(None, Some("event"), change);
+    }
+    if let Some(change) = output_change(old.state.as_ref(), new.state.as_ref()) {
+        push(None, Some("state"), change);
+    }
+}
+
+/// The change of the type of a value passed to the contract, if any.
+fn input_change(old: Option<&Type>, new: Option<&Type>) -> Option<(Severity, &'static str)> {
+    match (old, new) {
+        (None, None) => None,
+        (None, Some(_)) => Some((Severity::NonBreaking, "added")),
+        (Some(_), None) => Some((Severity::Breaking, "removed")),
+        (Some(old), Some(new)) if same_type(old, new) => None,
+        (Some(old), Some(new)) if extends(old, new) => Some((Severity::NonBreaking, "extended")),
+        (Some(_), Some(_)) => Some((Severity::Breaking, "changed")),
+    }
+}
+
+/// The change of the type of a value produced by the contract, if any.
+fn output_change(old: Option<&Type>, new: Option<&Type>) -> Option<(Severity, &'static str)> {
+    match (old, new) {
+        (None, None) => None,
+        (None, Some(_)) => Some((Severity::NonBreaking, "added")),
+        (Some(_), None) => Some((Severity::Breaking, "removed")),
+        (Some(old), Some(new)) if same_type(old, new) => None,
+        (Some(_), Some(_)) => Some((Severity::Breaking, "changed")),
+    }
+}
+
+/// Types are compared by their serialization.
+fn same_type(old: &Type, new: &Type) -> bool {
+    to_bytes(old) == to_bytes(new)
+}
+
+/// Whether every value of type `old` is serialized the same way as a value of
+/// type `new`. This is the case if `new` only adds variants to enums without
+/// changing the size of their tag.
+fn extends(old: &Type, new: &Type) -> bool {
+    if same_type(old, new) {
+        return true;
+    }
+    match (old, new) {
+        (Type::Enum(old_variants), Type::Enum(new_variants)) => {
+            old_variants.len() <= new_variants.len()
+                && enum_tag_size(old_variants.len()) == enum_tag_size(new_variants.len())
+                && old_variants.iter().zip(new_variants.iter()).all(
+                    |((old_name, old_fields), (new_name, new_fields))| {
+                        old_name == new_name && fields_extend(old_fields, new_fields)
+                    },
+                )
+        }
+        (Type::TaggedEnum(old_variants), Type::TaggedEnum(new_variants)) => {
+            old_variants.iter().all(|(tag, (old_name, old_fields))| {
+                new_variants.get(tag).is_some_and(|(new_name, new_fields)| {
+                    old_name == new_name && fields_extend(old_fields, new_fields)
+                })
+            })
+        }
+        (Type::Struct(old_fields), Type::Struct(new_fields)) => {
+            fields_extend(old_fields, new_fields)
+        }
+        (Type::Pair(old_left, old_right), Type::Pair(new_left, new_right)) => {
+            extends(old_left, new_left) && extends(old_right, new_right)
+        }
+        (Type::List(old_len, old_elem), Type::List(new_len, new_elem))
+        | (Type::Set(old_len, old_elem), Type::Set(new_len, new_elem)) => {
+            old_len == new_len && extends(old_elem, new_elem)
+        }
+        (Type::Map(old_len, old_key, old_value), Type::Map(new_len, new_key, new_value)) => {
+            old_len == new_len && extends(old_key, new_key) && extends(old_value, new_value)
+        }
+        (Type::Array(old_len, old_elem), Type::Array(new_len, new_elem)) => {
+            old_len == new_len && extends(old_elem, new_elem)
+        }
+        _ => false,
+    }
+}
+
+/// Whether the fields `new` extend the fields `old`: they have the same names
+/// in the same order, and the type of each field extends the old one.
+fn fields_extend(old: &Fields, new: &Fields) -> bool {
+    match (old, new) {
+        (Fields::None, Fields::None) => true,
+        (Fields::Unnamed(old_fields), Fields::Unnamed(new_fields)) => {
+            old_fields.len() == new_fields.len()
+                && old_fields
+                    .iter()
+                    .zip(new_fields.iter())
+                    .all(|(old, new)| extends(old, new))
+        }
+        (Fields::Named(old_fields), Fields::Named(new_fields)) => {
+            old_fields.len() == new_fields.len()
+                && old_fields.iter().zip(new_fields.iter()).all(
+                    |((old_name, old), (new_name, new))| old_name == new_name && extends(old, new),
+                )
+        }
+        _ => false,
+    }
+}
+
+impl Compatibility {
+    fn print(&self) {
+        if self.findings.is_empty() {
+            eprintln!("The schemas of the contracts did not change.");
+        }
+        for finding in self.findings.iter() {
+            let severity = match finding.severity {
+                Severity::Breaking => Color::Red.bold().paint("breaking"),
+                Severity::NonBreaking => Color::Green.paint("compatible"),
+            };
+            eprintln!("  - [{}] {}", severity, finding.describe());
+        }
+        if self.compatible {
+            eprintln!(
+                "\n{}",
+                Color::Green
+                    .bold()
+                    .paint("The new schema is compatible with the old one.")
+            );
+        } else {
+            let breaking = self
+                .findings
+                .iter()
+                .filter(|finding| finding.severity == Severity::Breaking)
+                .count();
+            eprintln!(
+                "\n{}",
+                Style::new().bold().paint(format!(
+                    "Found {} breaking change(s). Existing users of the contracts, such as \
+                     frontends, may no longer be able to interact with them.",
+                    breaking
+                ))
+            );
+        }
+    }
+}
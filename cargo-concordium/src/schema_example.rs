@@ -0,0 +1,304 @@
+//! Generation of example values of the parameters of contract functions, for
+//! `cargo concordium schema-example`.
+//!
+//! An example is generated as the binary serialization of a value of the type,
+//! which is then converted to JSON using the schema. The JSON is therefore in
+//! the representation accepted by `--parameter-json`, and is checked to
+//! serialize to the same bytes again.
+use crate::contract_schema::{contract_schemas, enum_tag_size};
+use anyhow::Context;
+use concordium_base::contracts_common::schema::{Fields, SizeLength, Type, VersionedModuleSchema};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use serde_json::{Map, Value};
+
+/// The timestamp used in deterministic examples, 2024-01-01T00:00:00Z.
+const EXAMPLE_TIMESTAMP: u64 = 1_704_067_200_000;
+/// The latest timestamp used in random examples, 2100-01-01T00:00:00Z.
+const MAX_RANDOM_TIMESTAMP: u64 = 4_102_444_800_000;
+/// The maximal number of elements of random lists, sets and maps.
+const MAX_RANDOM_ELEMENTS: usize = 3;
+/// The maximal length of random strings and byte lists.
+const MAX_RANDOM_LENGTH: usize = 12;
+
+/// Generates the binary serialization of values. Values are deterministic,
+/// using the first variant of enums and a single element in collections,
+/// unless generated from a seed.
+struct Generator {
+    rng: Option<SmallRng>,
+}
+
+impl Generator {
+    /// Pick `default` for deterministic values, or a value from `random`.
+    fn pick<T>(&mut self, default: T, random: impl FnOnce(&mut SmallRng) -> T) -> T {
+        match self.rng.as_mut() {
+            Some(rng) => random(rng),
+            None => default,
+        }
+    }
+
+    fn value(&mut self, ty: &Type, out: &mut Vec<u8>) -> anyhow::Result<()> {
+        match ty {
+            Type::Unit => {}
+            Type::Bool => out.push(self.pick(0, |rng| rng.gen_range(0..=1))),
+            Type::U8 => out.push(self.pick(1, |rng| rng.gen())),
+            Type::U16 => out.extend(self.pick(1u16, |rng| rng.gen()).to_le_bytes()),
+            Type::U32 => out.extend(self.pick(1u32, |rng| rng.gen()).to_le_bytes()),
+            Type::U64 => out.extend(self.pick(1u64, |rng| rng.gen()).to_le_bytes()),
+            Type::U128 => out.extend(self.pick(1u128, |rng| rng.gen()).to_le_bytes()),
+            Type::I8 => out.extend(self.pick(-1i8, |rng| rng.gen()).to_le_bytes()),
+            Type::I16 => out.extend(self.pick(-1i16, |rng| rng.gen()).to_le_bytes()),
+            Type::I32 => out.extend(self.pick(-1i32, |rng| rng.gen()).to_le_bytes()),
+            Type::I64 => out.extend(self.pick(-1i64, |rng| rng.gen()).to_le_bytes()),
+            Type::I128 => out.extend(self.pick(-1i128, |rng| rng.gen()).to_le_bytes()),
+            // One CCD in micro CCD.
+            Type::Amount => out.extend(self.pick(1_000_000u64, |rng| rng.gen()).to_le_bytes()),
+            Type::AccountAddress => out.extend(self.pick([0u8; 32], |rng| rng.gen())),
+            Type::ContractAddress => {
+                out.extend(self.pick(0u64, |rng| rng.gen()).to_le_bytes());
+                out.extend(self.pick(0u64, |rng| rng.gen()).to_le_bytes());
+            }
+            Type::Timestamp => out.extend(
+                self.pick(EXAMPLE_TIMESTAMP, |rng| {
+                    rng.gen_range(0..=MAX_RANDOM_TIMESTAMP)
+                })
+                .to_le_bytes(),
+            ),
+            // One hour in milliseconds, or up to a year.
+            Type::Duration => out.extend(
+                self.pick(3_600_000u64, |rng| rng.gen_range(0..31_536_000_000))
+                    .to_le_bytes(),
+            ),
+            Type::Pair(left, right) => {
+                self.value(left, out)?;
+                self.value(right, out)?;
+            }
+            Type::List(len, elem) => {
+                let count = self.element_count();
+                write_length(len, count, out);
+                for _ in 0..count {
+                    self.value(elem, out)?;
+                }
+            }
+            Type::Set(len, elem) => {
+                let elements = self.distinct_values(|generator, out| {
+                    generator.value(elem, out)?;
+                    Ok(out.len())
+                })?;
+                write_length(len, elements.len(), out);
+                elements.into_iter().for_each(|element| out.extend(element));
+            }
+            // Keys are compared by the serialization of the key, which is followed by
+            // the value.
+            Type::Map(len, key, value) => {
+                let entries = self.distinct_values(|generator, out| {
+                    generator.value(key, out)?;
+                    let key_len = out.len();
+                    generator.value(value, out)?;
+                    Ok(key_len)
+                })?;
+                write_length(len, entries.len(), out);
+                entries.into_iter().for_each(|entry| out.extend(entry));
+            }
+            Type::Array(len, elem) => {
+                for _ in 0..*len {
+                    self.value(elem, out)?;
+                }
+            }
+            Type::Struct(fields) => self.fields(fields, out)?,
+            Type::Enum(variants) => {
+                anyhow::ensure!(
+                    !variants.is_empty(),
+                    "The type contains an enum without variants, which has no values."
+                );
+                let index = self.pick(0, |rng| rng.gen_range(0..variants.len()));
+                write_length(&enum_tag_size(variants.len()), index, out);
+                self.fields(&variants[index].1, out)?;
+            }
+            Type::TaggedEnum(variants) => {
+                anyhow::ensure!(
+                    !variants.is_empty(),
+                    "The type contains an enum without variants, which has no values."
+                );
+                let index = self.pick(0, |rng| rng.gen_range(0..variants.len()));
+                let (tag, (_, fields)) = variants
+                    .iter()
+                    .nth(index)
+                    .context("The variant index is out of range.")?;
+                out.push(*tag);
+                self.fields(fields, out)?;
+            }
+            Type::String(len) => {
+                let string: String = self.pick("example".into(), |rng| {
+                    let length = rng.gen_range(0..=MAX_RANDOM_LENGTH);
+                    rng.sample_iter(rand::distributions::Alphanumeric)
+                        .take(length)
+                        .map(char::from)
+                        .collect()
+                });
+                write_length(len, string.len(), out);
+                out.extend(string.into_bytes());
+            }
+            Type::ContractName(len) => {
+                let name = "init_example";
+                write_length(len, name.len(), out);
+                out.extend(name.as_bytes());
+            }
+            Type::ReceiveName(len) => {
+                let name = "example.receive";
+                write_length(len, name.len(), out);
+                out.extend(name.as_bytes());
+            }
+            Type::ULeb128(_) => {
+                // A single byte fits any number of bytes allowed.
+                out.push(self.pick(1, |rng| rng.gen_range(0..0x80)));
+            }
+            Type::ILeb128(_) => {
+                // A single byte with the sign bit 0x40 set is a negative number.
+                out.push(self.pick(0x7f, |rng| rng.gen_range(0..0x80)));
+            }
+            Type::ByteList(len) => {
+                let length = self.pick(4, |rng| rng.gen_range(0..=MAX_RANDOM_LENGTH));
+                write_length(len, length, out);
+                for i in 0..length {
+                    out.push(self.pick(i as u8, |rng| rng.gen()));
+                }
+            }
+            Type::ByteArray(len) => {
+                for i in 0..*len {
+                    out.push(self.pick(i as u8, |rng| rng.gen()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn fields(&mut self, fields: &Fields, out: &mut Vec<u8>) -> anyhow::Result<()> {
+        match fields {
+            Fields::Named(fields) => {
+                for (_, ty) in fields {
+                    self.value(ty, out)?;
+                }
+            }
+            Fields::Unnamed(fields) => {
+                for ty in fields {
+                    self.value(ty, out)?;
+                }
+            }
+            Fields::None => {}
+        }
+        Ok(())
+    }
+
+    /// The number of elements of a list.
+    fn element_count(&mut self) -> usize {
+        self.pick(1, |rng| rng.gen_range(0..=MAX_RANDOM_ELEMENTS))
+    }
+
+    /// Generate the elements of a set or the entries of a map, using `element`,
+    /// which writes an element and returns the length of the part that has to
+    /// be distinct. Duplicates are dropped, since a type such as `Unit` has
+    /// only a single value, and the elements are ordered by that part.
+    fn distinct_values(
+        &mut self,
+        mut element: impl FnMut(&mut Self, &mut Vec<u8>) -> anyhow::Result<usize>,
+    ) -> anyhow::Result<Vec<Vec<u8>>> {
+        let count = self.element_count();
+        let mut elements: Vec<(Vec<u8>, Vec<u8>)> = Vec::with_capacity(count);
+        for _ in 0..count {
+            let mut bytes = Vec::new();
+            let key_len = element(self, &mut bytes)?;
+            let key = bytes[..key_len].to_vec();
+            if !elements.iter().any(|(existing, _)| *existing == key) {
+                elements.push((key, bytes));
+            }
+        }
+        elements.sort_by(|(left, _), (right, _)| left.cmp(right));
+        Ok(elements.into_iter().map(|(_, bytes)| bytes).collect())
+    }
+}
+
+fn write_length(len: &SizeLength, length: usize, out: &mut Vec<u8>) {
+    match len {
+        SizeLength::U8 => out.push(length as u8),
+        SizeLength::U16 => out.extend((length as u16).to_le_bytes()),
+        SizeLength::U32 => out.extend((length as u32).to_le_bytes()),
+        SizeLength::U64 => out.extend((length as u64).to_le_bytes()),
+    }
+}
+
+/// Generate an example JSON value of the type, and check that it serializes
+/// to the bytes it was generated from.
+fn example(generator: &mut Generator, ty: &Type) -> anyhow::Result<Value> {
+    let mut bytes = Vec::new();
+    generator.value(ty, &mut bytes)?;
+    let json = ty
+        .to_json_string_pretty(&bytes)
+        .map_err(|_| anyhow::anyhow!("Could not convert the example to JSON."))?;
+    let json: Value = serde_json::from_str(&json).context("Could not parse the example.")?;
+    let mut serialized = Vec::new();
+    ty.serial_value_into(&json, &mut serialized)
+        .context("Could not serialize the example using the schema.")?;
+    anyhow::ensure!(
+        serialized == bytes,
+        "The example does not serialize to the value it was generated from."
+    );
+    Ok(json)
+}
+
+/// Generate `count` examples of the parameter of each function of the
+/// contracts in the schema, or only of the given contract. The examples are
+/// random if a seed is given, and deterministic otherwise. The result is an
+/// object mapping contract names to the examples of their `init` function and
+/// `entrypoints`. Functions without a parameter type are omitted.
+pub(crate) fn schema_examples(
+    schema: &VersionedModuleSchema,
+    contract: Option<&str>,
+    seed: Option<u64>,
+    count: usize,
+) -> anyhow::Result<Value> {
+    let mut contracts = contract_schemas(schema);
+    if let Some(contract) = contract {
+        anyhow::ensure!(
+            contracts.contains_key(contract),
+            "The schema has no contract named '{}'.",
+            contract
+        );
+        contracts.retain(|name, _| name == contract);
+    }
+    let mut generator = Generator {
+        rng: seed.map(SmallRng::seed_from_u64),
+    };
+    let mut examples = |ty: &Type, function: &str, contract: &str| -> anyhow::Result<Value> {
+        (0..count)
+            .map(|_| example(&mut generator, ty))
+            .collect::<anyhow::Result<Vec<_>>>()
+            .map(Value::Array)
+            .with_context(|| {
+                format!(
+                    "Could not generate an example parameter of function '{}' of contract '{}'.",
+                    function, contract
+                )
+            })
+    };
+
+    let mut output = Map::new();
+    for (name, contract_schema) in contracts.iter() {
+        let mut contract_examples = Map::new();
+        if let Some(ty) = contract_schema
+            .init
+            .as_ref()
+            .and_then(|init| init.parameter.as_ref())
+        {
+            contract_examples.insert("init".into(), examples(ty, "init", name)?);
+        }
+        let mut entrypoints = Map::new();
+        for (entrypoint, function_schema) in contract_schema.receive.iter() {
+            if let Some(ty) = function_schema.parameter.as_ref() {
+                entrypoints.insert(entrypoint.clone(), examples(ty, entrypoint, name)?);
+            }
+        }
+        contract_examples.insert("entrypoints".into(), Value::Object(entrypoints));
+        output.insert(name.clone(), Value::Object(contract_examples));
+    }
+    Ok(Value::Object(output))
+}
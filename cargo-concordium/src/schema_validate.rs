@@ -0,0 +1,297 @@
+//! Validation of values in JSON against a type in a schema, for
+//! `cargo concordium schema-validate`, without running the contract.
+//!
+//! Composite types are checked here so that every mismatch can be reported
+//! with its path in the JSON, e.g., `$.tokens[2].amount`. Other values are
+//! checked by serializing them with the schema.
+use ansi_term::Color;
+use anyhow::Context;
+use concordium_base::contracts_common::schema::{Fields, SizeLength, Type};
+use serde_json::Value;
+use std::path::PathBuf;
+
+/// A value that does not match the type at the given path.
+struct Mismatch {
+    path: String,
+    message: String,
+}
+
+/// The largest length allowed by a length prefix.
+fn max_length(len: &SizeLength) -> u64 {
+    match len {
+        SizeLength::U8 => u8::MAX.into(),
+        SizeLength::U16 => u16::MAX.into(),
+        SizeLength::U32 => u32::MAX.into(),
+        SizeLength::U64 => u64::MAX,
+    }
+}
+
+/// Extend the path with a field name, using the bracket notation for names
+/// that are not identifiers.
+fn field_path(path: &str, name: &str) -> String {
+    let identifier = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if identifier {
+        format!("{}.{}", path, name)
+    } else {
+        format!("{}[{}]", path, Value::from(name))
+    }
+}
+
+fn index_path(path: &str, index: usize) -> String {
+    format!("{}[{}]", path, index)
+}
+
+/// A description of the JSON representation of values of types that are not
+/// checked by `Validator::value`.
+fn describe(ty: &Type) -> String {
+    match ty {
+        Type::Bool => "a boolean".into(),
+        Type::U8 | Type::U16 | Type::U32 | Type::U64 => {
+            format!("an unsigned integer that fits in {}", bits(ty))
+        }
+        Type::I8 | Type::I16 | Type::I32 | Type::I64 => {
+            format!("a signed integer that fits in {}", bits(ty))
+        }
+        Type::U128 => "a string with an unsigned 128-bit integer".into(),
+        Type::I128 => "a string with a signed 128-bit integer".into(),
+        Type::ULeb128(max) => format!(
+            "a string with an unsigned integer of at most {} LEB128 bytes",
+            max
+        ),
+        Type::ILeb128(max) => format!(
+            "a string with a signed integer of at most {} LEB128 bytes",
+            max
+        ),
+        Type::Amount => "a string with an amount of micro CCD".into(),
+        Type::AccountAddress => "a string with an account address in base58check".into(),
+        Type::ContractAddress => {
+            "an object with the fields 'index' and 'subindex' of a contract address".into()
+        }
+        Type::Timestamp => "a string with a timestamp in RFC3339 format".into(),
+        Type::Duration => "a string with a duration, e.g., '10d 1h 42m 7s 120ms'".into(),
+        Type::String(len) => format!("a string of at most {} bytes", max_length(len)),
+        Type::ContractName(_) => "an object with the field 'contract'".into(),
+        Type::ReceiveName(_) => "an object with the fields 'contract' and 'func'".into(),
+        Type::ByteList(len) => format!("a hex string of at most {} bytes", max_length(len)),
+        Type::ByteArray(len) => format!("a hex string of {} bytes", len),
+        _ => "a value of the type".into(),
+    }
+}
+
+fn bits(ty: &Type) -> &'static str {
+    match ty {
+        Type::U8 | Type::I8 => "8 bits",
+        Type::U16 | Type::I16 => "16 bits",
+        Type::U32 | Type::I32 => "32 bits",
+        _ => "64 bits",
+    }
+}
+
+#[derive(Default)]
+struct Validator {
+    mismatches: Vec<Mismatch>,
+}
+
+impl Validator {
+    fn mismatch(&mut self, path: &str, message: String) {
+        self.mismatches.push(Mismatch {
+            path: path.to_string(),
+            message,
+        });
+    }
+
+    fn expected(&mut self, path: &str, expected: impl std::fmt::Display) {
+        self.mismatch(path, format!("expected {}", expected));
+    }
+
+    /// The elements of an array with at most `max` elements, or `None` if the
+    /// value is not such an array.
+    fn array<'a>(
+        &mut self,
+        path: &str,
+        value: &'a Value,
+        max: u64,
+        expected: &str,
+    ) -> Option<&'a Vec<Value>> {
+        match value {
+            Value::Array(elements) if elements.len() as u64 <= max => Some(elements),
+            Value::Array(elements) => {
+                self.expected(
+                    path,
+                    format!(
+                        "{} with at most {} elements, found {}",
+                        expected,
+                        max,
+                        elements.len()
+                    ),
+                );
+                None
+            }
+            _ => {
+                self.expected(path, expected);
+                None
+            }
+        }
+    }
+
+    fn value(&mut self, path: &str, ty: &Type, value: &Value) {
+        match ty {
+            Type::Pair(left, right) => self.pair(path, left, right, value),
+            Type::List(len, elem) | Type::Set(len, elem) => {
+                if let Some(elements) = self.array(path, value, max_length(len), "an array") {
+                    for (i, element) in elements.iter().enumerate() {
+                        self.value(&index_path(path, i), elem, element);
+                    }
+                }
+            }
+            // Maps are represented by an array of key-value pairs.
+            Type::Map(len, key, val) => {
+                let expected = "an array of key-value pairs";
+                if let Some(entries) = self.array(path, value, max_length(len), expected) {
+                    for (i, entry) in entries.iter().enumerate() {
+                        self.pair(&index_path(path, i), key, val, entry);
+                    }
+                }
+            }
+            Type::Array(len, elem) => match value {
+                Value::Array(elements) if elements.len() as u64 == u64::from(*len) => {
+                    for (i, element) in elements.iter().enumerate() {
+                        self.value(&index_path(path, i), elem, element);
+                    }
+                }
+                _ => self.expected(path, format!("an array of {} elements", len)),
+            },
+            Type::Struct(fields) => self.fields(path, fields, value),
+            Type::Enum(variants) => self.variant(path, variants.iter(), value),
+            Type::TaggedEnum(variants) => self.variant(path, variants.values(), value),
+            _ => {
+                if ty.serial_value_into(value, &mut Vec::new()).is_err() {
+                    self.expected(path, describe(ty));
+                }
+            }
+        }
+    }
+
+    fn pair(&mut self, path: &str, left: &Type, right: &Type, value: &Value) {
+        match value {
+            Value::Array(elements) if elements.len() == 2 => {
+                self.value(&index_path(path, 0), left, &elements[0]);
+                self.value(&index_path(path, 1), right, &elements[1]);
+            }
+            _ => self.expected(path, "an array of 2 elements"),
+        }
+    }
+
+    fn fields(&mut self, path: &str, fields: &Fields, value: &Value) {
+        match fields {
+            Fields::Named(fields) => {
+                let Value::Object(object) = value else {
+                    self.expected(path, "an object");
+                    return;
+                };
+                for (name, ty) in fields {
+                    let field = field_path(path, name);
+                    match object.get(name) {
+                        Some(value) => self.value(&field, ty, value),
+                        None => self.mismatch(&field, "missing field".into()),
+                    }
+                }
+                for name in object.keys() {
+                    if !fields.iter().any(|(field, _)| field == name) {
+                        self.mismatch(&field_path(path, name), "unexpected field".into());
+                    }
+                }
+            }
+            Fields::Unnamed(fields) => {
+                let expected = format!("an array of {} elements", fields.len());
+                let Value::Array(elements) = value else {
+                    self.expected(path, expected);
+                    return;
+                };
+                if elements.len() != fields.len() {
+                    self.expected(path, format!("{}, found {}", expected, elements.len()));
+                    return;
+                }
+                for (i, (ty, element)) in fields.iter().zip(elements.iter()).enumerate() {
+                    self.value(&index_path(path, i), ty, element);
+                }
+            }
+            Fields::None => {
+                if Type::Struct(Fields::None)
+                    .serial_value_into(value, &mut Vec::new())
+                    .is_err()
+                {
+                    self.expected(path, "an empty array");
+                }
+            }
+        }
+    }
+
+    /// An enum is represented by an object with the name of the variant as its
+    /// only field.
+    fn variant<'a>(
+        &mut self,
+        path: &str,
+        mut variants: impl Iterator<Item = &'a (String, Fields)>,
+        value: &Value,
+    ) {
+        let expected = "an object with the name of a variant as its only field";
+        let Value::Object(object) = value else {
+            self.expected(path, expected);
+            return;
+        };
+        let mut entries = object.iter();
+        let (Some((name, fields_value)), None) = (entries.next(), entries.next()) else {
+            self.expected(path, expected);
+            return;
+        };
+        match variants.find(|(variant, _)| variant == name) {
+            Some((_, fields)) => self.fields(&field_path(path, name), fields, fields_value),
+            None => self.mismatch(path, format!("unknown variant '{}'", name)),
+        }
+    }
+}
+
+/// Check the JSON in each of the files against the type, and print every
+/// mismatch with its path. Fails if any of the files does not match.
+pub(crate) fn validate_files(ty: &Type, inputs: &[PathBuf]) -> anyhow::Result<()> {
+    let mut invalid = 0;
+    for input in inputs {
+        let source = std::fs::read(input)
+            .with_context(|| format!("Could not read input file {}.", input.display()))?;
+        let mut validator = Validator::default();
+        match serde_json::from_slice::<Value>(&source) {
+            Ok(json) => validator.value("$", ty, &json),
+            Err(e) => validator.mismatch("$", format!("invalid JSON: {}", e)),
+        }
+        if validator.mismatches.is_empty() {
+            eprintln!(
+                "{} {}",
+                Color::Green.bold().paint("Valid:"),
+                input.display()
+            );
+        } else {
+            invalid += 1;
+            eprintln!(
+                "{} {}",
+                Color::Red.bold().paint("Invalid:"),
+                input.display()
+            );
+            for mismatch in validator.mismatches {
+                eprintln!("  - at {}: {}", mismatch.path, mismatch.message);
+            }
+        }
+    }
+    if invalid > 0 {
+        anyhow::bail!(
+            "{} of {} file(s) do not match the schema.",
+            invalid,
+            inputs.len()
+        );
+    }
+    Ok(())
+}
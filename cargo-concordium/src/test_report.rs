@@ -0,0 +1,292 @@
+//! Reports of the results of `cargo concordium test` in formats read by CI
+//! systems.
+use anyhow::Context;
+use serde::Serialize;
+use std::{fs, path::PathBuf, str::FromStr, time::Duration};
+
+/// The format of a test report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ReportFormat {
+    /// The JUnit XML format.
+    Junit,
+    /// A JSON document with the suites and their tests.
+    Json,
+}
+
+/// A test report to write, given as `<format>=<path>`.
+#[derive(Debug, Clone)]
+pub(crate) struct ReportTarget {
+    pub format: ReportFormat,
+    pub path: PathBuf,
+}
+
+impl FromStr for ReportTarget {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (format, path) = s
+            .split_once('=')
+            .context("Expected a report of the form '<format>=<path>'.")?;
+        let format = match format {
+            "junit" => ReportFormat::Junit,
+            "json" => ReportFormat::Json,
+            _ => anyhow::bail!(
+                "Unsupported report format '{}'. Use 'junit' or 'json'.",
+                format
+            ),
+        };
+        anyhow::ensure!(!path.is_empty(), "The path of the report is empty.");
+        Ok(Self {
+            format,
+            path: PathBuf::from(path),
+        })
+    }
+}
+
+/// The status of a test after running it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum TestStatus {
+    Passed,
+    Failed,
+    Ignored,
+}
+
+/// The result of a single test.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TestCase {
+    pub name: String,
+    pub status: TestStatus,
+    /// The reason the test failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The seed of the random number generator, if the test used it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// The time spent running the test, if it is known.
+    #[serde(
+        rename = "durationSeconds",
+        serialize_with = "serialize_duration",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub duration: Option<Duration>,
+    /// The debug events emitted by the test.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub debug_events: Vec<String>,
+}
+
+fn serialize_duration<S: serde::Serializer>(
+    duration: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match duration {
+        Some(duration) => serializer.serialize_f64(duration.as_secs_f64()),
+        None => serializer.serialize_none(),
+    }
+}
+
+impl TestCase {
+    pub(crate) fn failed(&self) -> bool {
+        self.status == TestStatus::Failed
+    }
+}
+
+/// The tests run in one phase of `cargo concordium test`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TestSuite {
+    pub name: &'static str,
+    pub tests: Vec<TestCase>,
+}
+
+impl TestSuite {
+    fn count(&self, status: TestStatus) -> usize {
+        self.tests
+            .iter()
+            .filter(|test| test.status == status)
+            .count()
+    }
+
+    fn duration(&self) -> Duration {
+        self.tests.iter().filter_map(|test| test.duration).sum()
+    }
+}
+
+/// The report in the JSON format.
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    suites: &'a [TestSuite],
+}
+
+/// Write the results of the test suites to each of the reports.
+pub(crate) fn write_reports(targets: &[ReportTarget], suites: &[TestSuite]) -> anyhow::Result<()> {
+    for target in targets {
+        let contents = match target.format {
+            ReportFormat::Junit => junit(suites),
+            ReportFormat::Json => serde_json::to_string_pretty(&JsonReport { suites })
+                .context("Could not output the test report as JSON.")?,
+        };
+        if let Some(out_dir) = target.path.parent() {
+            fs::create_dir_all(out_dir).context("Unable to create directory for the report.")?;
+        }
+        fs::write(&target.path, contents).with_context(|| {
+            format!(
+                "Could not write the test report to {}.",
+                target.path.display()
+            )
+        })?;
+        eprintln!("Test report written to {}.", target.path.display());
+    }
+    Ok(())
+}
+
+/// The results in the JUnit XML format.
+fn junit(suites: &[TestSuite]) -> String {
+    let num_tests: usize = suites.iter().map(|suite| suite.tests.len()).sum();
+    let num_failures: usize = suites
+        .iter()
+        .map(|suite| suite.count(TestStatus::Failed))
+        .sum();
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str(&format!(
+        "<testsuites name=\"cargo-concordium\" tests=\"{}\" failures=\"{}\">\n",
+        num_tests, num_failures
+    ));
+    for suite in suites {
+        out.push_str(&format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"0\" skipped=\"{}\" \
+             time=\"{:.3}\">\n",
+            suite.name,
+            suite.tests.len(),
+            suite.count(TestStatus::Failed),
+            suite.count(TestStatus::Ignored),
+            suite.duration().as_secs_f64()
+        ));
+        for test in suite.tests.iter() {
+            out.push_str(&format!(
+                "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\">\n",
+                xml_escape(&test.name),
+                suite.name,
+                test.duration.unwrap_or_default().as_secs_f64()
+            ));
+            if let Some(seed) = test.seed {
+                out.push_str(&format!(
+                    "      <properties>\n        <property name=\"seed\" value=\"{}\"/>\n      \
+                     </properties>\n",
+                    seed
+                ));
+            }
+            match test.status {
+                TestStatus::Passed => {}
+                TestStatus::Failed => {
+                    let error = test.error.as_deref().unwrap_or_default();
+                    let message = error.lines().next().unwrap_or_default();
+                    out.push_str(&format!(
+                        "      <failure message=\"{}\">{}</failure>\n",
+                        xml_escape(message),
+                        xml_escape(error)
+                    ));
+                }
+                TestStatus::Ignored => out.push_str("      <skipped/>\n"),
+            }
+            if !test.debug_events.is_empty() {
+                out.push_str(&format!(
+                    "      <system-out>{}</system-out>\n",
+                    xml_escape(&test.debug_events.join("\n"))
+                ));
+            }
+            out.push_str("    </testcase>\n");
+        }
+        out.push_str("  </testsuite>\n");
+    }
+    out.push_str("</testsuites>\n");
+    out
+}
+
+/// Escape text for use in XML attributes and elements.
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Control characters other than whitespace are not allowed in XML.
+            c if c.is_control() && !matches!(c, '\n' | '\r' | '\t') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Collects the results of the tests from the output of the test harness used
+/// by `cargo test`, in its default format.
+#[derive(Default)]
+pub(crate) struct HarnessOutput {
+    tests: Vec<TestCase>,
+    /// The name and output of the test whose output is being read.
+    output: Option<(String, Vec<String>)>,
+}
+
+impl HarnessOutput {
+    /// Read a line of the output.
+    pub(crate) fn line(&mut self, line: &str) {
+        if let Some(rest) = line.strip_prefix("test ") {
+            if let Some((name, result)) = rest.rsplit_once(" ... ") {
+                let status = match result {
+                    "ok" => TestStatus::Passed,
+                    "FAILED" => TestStatus::Failed,
+                    _ if result.starts_with("ignored") => TestStatus::Ignored,
+                    _ => return,
+                };
+                self.tests.push(TestCase {
+                    name: name.to_string(),
+                    status,
+                    error: None,
+                    seed: None,
+                    duration: None,
+                    debug_events: Vec::new(),
+                });
+                return;
+            }
+        }
+        // The output of a test is printed after all the tests of the binary have
+        // run, in a block headed by `---- <name> stdout ----`. The output of a failed
+        // test is its error.
+        if let Some(name) = line
+            .strip_prefix("---- ")
+            .and_then(|rest| rest.strip_suffix(" stdout ----"))
+        {
+            self.finish_output();
+            self.output = Some((name.to_string(), Vec::new()));
+        } else if line == "failures:" || line == "successes:" || line.starts_with("test result:") {
+            self.finish_output();
+        } else if let Some((_, output)) = self.output.as_mut() {
+            output.push(line.to_string());
+        }
+    }
+
+    fn finish_output(&mut self) {
+        let Some((name, output)) = self.output.take() else {
+            return;
+        };
+        let output = output.join("\n").trim().to_string();
+        if let Some(test) = self
+            .tests
+            .iter_mut()
+            .rev()
+            .find(|test| test.name == name && test.failed())
+        {
+            test.error = Some(output);
+        }
+    }
+
+    /// The results of the tests.
+    pub(crate) fn finish(mut self) -> Vec<TestCase> {
+        self.finish_output();
+        self.tests
+    }
+}
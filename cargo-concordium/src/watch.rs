@@ -0,0 +1,126 @@
+//! Building and testing a package again whenever its sources change.
+//!
+//! The files of the package are polled for changes, so no support from the
+//! operating system is needed. The same files are watched as those included in
+//! the archive of a verifiable build, so files ignored by Git do not trigger a
+//! new run.
+use crate::build::{get_crate_metadata, package_files};
+use ansi_term::{Color, Style};
+use anyhow::Context;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+/// The time between two checks of the files for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// The time the files must be unchanged before running again. Editors may
+/// write a file in several steps, and several files may be saved at once.
+const SETTLE_TIME: Duration = Duration::from_millis(200);
+
+/// The modification time and size of each file of the package.
+type Snapshot = BTreeMap<PathBuf, (Option<SystemTime>, u64)>;
+
+/// Call `run` and call it again whenever the sources of the package change,
+/// until the process is stopped. If `workspace` is set, the sources of the
+/// whole workspace are watched. Errors returned by `run` are printed, and do
+/// not stop watching.
+pub(crate) fn watch_package(
+    cargo_args: &[String],
+    workspace: bool,
+    mut run: impl FnMut() -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let (metadata, _) = get_crate_metadata(cargo_args)?;
+    let root = if workspace {
+        metadata.workspace_root.as_std_path().to_path_buf()
+    } else {
+        // The manifest of a virtual workspace has no package to build.
+        let package = metadata.root_package().context(
+            "Unable to determine package. Select the packages of a workspace with `--workspace` \
+             or `-p <package>`.",
+        )?;
+        package
+            .manifest_path
+            .parent()
+            .context("Unable to get package root path.")?
+            .as_std_path()
+            .to_path_buf()
+    };
+    // The build output may be inside the package if it is not ignored by Git.
+    let target_dir = metadata.target_directory.clone().into_std_path_buf();
+
+    loop {
+        if let Err(err) = run() {
+            eprintln!("{} {:#}", Color::Red.bold().paint("Error:"), err);
+        }
+        // Files written by the run itself, such as schema files, do not trigger
+        // another run.
+        let files = snapshot(&root, &target_dir);
+        eprintln!(
+            "\n{}",
+            Style::new().bold().paint(format!(
+                "Watching {} for changes. Press Ctrl-C to stop.",
+                root.display()
+            ))
+        );
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let mut next = snapshot(&root, &target_dir);
+            if next == files {
+                continue;
+            }
+            loop {
+                thread::sleep(SETTLE_TIME);
+                let settled = snapshot(&root, &target_dir);
+                if settled == next {
+                    break;
+                }
+                next = settled;
+            }
+            for path in changed_files(&files, &next) {
+                eprintln!("Changed: {}", path.display());
+            }
+            break;
+        }
+        eprintln!("\n{}", Color::Green.bold().paint("Running again ..."));
+    }
+}
+
+/// Record the modification time and size of the files of the package,
+/// skipping those in the target directory. Files that cannot be read, for
+/// example because they were removed while walking the package, are skipped.
+fn snapshot(root: &Path, target_dir: &Path) -> Snapshot {
+    package_files(root)
+        .filter_map(Result::ok)
+        .filter(|entry| !entry.path().starts_with(target_dir))
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            if !metadata.is_file() {
+                return None;
+            }
+            Some((
+                entry.into_path(),
+                (metadata.modified().ok(), metadata.len()),
+            ))
+        })
+        .collect()
+}
+
+/// The files that were added, removed or modified between two snapshots.
+fn changed_files<'a>(before: &'a Snapshot, after: &'a Snapshot) -> Vec<&'a Path> {
+    let mut changed: Vec<_> = after
+        .iter()
+        .filter(|(path, stamp)| before.get(*path) != Some(stamp))
+        .map(|(path, _)| path.as_path())
+        .chain(
+            before
+                .keys()
+                .filter(|path| !after.contains_key(*path))
+                .map(PathBuf::as_path),
+        )
+        .collect();
+    changed.sort();
+    changed
+}